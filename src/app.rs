@@ -1,9 +1,9 @@
 use std::thread;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc;
 use std::collections::VecDeque;
 use std::time::Duration;
-use fnv::FnvHashMap;
+use fnv::{FnvHashMap, FnvHashSet};
 use chrono::Local;
 use framebuffer::{Framebuffer, KoboFramebuffer, UpdateMode};
 use view::{View, Event, EntryId, render, render_no_wait, handle_event, fill_crack};
@@ -13,9 +13,11 @@ use gesture::{GestureEvent, gesture_events};
 use helpers::{load_json, save_json};
 use device::CURRENT_DEVICE;
 use metadata::{Metadata, METADATA_FILENAME};
+use library::refresh_in_background;
 use settings::{Settings, SETTINGS_PATH};
 use view::home::Home;
 use view::reader::Reader;
+use view::details::Details;
 use font::Fonts;
 use errors::*;
 
@@ -42,9 +44,13 @@ pub fn run() -> Result<()> {
 
     let settings = load_json::<Settings, _>(path);
 
+    // A settings file that can't be parsed is left untouched, so that it can be fixed.
+    let mut settings_error = None;
+
     if let Err(ref e) = settings {
         if path.exists() {
             eprintln!("Warning: can't load settings: {}.", e);
+            settings_error = Some(e.to_string());
         }
     }
 
@@ -77,7 +83,7 @@ pub fn run() -> Result<()> {
         }
     });
 
-    let fb_rect = fb.rect();
+    let mut fb_rect = fb.rect();
 
     let fonts = Fonts::load().chain_err(|| "Can't load fonts.")?;
 
@@ -85,6 +91,14 @@ pub fn run() -> Result<()> {
     let mut history: Vec<Box<View>> = Vec::new();
     let mut view: Box<View> = Box::new(Home::new(fb_rect, &tx, &mut context)?);
 
+    if let Some(ref e) = settings_error {
+        tx.send(Event::Notify(format!("Can't load the settings: {}.", e))).unwrap();
+    }
+
+    if context.settings.import_on_startup {
+        refresh_in_background(&context.settings.library_path, &context.metadata, &tx);
+    }
+
     let mut updating = FnvHashMap::default();
 
     println!("{} is running on a Kobo {}.", APP_NAME,
@@ -99,6 +113,10 @@ pub fn run() -> Result<()> {
             Event::Gesture(ge) => {
                 match ge {
                     GestureEvent::HoldButton(ButtonCode::Power) => break,
+                    GestureEvent::Rotate { quarter_turns, .. } => {
+                        let n = CURRENT_DEVICE.rotation() - quarter_turns;
+                        tx.send(Event::Select(EntryId::SetRotation((n % 4 + 4) % 4))).unwrap();
+                    },
                     _ => {
                         handle_event(view.as_mut(), &evt, &tx, &mut bus, &mut context);
                     },
@@ -131,6 +149,32 @@ pub fn run() -> Result<()> {
                     handle_event(view.as_mut(), &Event::Invalid(info2), &tx, &mut bus, &mut context);
                 }
             },
+            Event::OpenAt(info, index) => {
+                let info2 = info.clone();
+                if let Some(r) = Reader::new(fb_rect, *info, &tx, &mut context) {
+                    history.push(view as Box<View>);
+                    view = Box::new(r) as Box<View>;
+                    tx.send(Event::GoTo(index)).unwrap();
+                } else {
+                    handle_event(view.as_mut(), &Event::Invalid(info2), &tx, &mut bus, &mut context);
+                }
+            },
+            Event::OpenDetails(path) => {
+                if let Some(d) = Details::new(fb_rect, &path, &tx, &mut context) {
+                    history.push(view as Box<View>);
+                    view = Box::new(d) as Box<View>;
+                }
+            },
+            Event::Imported(ref imported, _) => {
+                // The metadata might have changed while the library was scanned.
+                let known: FnvHashSet<PathBuf> = context.metadata.iter()
+                                                        .map(|info| info.file.path.clone())
+                                                        .collect();
+                context.metadata.extend(imported.iter()
+                                                .filter(|info| !known.contains(&info.file.path))
+                                                .cloned());
+                handle_event(view.as_mut(), &evt, &tx, &mut bus, &mut context);
+            },
             Event::Back => {
                 if let Some(v) = history.pop() {
                     view = v;
@@ -147,6 +191,26 @@ pub fn run() -> Result<()> {
                 context.monochrome = !context.monochrome;
                 tx.send(Event::Render(fb_rect, UpdateMode::Gui)).unwrap();
             },
+            Event::Select(EntryId::SetRotation(n)) if n != CURRENT_DEVICE.rotation() => {
+                let mut reader_info = None;
+                if let Some(r) = view.downcast_mut::<Reader>() {
+                    r.quit(&mut context);
+                    reader_info = Some(r.info().clone());
+                }
+                fb.set_rotation(n);
+                CURRENT_DEVICE.set_rotation(n);
+                fb_rect = fb.rect();
+                // Rebuild the views for the new dimensions.
+                history.clear();
+                view = Box::new(Home::new(fb_rect, &tx, &mut context)?);
+                if let Some(info) = reader_info {
+                    if let Some(r) = Reader::new(fb_rect, info, &tx, &mut context) {
+                        history.push(view as Box<View>);
+                        view = Box::new(r) as Box<View>;
+                    }
+                }
+                tx.send(Event::Render(fb_rect, UpdateMode::Full)).unwrap();
+            },
             Event::Select(EntryId::TakeScreenshot) => {
                 fb.save(&Local::now().format("screenshot-%Y%m%d_%H%M%S.png").to_string())?;
             },
@@ -167,5 +231,10 @@ pub fn run() -> Result<()> {
     let path = context.settings.library_path.join(METADATA_FILENAME);
     save_json(&context.metadata, path).chain_err(|| "Can't save metadata.")?;
 
+    if settings_error.is_none() {
+        let path = Path::new(SETTINGS_PATH);
+        save_json(&context.settings, path).chain_err(|| "Can't save settings.")?;
+    }
+
     Ok(())
 }
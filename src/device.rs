@@ -3,6 +3,7 @@ extern crate libc;
 use std::env;
 use std::fmt;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use unit::scale_by_dpi;
 use input::TouchProto;
 
@@ -47,6 +48,29 @@ pub struct Device {
     pub dpi: u16,
 }
 
+// Number of clockwise quarter turns applied to the screen's content.
+static ROTATION: AtomicUsize = AtomicUsize::new(0);
+
+impl Device {
+    pub fn rotation(&self) -> i8 {
+        ROTATION.load(Ordering::Relaxed) as i8
+    }
+
+    pub fn set_rotation(&self, n: i8) {
+        ROTATION.store(((n % 4 + 4) % 4) as usize, Ordering::Relaxed);
+    }
+
+    // The dimensions of the screen in the current orientation.
+    pub fn screen_dims(&self) -> (u32, u32) {
+        let (width, height) = self.dims;
+        if self.rotation() % 2 == 1 {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+}
+
 impl Default for Device {
     fn default() -> Device {
         Device {
@@ -58,10 +82,10 @@ impl Default for Device {
     }
 }
 
-lazy_static! {
-    pub static ref CURRENT_DEVICE: Device = {
-        let product = env::var("PRODUCT").unwrap_or_default();
-        match product.as_ref() {
+impl Device {
+    // The value of the PRODUCT environment variable identifies the model.
+    pub fn from_product(product: &str) -> Option<Device> {
+        Some(match product {
             "kraken" => Device {
                 model: Model::Glo,
                 proto: TouchProto::Single,
@@ -122,8 +146,15 @@ lazy_static! {
                 dims: (1080, 1440),
                 dpi: 265,
             },
-            _ => Device::default(),
-        }
+            _ => return None,
+        })
+    }
+}
+
+lazy_static! {
+    pub static ref CURRENT_DEVICE: Device = {
+        let product = env::var("PRODUCT").unwrap_or_default();
+        Device::from_product(&product).unwrap_or_default()
     };
 
 // Tuples of the form
@@ -138,7 +169,14 @@ pub static ref BAR_SIZES: HashMap<(u32, u16), (u32, u32)> =
      ((1440, 265), (104, 141)),
      ((1024, 212), ( 87, 109)),
      (( 800, 167), ( 66,  86)),
-     (( 800, 200), ( 80, 112))].iter().cloned().collect();
+     (( 800, 200), ( 80, 112)),
+     // Landscape orientations.
+     ((1404, 300), (126, 171)),
+     ((1072, 300), (124, 175)),
+     ((1080, 265), (110, 150)),
+     (( 768, 212), ( 88, 126)),
+     (( 600, 167), ( 65,  81)),
+     (( 600, 200), ( 84, 116))].iter().cloned().collect();
 }
 
 pub fn optimal_bars_setup(height: u32, dpi: u16) -> (u32, u32) {
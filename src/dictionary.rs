@@ -0,0 +1,518 @@
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use libflate::{gzip, deflate};
+use unicode_normalization::UnicodeNormalization;
+use errors::*;
+
+pub const DICTIONARIES_DIRNAME: &str = ".dictionaries";
+
+const DICTD_BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const DICTD_SHORT_NAMES: [&str; 2] = ["00databaseshort", "00-database-short"];
+
+// The gzip header flags.
+const GZIP_FHCRC: u8 = 0x02;
+const GZIP_FEXTRA: u8 = 0x04;
+const GZIP_FNAME: u8 = 0x08;
+const GZIP_FCOMMENT: u8 = 0x10;
+// An empty final deflate block, appended to a run of flushed chunks.
+const DEFLATE_END: [u8; 2] = [0x03, 0x00];
+
+#[derive(Debug, Clone)]
+struct Entry {
+    key: String,
+    offset: u64,
+    size: u64,
+}
+
+#[derive(Debug, Clone)]
+enum Format {
+    // The optional sequence gives the types of the fields shared by all the entries.
+    StarDict(Option<String>),
+    Dictd,
+}
+
+#[derive(Debug)]
+enum Data {
+    Plain(PathBuf),
+    // Dictzip files are inflated chunk by chunk.
+    Chunked(PathBuf, Chunks),
+    // Other compressed data is inflated on the first lookup.
+    Compressed(PathBuf, Option<Vec<u8>>),
+}
+
+// The random access table of a dictzip file: its data is split in chunks of the
+// same uncompressed length, which are compressed independently.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Chunks {
+    length: u64,
+    // The position of each chunk in the file, followed by the end of the last one.
+    offsets: Vec<u64>,
+}
+
+// An offline dictionary in the StarDict or dictd format.
+#[derive(Debug)]
+pub struct Dictionary {
+    pub name: String,
+    format: Format,
+    // Sorted by key.
+    entries: Vec<Entry>,
+    data: Data,
+}
+
+// Loads the dictionaries found in `dir`, skipping the ones that can't be read.
+pub fn load_dictionaries(dir: &Path) -> Vec<Dictionary> {
+    let mut dictionaries = Vec::new();
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return dictionaries,
+    };
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        let result = match path.extension().and_then(|e| e.to_str()) {
+            Some("ifo") => Dictionary::open_stardict(&path),
+            Some("index") => Dictionary::open_dictd(&path),
+            _ => continue,
+        };
+        match result {
+            Ok(dictionary) => dictionaries.push(dictionary),
+            Err(e) => eprintln!("Can't load dictionary {}: {}.", path.display(), e),
+        }
+    }
+    dictionaries.sort_by(|a, b| a.name.cmp(&b.name));
+    dictionaries
+}
+
+// Returns the names of the dictionaries that define `word`, along with the definitions.
+// Elided articles and pronouns, as in *l'homme*, are dropped if the whole word isn't found.
+pub fn define(dictionaries: &mut [Dictionary], word: &str) -> Vec<(String, String)> {
+    let mut results = Vec::new();
+    let mut candidates = vec![word];
+    if let Some(index) = word.rfind(|c| c == '\'' || c == '’') {
+        candidates.push(&word[index..]);
+    }
+    for candidate in candidates {
+        for dictionary in dictionaries.iter_mut() {
+            match dictionary.lookup(candidate) {
+                Ok(definitions) => {
+                    for definition in definitions {
+                        results.push((dictionary.name.clone(), definition));
+                    }
+                },
+                Err(e) => eprintln!("Can't look up {} in {}: {}.", candidate, dictionary.name, e),
+            }
+        }
+        if !results.is_empty() {
+            break;
+        }
+    }
+    results
+}
+
+impl Dictionary {
+    pub fn open_stardict(path: &Path) -> Result<Dictionary> {
+        let mut ifo = String::new();
+        File::open(path).and_then(|mut f| f.read_to_string(&mut ifo))
+                        .chain_err(|| "Can't read info file.")?;
+        let mut name = None;
+        let mut wide_offsets = false;
+        let mut same_type = None;
+        for line in ifo.lines() {
+            if let Some(index) = line.find('=') {
+                let (key, value) = (&line[..index], line[index+1..].trim());
+                match key {
+                    "bookname" => name = Some(value.to_string()),
+                    "idxoffsetbits" => wide_offsets = value == "64",
+                    "sametypesequence" => same_type = Some(value.to_string()),
+                    _ => (),
+                }
+            }
+        }
+        let index = read_file(&path.with_extension("idx"))
+                        .or_else(|_| inflate_file(&path.with_extension("idx.gz")))?;
+        let entries = parse_stardict_index(&index, wide_offsets)?;
+        let data = data_source(path)?;
+        Ok(Dictionary {
+            name: name.unwrap_or_else(|| file_stem(path)),
+            format: Format::StarDict(same_type),
+            entries,
+            data,
+        })
+    }
+
+    pub fn open_dictd(path: &Path) -> Result<Dictionary> {
+        let index = read_file(path)?;
+        let entries = parse_dictd_index(&String::from_utf8_lossy(&index))?;
+        let data = data_source(path)?;
+        let mut dictionary = Dictionary {
+            name: file_stem(path),
+            format: Format::Dictd,
+            entries,
+            data,
+        };
+        for short_name in &DICTD_SHORT_NAMES {
+            if let Some(text) = dictionary.lookup(short_name).ok().and_then(|d| d.into_iter().next()) {
+                // The first line might repeat the headword.
+                if let Some(name) = text.lines().map(|l| l.trim()).filter(|l| !l.is_empty() && !l.contains(short_name)).next() {
+                    dictionary.name = name.to_string();
+                }
+                break;
+            }
+        }
+        Ok(dictionary)
+    }
+
+    pub fn lookup(&mut self, word: &str) -> Result<Vec<String>> {
+        let key = fold(word);
+        let start = {
+            let (mut low, mut high) = (0, self.entries.len());
+            while low < high {
+                let mid = (low + high) / 2;
+                if self.entries[mid].key < key {
+                    low = mid + 1;
+                } else {
+                    high = mid;
+                }
+            }
+            low
+        };
+        let ranges: Vec<(u64, u64)> = self.entries[start..].iter()
+                                          .take_while(|e| e.key == key)
+                                          .map(|e| (e.offset, e.size))
+                                          .collect();
+        let mut definitions = Vec::new();
+        for (offset, size) in ranges {
+            let buf = self.read(offset, size)?;
+            let text = match self.format {
+                Format::StarDict(ref same_type) => stardict_text(&buf, same_type.as_ref().map(|s| s.as_str())),
+                Format::Dictd => String::from_utf8_lossy(&buf).into_owned(),
+            };
+            definitions.push(text.trim().to_string());
+        }
+        Ok(definitions)
+    }
+
+    fn read(&mut self, offset: u64, size: u64) -> Result<Vec<u8>> {
+        match self.data {
+            Data::Plain(ref path) => {
+                let mut file = File::open(path).chain_err(|| "Can't open data file.")?;
+                file.seek(SeekFrom::Start(offset)).chain_err(|| "Can't seek data file.")?;
+                let mut buf = vec![0; size as usize];
+                file.read_exact(&mut buf).chain_err(|| "Can't read data file.")?;
+                Ok(buf)
+            },
+            Data::Chunked(ref path, ref chunks) => {
+                if size == 0 {
+                    return Ok(Vec::new());
+                }
+                let first = (offset / chunks.length) as usize;
+                let last = ((offset + size - 1) / chunks.length) as usize;
+                if last + 1 >= chunks.offsets.len() {
+                    bail!("Entry out of bounds.");
+                }
+                let mut file = File::open(path).chain_err(|| "Can't open data file.")?;
+                file.seek(SeekFrom::Start(chunks.offsets[first])).chain_err(|| "Can't seek data file.")?;
+                let mut compressed = vec![0; (chunks.offsets[last + 1] - chunks.offsets[first]) as usize];
+                file.read_exact(&mut compressed).chain_err(|| "Can't read data file.")?;
+                let buf = inflate_chunks(&compressed)?;
+                let start = (offset - first as u64 * chunks.length) as usize;
+                let end = start + size as usize;
+                if end > buf.len() {
+                    bail!("Entry out of bounds.");
+                }
+                Ok(buf[start..end].to_vec())
+            },
+            Data::Compressed(ref path, ref mut cache) => {
+                if cache.is_none() {
+                    *cache = Some(inflate_file(path)?);
+                }
+                let buf = cache.as_ref().unwrap();
+                let (start, end) = (offset as usize, (offset + size) as usize);
+                if end > buf.len() {
+                    bail!("Entry out of bounds.");
+                }
+                Ok(buf[start..end].to_vec())
+            },
+        }
+    }
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem().map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+// The data file is either `NAME.dict` or `NAME.dict.dz`.
+fn data_source(path: &Path) -> Result<Data> {
+    let plain = path.with_extension("dict");
+    let compressed = path.with_extension("dict.dz");
+    if plain.exists() {
+        Ok(Data::Plain(plain))
+    } else if compressed.exists() {
+        let mut header = Vec::new();
+        File::open(&compressed).and_then(|f| f.take(1 << 17).read_to_end(&mut header))
+                               .chain_err(|| "Can't read data file.")?;
+        match parse_dictzip_header(&header) {
+            Some(chunks) => Ok(Data::Chunked(compressed, chunks)),
+            None => Ok(Data::Compressed(compressed, None)),
+        }
+    } else {
+        bail!("Missing data file.")
+    }
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    File::open(path).and_then(|mut f| f.read_to_end(&mut buf))
+                    .chain_err(|| format!("Can't read {}.", path.display()))?;
+    Ok(buf)
+}
+
+fn inflate_file(path: &Path) -> Result<Vec<u8>> {
+    let file = File::open(path).chain_err(|| format!("Can't open {}.", path.display()))?;
+    let mut buf = Vec::new();
+    gzip::Decoder::new(file).and_then(|mut d| d.read_to_end(&mut buf))
+                            .chain_err(|| format!("Can't inflate {}.", path.display()))?;
+    Ok(buf)
+}
+
+// Inflates consecutive chunks of a dictzip file. Each chunk ends with a flush,
+// hence the missing final block.
+fn inflate_chunks(compressed: &[u8]) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    deflate::Decoder::new(compressed.chain(&DEFLATE_END[..])).read_to_end(&mut buf)
+                                                           .chain_err(|| "Can't inflate chunks.")?;
+    Ok(buf)
+}
+
+fn read_little_endian(bytes: &[u8]) -> u64 {
+    bytes.iter().rev().fold(0, |n, &b| (n << 8) | b as u64)
+}
+
+// The chunks are described by the *RA* subfield of the extra field of the gzip header.
+fn parse_dictzip_header(buf: &[u8]) -> Option<Chunks> {
+    if buf.len() < 12 || buf[0] != 0x1f || buf[1] != 0x8b || buf[3] & GZIP_FEXTRA == 0 {
+        return None;
+    }
+    let flags = buf[3];
+    let extra_len = read_little_endian(&buf[10..12]) as usize;
+    let extra = buf.get(12..12+extra_len)?;
+    let mut table = None;
+    let mut i = 0;
+    while i + 4 <= extra.len() {
+        let len = read_little_endian(&extra[i+2..i+4]) as usize;
+        let data = extra.get(i+4..i+4+len)?;
+        if &extra[i..i+2] == b"RA" && data.len() >= 6 {
+            let length = read_little_endian(&data[2..4]);
+            let count = read_little_endian(&data[4..6]) as usize;
+            let sizes = data.get(6..6+2*count)?;
+            table = Some((length, sizes));
+        }
+        i += 4 + len;
+    }
+    let (length, sizes) = table?;
+    if length == 0 {
+        return None;
+    }
+    let mut start = 12 + extra_len;
+    for &flag in &[GZIP_FNAME, GZIP_FCOMMENT] {
+        if flags & flag != 0 {
+            start += buf.get(start..)?.iter().position(|&b| b == 0)? + 1;
+        }
+    }
+    if flags & GZIP_FHCRC != 0 {
+        start += 2;
+    }
+    let mut offsets = vec![start as u64];
+    for size in sizes.chunks(2) {
+        let next = offsets[offsets.len() - 1] + read_little_endian(size);
+        offsets.push(next);
+    }
+    Some(Chunks { length, offsets })
+}
+
+fn fold(word: &str) -> String {
+    word.trim_matches(|c: char| !c.is_alphanumeric())
+        .nfc().collect::<String>()
+        .to_lowercase()
+}
+
+// Each entry is a null terminated word followed by the big endian offset and size of its data.
+fn parse_stardict_index(buf: &[u8], wide_offsets: bool) -> Result<Vec<Entry>> {
+    let offset_len = if wide_offsets { 8 } else { 4 };
+    let mut entries = Vec::new();
+    let mut i = 0;
+    while i < buf.len() {
+        let end = match buf[i..].iter().position(|&b| b == 0) {
+            Some(len) => i + len,
+            None => bail!("Unterminated word."),
+        };
+        let word = String::from_utf8_lossy(&buf[i..end]);
+        i = end + 1;
+        if i + offset_len + 4 > buf.len() {
+            bail!("Truncated index.");
+        }
+        let offset = read_big_endian(&buf[i..i+offset_len]);
+        i += offset_len;
+        let size = read_big_endian(&buf[i..i+4]);
+        i += 4;
+        entries.push(Entry { key: fold(&word), offset, size });
+    }
+    entries.sort_by(|a, b| a.key.cmp(&b.key));
+    Ok(entries)
+}
+
+fn read_big_endian(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0, |n, &b| (n << 8) | b as u64)
+}
+
+// Each line holds a word, followed by the offset and size of its data, separated by tabs.
+fn parse_dictd_index(text: &str) -> Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for line in text.lines().filter(|l| !l.is_empty()) {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 3 {
+            bail!("Invalid index line.");
+        }
+        match (decode_dictd_number(fields[1]), decode_dictd_number(fields[2])) {
+            (Some(offset), Some(size)) => entries.push(Entry { key: fold(fields[0]), offset, size }),
+            _ => bail!("Invalid index numbers."),
+        }
+    }
+    entries.sort_by(|a, b| a.key.cmp(&b.key));
+    Ok(entries)
+}
+
+fn decode_dictd_number(text: &str) -> Option<u64> {
+    text.bytes().fold(Some(0), |n, b| {
+        n.and_then(|n| DICTD_BASE64.iter().position(|&c| c == b).map(|d| n * 64 + d as u64))
+    })
+}
+
+// Extracts the textual fields of an entry. Without a shared type sequence,
+// each field is preceded by its type. Lowercase types are null terminated
+// texts, uppercase types are binary data preceded by their size.
+fn stardict_text(buf: &[u8], same_type: Option<&str>) -> String {
+    let mut fields = Vec::new();
+    if let Some(types) = same_type {
+        // The size or the terminator of the last field is omitted.
+        let mut i = 0;
+        for (n, t) in types.bytes().enumerate() {
+            if i >= buf.len() {
+                break;
+            }
+            let last = n == types.len() - 1;
+            if t.is_ascii_lowercase() {
+                let end = if last {
+                    buf.len()
+                } else {
+                    buf[i..].iter().position(|&b| b == 0).map_or(buf.len(), |len| i + len)
+                };
+                fields.push((t, &buf[i..end]));
+                i = end + 1;
+            } else if !last {
+                if i + 4 > buf.len() {
+                    break;
+                }
+                i += 4 + read_big_endian(&buf[i..i+4]) as usize;
+            }
+        }
+    } else {
+        let mut i = 0;
+        while i < buf.len() {
+            let t = buf[i];
+            i += 1;
+            if t.is_ascii_lowercase() {
+                let end = buf[i..].iter().position(|&b| b == 0).map_or(buf.len(), |len| i + len);
+                fields.push((t, &buf[i..end]));
+                i = end + 1;
+            } else {
+                if i + 4 > buf.len() {
+                    break;
+                }
+                i += 4 + read_big_endian(&buf[i..i+4]) as usize;
+            }
+        }
+    }
+    fields.into_iter().filter_map(|(t, bytes)| {
+        let text = String::from_utf8_lossy(bytes);
+        match t {
+            b'm' | b'l' | b't' | b'y' => Some(text.into_owned()),
+            b'g' | b'h' | b'x' => Some(strip_markup(&text)),
+            _ => None,
+        }
+    }).collect::<Vec<String>>().join("\n")
+}
+
+// Removes the tags of Pango, HTML and XDXF definitions.
+fn strip_markup(text: &str) -> String {
+    let mut result = String::new();
+    let mut tag: Option<String> = None;
+    for c in text.chars() {
+        match c {
+            '<' => tag = Some(String::new()),
+            '>' if tag.is_some() => {
+                let name = tag.take().unwrap().trim_matches('/').to_lowercase();
+                let name = name.split_whitespace().next().unwrap_or("");
+                if ["br", "p", "div", "li", "tr"].contains(&name) {
+                    result.push('\n');
+                }
+            },
+            _ => match tag {
+                Some(ref mut t) => t.push(c),
+                None => result.push(c),
+            },
+        }
+    }
+    result.replace("&lt;", "<").replace("&gt;", ">")
+          .replace("&quot;", "\"").replace("&apos;", "'")
+          .replace("&nbsp;", " ").replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dictd_numbers() {
+        assert_eq!(decode_dictd_number("A"), Some(0));
+        assert_eq!(decode_dictd_number("Bz"), Some(64 + 51));
+        assert_eq!(decode_dictd_number("B!"), None);
+    }
+
+    #[test]
+    fn test_stardict_index() {
+        let buf = b"Zoo\0\x00\x00\x00\x10\x00\x00\x00\x05apple\0\x00\x00\x00\x00\x00\x00\x00\x10";
+        let entries = parse_stardict_index(buf, false).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!((entries[0].key.as_str(), entries[0].offset, entries[0].size), ("apple", 0, 16));
+        assert_eq!((entries[1].key.as_str(), entries[1].offset, entries[1].size), ("zoo", 16, 5));
+        assert!(parse_stardict_index(b"apple\0\x00\x00", false).is_err());
+    }
+
+    #[test]
+    fn test_stardict_text() {
+        assert_eq!(stardict_text(b"a fruit", Some("m")), "a fruit");
+        assert_eq!(stardict_text(b"tap-l\0h<b>a</b> fruit<br>red\0", None), "ap-l\na fruit\nred");
+    }
+
+    #[test]
+    fn test_dictzip() {
+        // Two chunks of six bytes, stored without compression.
+        let mut file = vec![0x1f, 0x8b, 8, GZIP_FEXTRA | GZIP_FNAME, 0, 0, 0, 0, 0, 3, 14, 0];
+        file.extend_from_slice(b"RA\x0a\x00\x01\x00\x06\x00\x02\x00\x0b\x00\x0b\x00");
+        file.extend_from_slice(b"en.dict\0");
+        file.extend_from_slice(b"\x00\x06\x00\xf9\xffhello ");
+        file.extend_from_slice(b"\x00\x06\x00\xf9\xffworld!");
+        let chunks = parse_dictzip_header(&file).unwrap();
+        assert_eq!(chunks, Chunks { length: 6, offsets: vec![34, 45, 56] });
+        assert_eq!(inflate_chunks(&file[45..56]).unwrap(), b"world!");
+        assert_eq!(inflate_chunks(&file[34..56]).unwrap(), b"hello world!");
+        assert!(parse_dictzip_header(&file[..20]).is_none());
+    }
+
+    #[test]
+    fn test_fold() {
+        assert_eq!(fold("«Maison»,"), "maison");
+    }
+}
@@ -0,0 +1,191 @@
+use framebuffer::Pixmap;
+use geom::Rectangle;
+
+// Pixels darker than this are considered as ink.
+const INK_THRESHOLD: u8 = 0x80;
+// Minimum height of the blank bands that separate blocks, relative to the pixmap's height.
+const BAND_GAP_RATIO: f32 = 0.015;
+// Minimum width of the gutters that separate columns, relative to the pixmap's width.
+const GUTTER_RATIO: f32 = 0.02;
+// Minimum width of a column, relative to the width of the content.
+const MIN_COLUMN_RATIO: f32 = 0.2;
+// Blocks shorter than this, relative to the height of the content, are considered minor.
+const MIN_BLOCK_RATIO: f32 = 0.08;
+
+fn is_ink(pixmap: &Pixmap, x: i32, y: i32) -> bool {
+    pixmap.buf[(y * pixmap.width + x) as usize] < INK_THRESHOLD
+}
+
+fn ink_rows(pixmap: &Pixmap, rect: &Rectangle) -> Vec<usize> {
+    (rect.min.y..rect.max.y).map(|y| {
+        (rect.min.x..rect.max.x).filter(|&x| is_ink(pixmap, x, y)).count()
+    }).collect()
+}
+
+fn ink_columns(pixmap: &Pixmap, rect: &Rectangle) -> Vec<usize> {
+    (rect.min.x..rect.max.x).map(|x| {
+        (rect.min.y..rect.max.y).filter(|&y| is_ink(pixmap, x, y)).count()
+    }).collect()
+}
+
+// Returns the ranges of the profile that contain ink and are separated by at least `min_gap` blank lines.
+fn segments(profile: &[usize], noise: usize, min_gap: usize) -> Vec<(usize, usize)> {
+    let mut result: Vec<(usize, usize)> = Vec::new();
+    let mut start = None;
+    let mut last_ink = 0;
+
+    for (i, &count) in profile.iter().enumerate() {
+        if count <= noise {
+            continue;
+        }
+        match start {
+            Some(s) if i - last_ink > min_gap => {
+                result.push((s, last_ink + 1));
+                start = Some(i);
+            },
+            None => start = Some(i),
+            _ => (),
+        }
+        last_ink = i;
+    }
+
+    if let Some(s) = start {
+        result.push((s, last_ink + 1));
+    }
+
+    result
+}
+
+fn noise_level(length: usize) -> usize {
+    length / 200
+}
+
+// The bounding box of the ink.
+pub fn content_bounds(pixmap: &Pixmap) -> Option<Rectangle> {
+    let rect = rect![0, 0, pixmap.width, pixmap.height];
+    let rows = ink_rows(pixmap, &rect);
+    let columns = ink_columns(pixmap, &rect);
+    let row_noise = noise_level(pixmap.width as usize);
+    let column_noise = noise_level(pixmap.height as usize);
+    let y_min = rows.iter().position(|&c| c > row_noise)?;
+    let y_max = rows.iter().rposition(|&c| c > row_noise)? + 1;
+    let x_min = columns.iter().position(|&c| c > column_noise)?;
+    let x_max = columns.iter().rposition(|&c| c > column_noise)? + 1;
+    Some(rect![x_min as i32, y_min as i32, x_max as i32, y_max as i32])
+}
+
+// Splits the page into blocks separated by blank bands, splits each block into columns,
+// and returns the columns in reading order. Consecutive blocks with the same column
+// structure are merged.
+pub fn reading_regions(pixmap: &Pixmap) -> Vec<Rectangle> {
+    let bounds = match content_bounds(pixmap) {
+        Some(bounds) => bounds,
+        None => return Vec::new(),
+    };
+
+    let band_gap = ((pixmap.height as f32 * BAND_GAP_RATIO).ceil() as usize).max(1);
+    let gutter = ((pixmap.width as f32 * GUTTER_RATIO).ceil() as usize).max(1);
+    let min_column_width = (bounds.width() as f32 * MIN_COLUMN_RATIO) as i32;
+
+    let rows = ink_rows(pixmap, &bounds);
+    let mut blocks: Vec<Vec<Rectangle>> = Vec::new();
+
+    for (start, end) in segments(&rows, noise_level(bounds.width() as usize), band_gap) {
+        let band = rect![bounds.min.x, bounds.min.y + start as i32,
+                         bounds.max.x, bounds.min.y + end as i32];
+        let profile = ink_columns(pixmap, &band);
+        let mut columns: Vec<Rectangle> = segments(&profile, 0, gutter).into_iter().map(|(start, end)| {
+            rect![band.min.x + start as i32, band.min.y,
+                  band.min.x + end as i32, band.max.y]
+        }).collect();
+
+        // Words separated by large spaces aren't columns.
+        if columns.iter().any(|c| (c.width() as i32) < min_column_width) {
+            columns = vec![band];
+        }
+
+        if let Some(last) = blocks.last_mut() {
+            if last.len() == columns.len() &&
+               last.iter().zip(columns.iter()).all(|(a, b)| a.min.x < b.max.x && b.min.x < a.max.x) {
+                for (a, b) in last.iter_mut().zip(columns.iter()) {
+                    a.absorb(b);
+                }
+                continue;
+            }
+        }
+
+        blocks.push(columns);
+    }
+
+    // Minor blocks at the edges are running heads, folios or footnotes separators:
+    // merge them into a neighboring single column block, or drop them.
+    let min_block_height = (bounds.height() as f32 * MIN_BLOCK_RATIO) as i32;
+    let is_minor = |b: &Vec<Rectangle>| b.len() == 1 && (b[0].height() as i32) < min_block_height;
+
+    if blocks.len() > 1 && is_minor(&blocks[0]) {
+        let first = blocks.remove(0);
+        if blocks[0].len() == 1 {
+            blocks[0][0].absorb(&first[0]);
+        }
+    }
+
+    if blocks.len() > 1 && is_minor(&blocks[blocks.len() - 1]) {
+        let last = blocks.pop().unwrap();
+        let n = blocks.len() - 1;
+        if blocks[n].len() == 1 {
+            blocks[n][0].absorb(&last[0]);
+        }
+    }
+
+    blocks.into_iter().flat_map(|b| b).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blank_pixmap(width: i32, height: i32) -> Pixmap {
+        Pixmap { width, height, buf: vec![0xff; (width * height) as usize] }
+    }
+
+    fn fill(pixmap: &mut Pixmap, rect: &Rectangle) {
+        for y in rect.min.y..rect.max.y {
+            for x in rect.min.x..rect.max.x {
+                pixmap.buf[(y * pixmap.width + x) as usize] = 0;
+            }
+        }
+    }
+
+    #[test]
+    fn test_content_bounds() {
+        let mut pixmap = blank_pixmap(200, 300);
+        assert_eq!(content_bounds(&pixmap), None);
+        fill(&mut pixmap, &rect![20, 30, 180, 60]);
+        fill(&mut pixmap, &rect![40, 100, 150, 250]);
+        assert_eq!(content_bounds(&pixmap), Some(rect![20, 30, 180, 250]));
+    }
+
+    #[test]
+    fn test_two_columns() {
+        let mut pixmap = blank_pixmap(400, 600);
+        // Title spanning both columns.
+        fill(&mut pixmap, &rect![20, 20, 380, 100]);
+        fill(&mut pixmap, &rect![20, 140, 190, 560]);
+        fill(&mut pixmap, &rect![210, 140, 380, 560]);
+        // Folio.
+        fill(&mut pixmap, &rect![190, 580, 210, 590]);
+        assert_eq!(reading_regions(&pixmap),
+                   vec![rect![20, 20, 380, 100],
+                        rect![20, 140, 190, 560],
+                        rect![210, 140, 380, 560]]);
+    }
+
+    #[test]
+    fn test_single_column() {
+        let mut pixmap = blank_pixmap(400, 600);
+        fill(&mut pixmap, &rect![20, 20, 380, 200]);
+        fill(&mut pixmap, &rect![20, 240, 380, 560]);
+        assert_eq!(reading_regions(&pixmap),
+                   vec![rect![20, 20, 380, 560]]);
+    }
+}
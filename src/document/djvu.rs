@@ -6,7 +6,7 @@ use std::rc::Rc;
 use std::path::Path;
 use std::ffi::{CStr, CString};
 use std::os::unix::ffi::OsStrExt;
-use document::{Document, TextLayer, LayerGrain, TocEntry};
+use document::{Document, TextLayer, LayerGrain, TocEntry, Link, LinkTarget};
 use framebuffer::Pixmap;
 use geom::Rectangle;
 use app::APP_NAME;
@@ -327,6 +327,37 @@ impl Document for DjvuDocument {
         }
     }
 
+    fn links(&self, index: usize) -> Option<Vec<Link>> {
+        unsafe {
+            let height = self.page(index)?.height() as i32;
+            let mut exp = ddjvu_document_get_pageanno(self.doc, index as libc::c_int);
+            while exp == MINIEXP_DUMMY {
+                self.ctx.handle_message();
+                exp = ddjvu_document_get_pageanno(self.doc, index as libc::c_int);
+            }
+            if exp == MINIEXP_NIL {
+                return None;
+            }
+            let links = ddjvu_anno_get_hyperlinks(exp);
+            if links.is_null() {
+                ddjvu_miniexp_release(self.doc, exp);
+                return None;
+            }
+            let pages_count = self.pages_count();
+            let mut result = Vec::new();
+            let mut cur = links;
+            while !(*cur).is_null() {
+                if let Some(link) = Self::parse_link(*cur, index, pages_count, height) {
+                    result.push(link);
+                }
+                cur = cur.offset(1);
+            }
+            libc::free(links as *mut libc::c_void);
+            ddjvu_miniexp_release(self.doc, exp);
+            Some(result)
+        }
+    }
+
     fn title(&self) -> Option<String> {
         self.info("title")
     }
@@ -431,6 +462,56 @@ impl DjvuDocument {
         }
     }
 
+    // A link has the form `(maparea url comment area ...)`, where `url` is either
+    // a string or `(url target)`, and `area` is `(rect x y w h)`, `(oval x y w h)`,
+    // `(text x y w h)` or `(poly x0 y0 x1 y1 ...)`.
+    fn parse_link(exp: *mut MiniExp, index: usize, pages_count: usize, height: i32) -> Option<Link> {
+        unsafe {
+            let mut url = miniexp_nth(1, exp);
+            if miniexp_stringp(url) != 1 {
+                url = miniexp_nth(0, url);
+            }
+            if miniexp_stringp(url) != 1 {
+                return None;
+            }
+            let url = CStr::from_ptr(miniexp_to_str(url)).to_string_lossy().into_owned();
+            let area = miniexp_nth(3, exp);
+            if !miniexp_consp(area) {
+                return None;
+            }
+            let shape = miniexp_nth(0, area);
+            if !miniexp_symbolp(shape) {
+                return None;
+            }
+            let name = miniexp_to_name(shape);
+            if name.is_null() {
+                return None;
+            }
+            let shape = CStr::from_ptr(name).to_bytes();
+            let coords: Vec<i32> = (1..miniexp_length(area)).map(|i| miniexp_nth(i, area))
+                                                            .take_while(|&n| miniexp_numberp(n))
+                                                            .map(miniexp_to_int)
+                                                            .collect();
+            let (min_x, min_y, max_x, max_y) = match shape {
+                b"rect" | b"oval" | b"text" if coords.len() >= 4 => {
+                    (coords[0], coords[1], coords[0] + coords[2], coords[1] + coords[3])
+                },
+                b"poly" if coords.len() >= 2 => {
+                    let xs = coords.iter().step_by(2);
+                    let ys = coords.iter().skip(1).step_by(2);
+                    (*xs.clone().min().unwrap(), *ys.clone().min().unwrap(),
+                     *xs.max().unwrap(), *ys.max().unwrap())
+                },
+                _ => return None,
+            };
+            let target = link_target(&url, index, pages_count)?;
+            Some(Link {
+                rect: rect![min_x, height - max_y, max_x, height - min_y],
+                target,
+            })
+        }
+    }
+
     pub fn info(&self, key: &str) -> Option<String> {
         unsafe {
             let mut exp = ddjvu_document_get_anno(self.doc, 1);
@@ -455,6 +536,46 @@ impl DjvuDocument {
     }
 }
 
+// The inline functions of miniexp.h: the two lowest bits of an expression give its type.
+fn miniexp_consp(exp: *mut MiniExp) -> bool {
+    exp != MINIEXP_NIL && (exp as libc::size_t) & 3 == 0
+}
+
+fn miniexp_symbolp(exp: *mut MiniExp) -> bool {
+    (exp as libc::size_t) & 3 == 2
+}
+
+fn miniexp_numberp(exp: *mut MiniExp) -> bool {
+    (exp as libc::size_t) & 3 == 3
+}
+
+fn miniexp_to_int(exp: *mut MiniExp) -> i32 {
+    (exp as libc::size_t as i32) >> 2
+}
+
+// Internal links start with a hash followed by a page number,
+// a relative offset or a page name.
+fn link_target(url: &str, index: usize, pages_count: usize) -> Option<LinkTarget> {
+    if !url.starts_with('#') {
+        return Some(LinkTarget::Uri(url.to_string()));
+    }
+    let name = &url[1..];
+    let page = if name.starts_with('+') || name.starts_with('-') {
+        name.parse::<i64>().ok().map(|offset| index as i64 + offset)
+    } else {
+        let digits = name.chars().filter(|c| c.is_digit(10))
+                         .collect::<String>();
+        digits.parse::<i64>().ok().map(|number| number - 1)
+    };
+    page.and_then(|page| {
+        if page >= 0 && (page as usize) < pages_count {
+            Some(LinkTarget::Page(page as usize))
+        } else {
+            None
+        }
+    })
+}
+
 impl<'a> DjvuPage<'a> {
     pub fn pixmap(&self, scale: f32) -> Option<Pixmap> {
         unsafe {
@@ -1,16 +1,25 @@
 pub mod djvu;
 pub mod pdf;
+pub mod analysis;
+pub mod thumbnail;
 
 use std::path::Path;
 use std::str::FromStr;
-use fnv::FnvHashSet;
+use fnv::{FnvHashMap, FnvHashSet};
 use isbn::Isbn;
+use regex::Regex;
 use unicode_normalization::UnicodeNormalization;
 use unicode_normalization::char::{is_combining_mark};
 use geom::{Rectangle, CycleDir};
 use document::djvu::{DjvuOpener};
 use document::pdf::{PdfOpener};
 use framebuffer::Pixmap;
+use device::Device;
+use font::DEFAULT_FONT_SIZE;
+use unit::pt_to_px;
+
+// Number of words shown on each side of a search match.
+const CONTEXT_WORDS: usize = 5;
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum LayerGrain {
@@ -38,6 +47,19 @@ impl TextLayer {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct TextMatch {
+    pub page: usize,
+    pub rects: Vec<Rectangle>,
+    pub context: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct BoundedText {
+    pub text: String,
+    pub rect: Rectangle,
+}
+
 #[derive(Debug, Clone)]
 pub struct TocEntry {
     pub title: String,
@@ -45,6 +67,19 @@ pub struct TocEntry {
     pub children: Vec<TocEntry>,
 }
 
+#[derive(Debug, Clone)]
+pub enum LinkTarget {
+    Page(usize),
+    Uri(String),
+}
+
+// The rectangle of a link is given in page coordinates.
+#[derive(Debug, Clone)]
+pub struct Link {
+    pub rect: Rectangle,
+    pub target: LinkTarget,
+}
+
 pub fn chapter_at(toc: &[TocEntry], index: usize) -> Option<TocEntry> {
     let mut chap = None;
     chapter_at_aux(toc, index, &mut chap);
@@ -90,6 +125,124 @@ fn chapter_relative_prev<'a>(toc: &'a [TocEntry], index: usize, page: &mut Optio
     }
 }
 
+// The width, height and font size, in pixels, used by the reader to lay out
+// reflowable documents on the given device, given the font size of the book in points.
+pub fn reader_layout(device: &Device, font_size: Option<f32>) -> (f32, f32, f32) {
+    let (width, height) = device.screen_dims();
+    let font_size = pt_to_px(font_size.unwrap_or(DEFAULT_FONT_SIZE), device.dpi);
+    (width as f32, height as f32, font_size)
+}
+
+// A layout independent position within a reflowable document: the index of
+// a chapter in the flattened table of contents and the number of characters
+// between the beginning of that chapter and the beginning of the page.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Anchor {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chapter: Option<usize>,
+    pub offset: usize,
+}
+
+fn flatten_toc<'a>(toc: &'a [TocEntry], entries: &mut Vec<&'a TocEntry>) {
+    for entry in toc {
+        entries.push(entry);
+        flatten_toc(&entry.children, entries);
+    }
+}
+
+fn chars_count(doc: &Document, index: usize) -> usize {
+    doc.text(index).map(|t| {
+        t.words().iter().map(|w| w.chars().count()).sum()
+    }).unwrap_or(0)
+}
+
+// The number of characters of the pages extracted so far, for the current layout.
+// Extracting the text of a page is costly: each page is only counted once.
+#[derive(Debug, Clone, Default)]
+pub struct CharsCounts(FnvHashMap<usize, usize>);
+
+impl CharsCounts {
+    fn get(&mut self, doc: &Document, index: usize) -> usize {
+        *self.0.entry(index).or_insert_with(|| chars_count(doc, index))
+    }
+
+    // Needs to be called when the document is laid out again.
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+pub fn anchor_at(doc: &Document, index: usize, counts: &mut CharsCounts) -> Anchor {
+    let toc = doc.toc().unwrap_or_default();
+    let mut entries = Vec::new();
+    flatten_toc(&toc, &mut entries);
+
+    let mut chapter: Option<usize> = None;
+    for (i, entry) in entries.iter().enumerate() {
+        if entry.page <= index && chapter.map_or(true, |c| entry.page > entries[c].page) {
+            chapter = Some(i);
+        }
+    }
+
+    let start = chapter.map_or(0, |c| entries[c].page);
+    let offset = (start..index).map(|i| counts.get(doc, i)).sum();
+
+    Anchor { chapter, offset }
+}
+
+// The page that contains the given anchor and the number of characters
+// between the beginning of that page and the anchor.
+fn locate_anchor(doc: &Document, anchor: &Anchor, counts: &mut CharsCounts) -> (usize, usize) {
+    let toc = doc.toc().unwrap_or_default();
+    let mut entries = Vec::new();
+    flatten_toc(&toc, &mut entries);
+
+    let pages_count = doc.pages_count();
+    let start = anchor.chapter.and_then(|c| entries.get(c))
+                      .map_or(0, |e| e.page);
+    let mut offset = 0;
+
+    for index in start..pages_count {
+        let count = counts.get(doc, index);
+        if offset == anchor.offset || offset + count > anchor.offset {
+            return (index, anchor.offset - offset);
+        }
+        offset += count;
+    }
+
+    (pages_count.saturating_sub(1), 0)
+}
+
+// The positions are exact when the chapters begin on a new page.
+pub fn page_at_anchor(doc: &Document, anchor: &Anchor, counts: &mut CharsCounts) -> usize {
+    locate_anchor(doc, anchor, counts).0
+}
+
+// The page that contains the given anchor and the words of that page that follow it.
+pub fn words_at_anchor(doc: &Document, anchor: &Anchor, counts: &mut CharsCounts) -> (usize, Vec<BoundedText>) {
+    let (index, mut offset) = locate_anchor(doc, anchor, counts);
+    let mut words = doc.text(index).map(|t| t.word_boxes()).unwrap_or_default();
+    let start = words.iter().position(|w| {
+        let count = w.text.chars().count();
+        if offset < count {
+            true
+        } else {
+            offset -= count;
+            false
+        }
+    }).unwrap_or_else(|| words.len());
+    (index, words.split_off(start))
+}
+
+// The chapter of the given anchor doesn't depend on the layout.
+#[cfg(feature = "importer")]
+pub fn chapter_of_anchor(toc: &[TocEntry], anchor: &Anchor) -> Option<TocEntry> {
+    let mut entries = Vec::new();
+    flatten_toc(toc, &mut entries);
+    anchor.chapter.and_then(|c| entries.get(c).map(|&e| e.clone()))
+}
+
 pub trait Document {
     fn pages_count(&self) -> usize;
     fn pixmap(&self, index: usize, scale: f32) -> Option<Pixmap>;
@@ -97,6 +250,7 @@ pub trait Document {
 
     fn toc(&self) -> Option<Vec<TocEntry>>;
     fn text(&self, index: usize) -> Option<TextLayer>;
+    fn links(&self, index: usize) -> Option<Vec<Link>>;
 
     fn title(&self) -> Option<String>;
     fn author(&self) -> Option<String>;
@@ -175,6 +329,64 @@ impl TextLayer {
             }
         }
     }
+
+    fn word_layers<'a>(&'a self, layers: &mut Vec<&'a TextLayer>) {
+        match self.grain {
+            LayerGrain::Word => layers.push(self),
+            LayerGrain::Character => (),
+            _ => {
+                for child in &self.children {
+                    child.word_layers(layers);
+                }
+            },
+        }
+    }
+
+    pub fn word_boxes(&self) -> Vec<BoundedText> {
+        let mut layers = Vec::new();
+        self.word_layers(&mut layers);
+        layers.into_iter().map(|l| {
+            BoundedText {
+                text: l.text.clone().unwrap(),
+                rect: l.rect,
+            }
+        }).collect()
+    }
+
+    // The words are joined with spaces, the matches are then mapped back to the
+    // rectangles of the words they overlap.
+    pub fn search(&self, page: usize, query: &Regex) -> Vec<TextMatch> {
+        let mut layers = Vec::new();
+        self.word_layers(&mut layers);
+
+        let mut text = String::new();
+        let mut bounds = Vec::with_capacity(layers.len());
+
+        for layer in &layers {
+            if !text.is_empty() {
+                text.push(' ');
+            }
+            let start = text.len();
+            text += layer.text.as_ref().unwrap();
+            bounds.push((start, text.len()));
+        }
+
+        query.find_iter(&text).filter_map(|m| {
+            let first = bounds.iter().position(|&(_, end)| end > m.start())?;
+            let last = bounds.iter().rposition(|&(start, _)| start < m.end())?;
+            if last < first {
+                return None;
+            }
+            let rects = layers[first..=last].iter().map(|l| l.rect).collect();
+            let context_start = bounds[first.saturating_sub(CONTEXT_WORDS)].0;
+            let context_end = bounds[(last + CONTEXT_WORDS).min(bounds.len() - 1)].1;
+            Some(TextMatch {
+                page,
+                rects,
+                context: text[context_start..context_end].to_string(),
+            })
+        }).collect()
+    }
 }
 
 pub fn asciify(name: &str) -> String {
@@ -265,3 +477,36 @@ pub static ref ALLOWED_KINDS: FnvHashSet<&'static str> =
     "xps",
     ].iter().cloned().collect();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use metadata::make_query;
+
+    fn word(text: &str, x: i32) -> TextLayer {
+        TextLayer {
+            grain: LayerGrain::Word,
+            rect: rect![x, 0, x + 10, 10],
+            text: Some(text.to_string()),
+            children: vec![],
+        }
+    }
+
+    #[test]
+    fn test_search() {
+        let line = TextLayer {
+            grain: LayerGrain::Line,
+            rect: rect![0, 0, 60, 10],
+            text: None,
+            children: vec![word("The", 0), word("quick", 10), word("brown", 20),
+                           word("fox", 30), word("jumps", 40), word("over", 50)],
+        };
+        let matches = line.search(3, &make_query("brown fox").unwrap());
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].page, 3);
+        assert_eq!(matches[0].rects, vec![rect![20, 0, 30, 10], rect![30, 0, 40, 10]]);
+        assert_eq!(matches[0].context, "The quick brown fox jumps over");
+        assert!(line.search(0, &make_query("dog").unwrap()).is_empty());
+        assert_eq!(line.search(0, &make_query("O").unwrap()).len(), 3);
+    }
+}
@@ -10,7 +10,7 @@ use std::io::Read;
 use std::fs::File;
 use std::ffi::{CString, CStr};
 use std::os::unix::ffi::OsStrExt;
-use document::{Document, TextLayer, LayerGrain, TocEntry};
+use document::{Document, TextLayer, LayerGrain, TocEntry, Link, LinkTarget};
 use framebuffer::Pixmap;
 use geom::Rectangle;
 
@@ -82,6 +82,10 @@ extern {
     fn fz_clear_pixmap(ctx: *mut FzContext, pixmap: *mut FzPixmap);
     fn fz_union_rect(a: *mut FzRect, b: *const FzRect);
     fn fz_runetochar(buf: *mut u8, rune: libc::c_int) -> libc::c_int;
+    fn mp_load_links(ctx: *mut FzContext, page: *mut FzPage) -> *mut FzLink;
+    fn fz_drop_link(ctx: *mut FzContext, link: *mut FzLink);
+    fn fz_is_external_link(ctx: *mut FzContext, uri: *const libc::c_char) -> libc::c_int;
+    fn mp_resolve_link(ctx: *mut FzContext, doc: *mut FzDocument, uri: *const libc::c_char, xp: *mut libc::c_float, yp: *mut libc::c_float) -> libc::c_int;
     static fz_identity: FzMatrix;
 }
 
@@ -256,6 +260,15 @@ struct FzOutline {
     is_open: libc::c_int,
 }
 
+#[repr(C)]
+struct FzLink {
+    refs: libc::c_int,
+    next: *mut FzLink,
+    rect: FzRect,
+    doc: *mut libc::c_void,
+    uri: *mut libc::c_char,
+}
+
 impl Default for FzOutline {
     fn default() -> Self {
         unsafe { mem::zeroed() }
@@ -421,6 +434,10 @@ impl Document for PdfDocument {
         self.page(index).and_then(|page| page.text())
     }
 
+    fn links(&self, index: usize) -> Option<Vec<Link>> {
+        self.page(index).and_then(|page| page.links())
+    }
+
     fn title(&self) -> Option<String> {
         self.info(FZ_META_INFO_TITLE)
     }
@@ -513,6 +530,40 @@ impl<'a> PdfPage<'a> {
         }
     }
 
+    pub fn links(&self) -> Option<Vec<Link>> {
+        unsafe {
+            let links = mp_load_links(self.ctx.0, self.page);
+            if links.is_null() {
+                return None;
+            }
+            let mut result = Vec::new();
+            let mut link = links;
+            while !link.is_null() {
+                let uri = (*link).uri;
+                let target = if fz_is_external_link(self.ctx.0, uri) == 1 {
+                    Some(LinkTarget::Uri(CStr::from_ptr(uri).to_string_lossy().into_owned()))
+                } else {
+                    let (mut x, mut y) = (0.0, 0.0);
+                    let page = mp_resolve_link(self.ctx.0, self.doc.doc, uri, &mut x, &mut y);
+                    if page < 0 {
+                        None
+                    } else {
+                        Some(LinkTarget::Page(page as usize))
+                    }
+                };
+                if let Some(target) = target {
+                    result.push(Link {
+                        rect: (*link).rect.clone().into(),
+                        target,
+                    });
+                }
+                link = (*link).next;
+            }
+            fz_drop_link(self.ctx.0, links);
+            Some(result)
+        }
+    }
+
     pub fn pixmap(&self, scale: f32) -> Option<Pixmap> {
         unsafe {
             let mut mat = FzMatrix::default();
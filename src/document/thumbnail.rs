@@ -0,0 +1,70 @@
+extern crate png;
+
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use fnv::FnvHasher;
+use png::HasParameters;
+use document::Document;
+use framebuffer::Pixmap;
+use errors::*;
+
+pub const THUMBNAILS_DIRNAME: &str = ".thumbnails";
+
+// The directory, within the library, that holds the thumbnails of the document at `path`.
+pub fn thumbnails_dir<P: AsRef<Path>>(library_path: &Path, path: P) -> PathBuf {
+    let mut hasher = FnvHasher::default();
+    path.as_ref().hash(&mut hasher);
+    library_path.join(THUMBNAILS_DIRNAME)
+                .join(format!("{:016x}", hasher.finish()))
+}
+
+// Renders the page at `index` so that it fits in a `width` by `height` box.
+pub fn render_thumbnail(doc: &Document, index: usize, width: u32, height: u32) -> Option<Pixmap> {
+    let (w, h) = doc.dims(index)?;
+    let scale = (width as f32 / w).min(height as f32 / h);
+    doc.pixmap(index, scale)
+}
+
+pub fn thumbnail_path(dir: &Path, index: usize, width: u32, height: u32) -> PathBuf {
+    dir.join(format!("{}-{}x{}.png", index, width, height))
+}
+
+// Loads the thumbnail from the disk, or renders and saves it.
+pub fn cached_thumbnail(doc: &Document, dir: &Path, index: usize, width: u32, height: u32) -> Option<Pixmap> {
+    let path = thumbnail_path(dir, index, width, height);
+    if let Ok(pixmap) = load_pixmap(&path) {
+        return Some(pixmap);
+    }
+    let pixmap = render_thumbnail(doc, index, width, height)?;
+    if let Err(e) = fs::create_dir_all(dir).chain_err(|| "Can't create thumbnails directory.")
+                      .and_then(|_| save_pixmap(&pixmap, &path)) {
+        eprintln!("Can't save thumbnail {}: {}.", path.display(), e);
+    }
+    Some(pixmap)
+}
+
+pub fn load_pixmap(path: &Path) -> Result<Pixmap> {
+    let file = File::open(path).chain_err(|| "Can't open thumbnail.")?;
+    let decoder = png::Decoder::new(file);
+    let (info, mut reader) = decoder.read_info().chain_err(|| "Can't read header.")?;
+    if info.color_type != png::ColorType::Grayscale || info.bit_depth != png::BitDepth::Eight {
+        bail!("Unsupported color type.");
+    }
+    let mut buf = vec![0; info.buffer_size()];
+    reader.next_frame(&mut buf).chain_err(|| "Can't read data.")?;
+    Ok(Pixmap {
+        width: info.width as i32,
+        height: info.height as i32,
+        buf,
+    })
+}
+
+pub fn save_pixmap(pixmap: &Pixmap, path: &Path) -> Result<()> {
+    let file = File::create(path).chain_err(|| "Can't create output file.")?;
+    let mut encoder = png::Encoder::new(file, pixmap.width as u32, pixmap.height as u32);
+    encoder.set(png::ColorType::Grayscale).set(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().chain_err(|| "Can't write header.")?;
+    writer.write_image_data(&pixmap.buf).chain_err(|| "Can't write data to file.")?;
+    Ok(())
+}
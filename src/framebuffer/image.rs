@@ -13,6 +13,7 @@ pub struct ImageFramebuffer {
     data: Vec<u8>,
     inverted: bool,
     monochrome: bool,
+    rotation: i8,
 }
 
 impl ImageFramebuffer {
@@ -24,6 +25,7 @@ impl ImageFramebuffer {
             data: vec![WHITE; len],
             inverted: false,
             monochrome: false,
+            rotation: 0,
         }
     }
 }
@@ -85,6 +87,15 @@ impl Framebuffer for ImageFramebuffer {
         self.monochrome = !self.monochrome;
     }
 
+    // The content isn't preserved when the orientation changes.
+    fn set_rotation(&mut self, n: i8) {
+        if (n - self.rotation) % 2 != 0 {
+            ::std::mem::swap(&mut self.width, &mut self.height);
+            self.data = vec![WHITE; (self.width * self.height) as usize];
+        }
+        self.rotation = n;
+    }
+
     fn dims(&self) -> (u32, u32) {
         (self.width, self.height)
     }
@@ -186,6 +186,7 @@ pub struct KoboFramebuffer {
     frame_size: libc::size_t, 
     token: u32,
     flags: u32,
+    rotation: i8,
     set_pixel_rgb: SetPixelRgb,
     get_pixel_rgb: GetPixelRgb,
     as_rgb: AsRgb,
@@ -196,6 +197,7 @@ pub struct KoboFramebuffer {
 
 impl Framebuffer for KoboFramebuffer {
     fn set_pixel(&mut self, x: u32, y: u32, color: u8) {
+        let (x, y) = self.physical(x, y);
         (self.set_pixel_rgb)(self, x, y, [color, color, color]);
     }
 
@@ -204,6 +206,7 @@ impl Framebuffer for KoboFramebuffer {
             self.set_pixel(x, y, color);
             return;
         }
+        let (x, y) = self.physical(x, y);
         let rgb = (self.get_pixel_rgb)(self, x, y);
         let color_alpha = color as f32 * alpha;
         let r = color_alpha + (1.0 - alpha) * rgb[0] as f32;
@@ -241,7 +244,7 @@ impl Framebuffer for KoboFramebuffer {
             flags |= EPDC_FLAG_FORCE_MONOCHROME;
         }
         let update_data = MxcfbUpdateData {
-            update_region: (*rect).into(),
+            update_region: self.physical_rect(rect).into(),
             waveform_mode,
             update_mode,
             update_marker,
@@ -280,7 +283,12 @@ impl Framebuffer for KoboFramebuffer {
         let mut encoder = png::Encoder::new(file, width, height);
         encoder.set(png::ColorType::RGB).set(png::BitDepth::Eight);
         let mut writer = encoder.write_header().chain_err(|| "Can't write header.")?;
-        writer.write_image_data(&(self.as_rgb)(self)).chain_err(|| "Can't write data to file.")?;
+        let data = if self.rotation == 0 {
+            (self.as_rgb)(self)
+        } else {
+            self.rotated_rgb()
+        };
+        writer.write_image_data(&data).chain_err(|| "Can't write data to file.")?;
         Ok(())
     }
 
@@ -292,12 +300,24 @@ impl Framebuffer for KoboFramebuffer {
         self.flags ^= EPDC_FLAG_FORCE_MONOCHROME;
     }
 
+    fn set_rotation(&mut self, n: i8) {
+        self.rotation = (n % 4 + 4) % 4;
+    }
+
     fn width(&self) -> u32 {
-        self.var_info.xres
+        if self.rotation % 2 == 1 {
+            self.var_info.yres
+        } else {
+            self.var_info.xres
+        }
     }
 
     fn height(&self) -> u32 {
-        self.var_info.yres
+        if self.rotation % 2 == 1 {
+            self.var_info.xres
+        } else {
+            self.var_info.yres
+        }
     }
 }
 
@@ -344,6 +364,7 @@ impl KoboFramebuffer {
                    frame_size: frame_size,
                    token: 1,
                    flags: 0,
+                   rotation: 0,
                    set_pixel_rgb: set_pixel_rgb,
                    get_pixel_rgb: get_pixel_rgb,
                    as_rgb: as_rgb,
@@ -354,6 +375,40 @@ impl KoboFramebuffer {
         }
     }
     
+    // Maps screen coordinates to device coordinates.
+    #[inline]
+    fn physical(&self, x: u32, y: u32) -> (u32, u32) {
+        let (width, height) = (self.var_info.xres, self.var_info.yres);
+        match self.rotation {
+            1 => (width - 1 - y, x),
+            2 => (width - 1 - x, height - 1 - y),
+            3 => (y, height - 1 - x),
+            _ => (x, y),
+        }
+    }
+
+    fn physical_rect(&self, rect: &Rectangle) -> Rectangle {
+        let (width, height) = (self.var_info.xres as i32, self.var_info.yres as i32);
+        match self.rotation {
+            1 => rect![width - rect.max.y, rect.min.x, width - rect.min.y, rect.max.x],
+            2 => rect![width - rect.max.x, height - rect.max.y, width - rect.min.x, height - rect.min.y],
+            3 => rect![rect.min.y, height - rect.max.x, rect.max.y, height - rect.min.x],
+            _ => *rect,
+        }
+    }
+
+    fn rotated_rgb(&self) -> Vec<u8> {
+        let (width, height) = self.dims();
+        let mut rgb888 = Vec::with_capacity((width * height * 3) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let (px, py) = self.physical(x, y);
+                rgb888.extend_from_slice(&(self.get_pixel_rgb)(self, px, py));
+            }
+        }
+        rgb888
+    }
+
     fn as_bytes(&self) -> &[u8] {
         unsafe { slice::from_raw_parts(self.frame as *const u8, self.frame_size) }
     }
@@ -32,6 +32,7 @@ pub trait Framebuffer {
     fn save(&self, path: &str) -> Result<()>;
     fn toggle_inverted(&mut self);
     fn toggle_monochrome(&mut self);
+    fn set_rotation(&mut self, n: i8);
 
     fn width(&self) -> u32 {
         let (width, _) = self.dims();
@@ -0,0 +1,236 @@
+use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+use document::{Document, open};
+use device::Device;
+use font::DEFAULT_FONT_SIZE;
+use unit::pt_to_px;
+
+pub const FULLTEXT_INDEX_FILENAME: &str = ".fulltext.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexedBook {
+    pub path: PathBuf,
+    #[serde(default = "default_reflowable")]
+    pub reflowable: bool,
+    // The layout of reflowable documents: the screen dimensions, the screen
+    // density and the font size, in points.
+    #[serde(default)]
+    pub width: u32,
+    #[serde(default)]
+    pub height: u32,
+    #[serde(default)]
+    pub dpi: u16,
+    pub font_size: f32,
+}
+
+// The books of older indexes are assumed to be reflowable until they're indexed again.
+fn default_reflowable() -> bool {
+    true
+}
+
+impl IndexedBook {
+    // The pages of the book as they are in the reader of the given device,
+    // given the font size of the book.
+    pub fn new(path: PathBuf, reflowable: bool, device: &Device, font_size: Option<f32>) -> IndexedBook {
+        let (width, height) = device.screen_dims();
+        IndexedBook {
+            path,
+            reflowable,
+            width,
+            height,
+            dpi: device.dpi,
+            font_size: font_size.unwrap_or(DEFAULT_FONT_SIZE),
+        }
+    }
+
+    // The pages of fixed-layout documents never change.
+    pub fn same_layout(&self, other: &IndexedBook) -> bool {
+        !self.reflowable ||
+        (self.width == other.width && self.height == other.height &&
+         self.dpi == other.dpi && self.font_size == other.font_size)
+    }
+}
+
+// An inverted index mapping each word of the library to the pages it appears on.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct FulltextIndex {
+    pub books: Vec<IndexedBook>,
+    // The postings are pairs of book and page indices, sorted.
+    pub words: BTreeMap<String, Vec<(u32, u32)>>,
+}
+
+// Case and accents are ignored, and so is the punctuation surrounding a word.
+pub fn fold_word(word: &str) -> String {
+    word.nfkd()
+        .filter(|&c| !is_combining_mark(c))
+        .flat_map(|c| c.to_lowercase())
+        .collect::<String>()
+        .trim_matches(|c: char| !c.is_alphanumeric())
+        .to_string()
+}
+
+pub fn fold_words<S: AsRef<str>>(words: &[S]) -> Vec<String> {
+    words.iter().map(|w| fold_word(w.as_ref()))
+         .filter(|w| !w.is_empty()).collect()
+}
+
+pub fn phrase_words(phrase: &str) -> Vec<String> {
+    fold_words(&phrase.split_whitespace().collect::<Vec<&str>>())
+}
+
+// Checks whether the folded words contain the folded phrase.
+pub fn contains_phrase(words: &[String], phrase: &[String]) -> bool {
+    !phrase.is_empty() && words.windows(phrase.len()).any(|w| w == phrase)
+}
+
+impl FulltextIndex {
+    pub fn add_book(&mut self, book: IndexedBook) -> u32 {
+        self.books.push(book);
+        (self.books.len() - 1) as u32
+    }
+
+    // The pages of a book must be added in order.
+    pub fn add_page(&mut self, book: u32, page: u32, words: &[String]) {
+        for word in words {
+            let postings = self.words.entry(word.clone()).or_insert_with(Vec::new);
+            if postings.last() != Some(&(book, page)) {
+                postings.push((book, page));
+            }
+        }
+    }
+
+    pub fn rename_book(&mut self, from: &Path, to: &Path) {
+        for book in self.books.iter_mut().filter(|b| b.path == from) {
+            book.path = to.to_path_buf();
+        }
+    }
+
+    // The books that follow the removed one are shifted.
+    pub fn remove_book(&mut self, path: &Path) {
+        let index = match self.books.iter().position(|b| b.path == path) {
+            Some(index) => index,
+            None => return,
+        };
+        self.books.remove(index);
+        let index = index as u32;
+        for postings in self.words.values_mut() {
+            postings.retain(|&(book, _)| book != index);
+            for posting in postings.iter_mut().filter(|p| p.0 > index) {
+                posting.0 -= 1;
+            }
+        }
+        self.words.retain(|_, postings| !postings.is_empty());
+    }
+
+    // The words of each indexed page of the given book.
+    pub fn book_pages(&self, book: u32) -> BTreeMap<u32, Vec<String>> {
+        let mut pages = BTreeMap::new();
+        for (word, postings) in &self.words {
+            for &(b, page) in postings {
+                if b == book {
+                    pages.entry(page).or_insert_with(Vec::new).push(word.clone());
+                }
+            }
+        }
+        pages
+    }
+
+    // Returns the pages that contain all the given words.
+    pub fn lookup(&self, words: &[String]) -> Vec<(u32, u32)> {
+        let mut result: Option<Vec<(u32, u32)>> = None;
+        for word in words {
+            let postings = match self.words.get(word) {
+                Some(postings) => postings,
+                None => return Vec::new(),
+            };
+            result = Some(match result {
+                None => postings.clone(),
+                Some(pages) => pages.into_iter()
+                                    .filter(|p| postings.binary_search(p).is_ok())
+                                    .collect(),
+            });
+        }
+        result.unwrap_or_default()
+    }
+}
+
+// Lays out the document the way it was when it was indexed.
+pub fn open_indexed(library_path: &Path, book: &IndexedBook) -> Option<Box<Document>> {
+    open(library_path.join(&book.path)).map(|mut doc| {
+        if book.reflowable {
+            doc.layout(book.width as f32, book.height as f32, pt_to_px(book.font_size, book.dpi));
+        }
+        doc
+    })
+}
+
+pub fn page_words(doc: &Document, index: usize) -> Vec<String> {
+    doc.text(index).map(|t| fold_words(&t.words())).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fold_word() {
+        assert_eq!(fold_word("Éléphant,"), "elephant");
+        assert_eq!(fold_word("«well-known»"), "well-known");
+        assert_eq!(fold_word("—"), "");
+    }
+
+    #[test]
+    fn test_lookup() {
+        let mut index = FulltextIndex::default();
+        let a = index.add_book(IndexedBook::new(PathBuf::from("a.pdf"), false, &Device::default(), None));
+        let b = index.add_book(IndexedBook::new(PathBuf::from("b.pdf"), false, &Device::default(), Some(11.0)));
+        index.add_page(a, 0, &phrase_words("The quick brown fox"));
+        index.add_page(a, 1, &phrase_words("the lazy dog, the fox"));
+        index.add_page(b, 3, &phrase_words("A brown dog"));
+        assert_eq!(index.words["the"], vec![(0, 0), (0, 1)]);
+        assert_eq!(index.lookup(&phrase_words("fox")), vec![(0, 0), (0, 1)]);
+        assert_eq!(index.lookup(&phrase_words("Brown DOG")), vec![(1, 3)]);
+        assert_eq!(index.lookup(&phrase_words("brown cat")), vec![]);
+        assert!(index.lookup(&[]).is_empty());
+        assert_eq!(index.book_pages(b).into_iter().collect::<Vec<_>>(),
+                   vec![(3, vec!["a".to_string(), "brown".to_string(), "dog".to_string()])]);
+    }
+
+    #[test]
+    fn test_remove_book() {
+        let mut index = FulltextIndex::default();
+        let a = index.add_book(IndexedBook::new(PathBuf::from("a.pdf"), false, &Device::default(), None));
+        let b = index.add_book(IndexedBook::new(PathBuf::from("b.pdf"), false, &Device::default(), None));
+        index.add_page(a, 0, &phrase_words("red fox"));
+        index.add_page(b, 2, &phrase_words("blue fox"));
+        index.rename_book(Path::new("b.pdf"), Path::new("c.pdf"));
+        index.remove_book(Path::new("a.pdf"));
+        assert_eq!(index.books.len(), 1);
+        assert_eq!(index.books[0].path, PathBuf::from("c.pdf"));
+        assert_eq!(index.lookup(&phrase_words("fox")), vec![(0, 2)]);
+        assert!(!index.words.contains_key("red"));
+    }
+
+    #[test]
+    fn test_same_layout() {
+        let device = Device::from_product("dragon").unwrap();
+        let epub = IndexedBook::new(PathBuf::from("a.epub"), true, &Device::default(), None);
+        let pdf = IndexedBook::new(PathBuf::from("b.pdf"), false, &Device::default(), None);
+        assert!(epub.same_layout(&IndexedBook::new(PathBuf::from("a.epub"), true, &Device::default(), None)));
+        assert!(!epub.same_layout(&IndexedBook::new(PathBuf::from("a.epub"), true, &device, None)));
+        assert!(!epub.same_layout(&IndexedBook::new(PathBuf::from("a.epub"), true, &Device::default(), Some(9.0))));
+        assert!(pdf.same_layout(&IndexedBook::new(PathBuf::from("b.pdf"), false, &device, Some(9.0))));
+    }
+
+    #[test]
+    fn test_contains_phrase() {
+        let words = phrase_words("The quick brown fox jumps.");
+        assert!(contains_phrase(&words, &phrase_words("brown fox")));
+        assert!(!contains_phrase(&words, &phrase_words("quick fox")));
+        assert!(!contains_phrase(&words, &[]));
+    }
+}
@@ -36,7 +36,7 @@ pub enum LinearDir {
     Forward,
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Point {
     pub x: i32,
     pub y: i32,
@@ -268,7 +268,7 @@ impl Vec2 {
 
 
 // Based on https://golang.org/pkg/image/#Rectangle
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Rectangle {
     pub min: Point,
     pub max: Point,
@@ -380,6 +380,13 @@ impl Rectangle {
     pub fn center(&self) -> Point {
         (self.min + self.max) / 2
     }
+
+    pub fn scale(&self, factor: f32) -> Rectangle {
+        Rectangle::new(Point::new((self.min.x as f32 * factor).floor() as i32,
+                                  (self.min.y as f32 * factor).floor() as i32),
+                       Point::new((self.max.x as f32 * factor).ceil() as i32,
+                                  (self.max.y as f32 * factor).ceil() as i32))
+    }
 }
 
 impl Default for Rectangle {
@@ -825,6 +832,13 @@ mod tests {
         assert!(c.contains(&b));
     }
 
+    #[test]
+    fn scaled_rectangles() {
+        let a = rect![2, 3, 10, 11];
+        assert_eq!(a.scale(2.0), rect![4, 6, 20, 22]);
+        assert_eq!(a.scale(0.5), rect![1, 1, 5, 6]);
+    }
+
     #[test]
     fn divide_integers() {
         let a: i32 = 73;
@@ -2,18 +2,21 @@ extern crate serde_json;
 extern crate reqwest;
 
 use std::env;
-use std::fs;
-use std::io::Read;
-use std::path::{self, Path, PathBuf};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 use regex::Regex;
-use fnv::FnvHashSet;
 use getopts::Options;
 use titlecase::titlecase;
 use helpers::{load_json, save_json};
-use metadata::{Info, FileInfo, Metadata, METADATA_FILENAME, IMPORTED_MD_FILENAME};
-use document::{Document, file_kind, open, asciify};
+use metadata::{Info, Metadata, METADATA_FILENAME, IMPORTED_MD_FILENAME};
+use document::{Anchor, open, asciify, chapter_at, chapter_of_anchor, reader_layout};
 use html_entities::decode_html_entities;
-use symbolic_path;
+use fulltext::{FulltextIndex, IndexedBook, FULLTEXT_INDEX_FILENAME, page_words};
+use device::Device;
+use library::{import, extract_info};
+use merge::{MergePolicy, merge};
+use sync::{sync, content_hash};
 use errors::*;
 
 pub fn run() -> Result<()> {
@@ -30,6 +33,14 @@ pub fn run() -> Result<()> {
     opts.optflag("C", "consolidate", "Consolidate an existing database.");
     opts.optflag("N", "rename", "Rename files based on their info.");
     opts.optflag("Z", "initialize", "Initialize a database.");
+    opts.optflag("E", "export-annotations", "Export the annotations and bookmarks.");
+    opts.optflag("T", "index-content", "Build the full-text index.");
+    opts.optflag("G", "merge", "Merge the output file into the input file.");
+    opts.optflag("Y", "sync", "Synchronize the reading states of the given libraries.");
+    opts.optflag("F", "furthest", "Keep the furthest position when synchronizing.");
+    opts.optopt("p", "policy", "Merge conflict policy: ours, theirs, longest or newer.", "POLICY");
+    opts.optopt("f", "format", "Export format: markdown or json.", "FORMAT");
+    opts.optopt("D", "device", "Target device, given by the value of PRODUCT on the device.", "PRODUCT");
     opts.optopt("i", "input", "Input file name.", "INPUT_NAME");
     opts.optopt("o", "output", "Output file name.", "OUTPUT_NAME");
 
@@ -38,7 +49,7 @@ pub fn run() -> Result<()> {
     )?;
 
     if matches.opt_present("h") {
-        println!("{}", opts.usage("Usage: plato -h|-I|-S|-R[s]|-M|-C|-N|-Z [-i INPUT_NAME] [-o OUTPUT_NAME] LIBRARY_PATH"));
+        println!("{}", opts.usage("Usage: plato -h|-I|-S|-R[s]|-M|-C|-N|-Z|-E|-T|-G|-Y[F] [-f FORMAT] [-p POLICY] [-D PRODUCT] [-i INPUT_NAME] [-o OUTPUT_NAME] LIBRARY_PATH..."));
         return Ok(());
     }
 
@@ -48,7 +59,26 @@ pub fn run() -> Result<()> {
 
     let library_path = Path::new(&matches.free[0]);
     let input_name = matches.opt_str("i").unwrap_or_else(|| METADATA_FILENAME.to_string());
-    let output_name = matches.opt_str("o").unwrap_or_else(|| IMPORTED_MD_FILENAME.to_string());
+    let export_format = match matches.opt_str("f") {
+        Some(name) => ExportFormat::from_name(&name)?,
+        None => ExportFormat::Markdown,
+    };
+    let default_output_name = if matches.opt_present("E") {
+        export_format.default_name()
+    } else {
+        IMPORTED_MD_FILENAME
+    };
+    let merge_policy = match matches.opt_str("p") {
+        Some(name) => MergePolicy::from_name(&name)?,
+        None => MergePolicy::Newer,
+    };
+    let output_name = matches.opt_str("o").unwrap_or_else(|| default_output_name.to_string());
+    let device = match matches.opt_str("D") {
+        Some(product) => Device::from_product(&product).ok_or_else(|| {
+            Error::from(format!("Unknown device: {}.", product))
+        })?,
+        None => Device::default(),
+    };
 
     let input_path = library_path.join(&input_name);
     let output_path = library_path.join(&output_name);
@@ -63,12 +93,49 @@ pub fn run() -> Result<()> {
     } else if matches.opt_present("I") {
         let metadata = load_json(input_path)?;
         let metadata = import(library_path, &metadata)?;
+        for info in &metadata {
+            println!("{}", info.file.path.display());
+        }
         save_json(&metadata, output_path)?;
+    } else if matches.opt_present("E") {
+        let metadata = load_json(input_path)?;
+        export(library_path, &metadata, export_format, &output_path, &device)?;
+    } else if matches.opt_present("G") {
+        let mut metadata = load_json(&input_path)?;
+        let conflicts = merge(&mut metadata, load_json(output_path)?, merge_policy);
+        for c in &conflicts {
+            println!("{}: {}: {:?} / {:?} → {:?}.", c.path.display(), c.field, c.ours, c.theirs, c.kept);
+        }
+        if !conflicts.is_empty() {
+            eprintln!("{} conflicts.", conflicts.len());
+        }
+        save_json(&metadata, input_path)?;
+    } else if matches.opt_present("Y") {
+        if matches.free.len() < 2 {
+            return Err(Error::from("At least two library paths are required."));
+        }
+        let libraries: Vec<PathBuf> = matches.free.iter().map(PathBuf::from).collect();
+        let mut metadata = Vec::new();
+        for library_path in &libraries {
+            metadata.push(load_json::<Metadata, _>(library_path.join(&input_name))?);
+        }
+        let synced = sync(&mut metadata, matches.opt_present("F"), |k, path| {
+            content_hash(&libraries[k].join(path))
+        });
+        for (library_path, md) in libraries.iter().zip(&metadata) {
+            save_json(md, library_path.join(&input_name))?;
+        }
+        println!("Synchronized {} books.", synced);
+    } else if matches.opt_present("T") {
+        let metadata = load_json(input_path)?;
+        let previous = load_json(library_path.join(FULLTEXT_INDEX_FILENAME)).ok();
+        let index = index_content(library_path, &metadata, previous, &device);
+        save_json(&index, library_path.join(FULLTEXT_INDEX_FILENAME))?;
     } else {
         let mut metadata = load_json(&output_path)?;
 
         if matches.opt_present("S") {
-            extract_isbn(library_path, &mut metadata);
+            extract_info(library_path, &mut metadata, false, true);
         }
 
         if matches.opt_present("R") {
@@ -76,7 +143,7 @@ pub fn run() -> Result<()> {
         }
 
         if matches.opt_present("M") {
-            extract_metadata(library_path, &mut metadata);
+            extract_info(library_path, &mut metadata, true, false);
         }
 
         if matches.opt_present("C") {
@@ -93,63 +160,6 @@ pub fn run() -> Result<()> {
     Ok(())
 }
 
-pub fn import(dir: &Path, metadata: &Metadata) -> Result<Metadata> {
-    let files = find_files(dir, dir)?;
-    let known: FnvHashSet<PathBuf> = metadata.iter()
-                                             .map(|info| info.file.path.clone())
-                                             .collect();
-    let mut metadata = Vec::new();
-
-    for file_info in &files {
-        if !known.contains(&file_info.path) {
-            println!("{}", file_info.path.display());
-            let mut info = Info::default();
-            info.file = file_info.clone();
-            if let Some(p) = info.file.path.parent() {
-                let categ = p.to_string_lossy()
-                             .replace(symbolic_path::PATH_SEPARATOR, "")
-                             .replace(path::MAIN_SEPARATOR, &symbolic_path::PATH_SEPARATOR.to_string());
-                if !categ.is_empty() {
-                    info.categories = [categ].iter().cloned().collect();
-                }
-            }
-            metadata.push(info);
-        }
-    }
-
-    Ok(metadata)
-}
-
-pub fn extract_isbn(dir: &Path, metadata: &mut Metadata) {
-    for info in metadata.iter_mut() {
-        if !info.isbn.is_empty() {
-            continue;
-        }
-
-        let path = dir.join(&info.file.path);
-
-        if let Some(isbn) = open(&path).and_then(|d| d.isbn()) {
-            println!("{}", isbn);
-            info.isbn = isbn;
-        }
-    }
-}
-
-pub fn extract_metadata(dir: &Path, metadata: &mut Metadata) {
-    for info in metadata.iter_mut() {
-        if !info.title.is_empty() {
-            continue;
-        }
-
-        let path = dir.join(&info.file.path);
-
-        if let Some(doc) = open(&path) {
-            info.title = doc.title().unwrap_or_default();
-            info.author = doc.author().unwrap_or_default();
-        }
-    }
-}
-
 pub fn retreive(metadata: &mut Metadata, strict: bool) {
     for info in metadata.iter_mut() {
         if !info.title.is_empty() {
@@ -238,6 +248,231 @@ pub fn rename(dir: &Path, metadata: &mut Metadata) {
     }
 }
 
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ExportFormat {
+    Markdown,
+    Json,
+}
+
+impl ExportFormat {
+    fn from_name(name: &str) -> Result<ExportFormat> {
+        match name.to_lowercase().as_ref() {
+            "markdown" | "md" => Ok(ExportFormat::Markdown),
+            "json" => Ok(ExportFormat::Json),
+            _ => Err(Error::from(format!("Unknown export format: {}.", name))),
+        }
+    }
+
+    // Markdown exports are written as one file per book, inside a directory.
+    fn default_name(&self) -> &'static str {
+        match *self {
+            ExportFormat::Markdown => "annotations",
+            ExportFormat::Json => "annotations.json",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BookAnnotations {
+    pub title: String,
+    pub author: String,
+    pub path: PathBuf,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub annotations: Vec<ExportedAnnotation>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub bookmarks: Vec<ExportedBookmark>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportedAnnotation {
+    pub page: usize,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub chapter: String,
+    pub text: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub note: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportedBookmark {
+    pub page: usize,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub chapter: String,
+}
+
+pub fn export(dir: &Path, metadata: &Metadata, format: ExportFormat,
+              output_path: &Path, device: &Device) -> Result<()> {
+    let books: Vec<(&Info, BookAnnotations)> = metadata.iter().filter_map(|info| {
+        book_annotations(dir, info, device).map(|book| (info, book))
+    }).collect();
+
+    match format {
+        ExportFormat::Json => {
+            let books: Vec<&BookAnnotations> = books.iter().map(|&(_, ref book)| book).collect();
+            save_json(&books, output_path)
+        },
+        ExportFormat::Markdown => {
+            fs::create_dir_all(output_path).chain_err(|| "Can't create output directory.")?;
+            for &(info, ref book) in &books {
+                let path = output_path.join(markdown_file_name(info));
+                println!("{}", path.display());
+                let mut file = File::create(&path).chain_err(|| "Can't create markdown file.")?;
+                file.write_all(markdown_from_book(book).as_bytes())
+                    .chain_err(|| "Can't write markdown file.")?;
+            }
+            Ok(())
+        },
+    }
+}
+
+fn has_annotations(info: &Info) -> bool {
+    info.reader.as_ref().map_or(false, |r| {
+        !r.annotations.is_empty() || !r.bookmarks.is_empty()
+    })
+}
+
+// In reflowable documents, the chapters are resolved from the anchors, when there's one.
+// Otherwise, the document is laid out as it was when the pages were saved, or, if that's
+// unknown, as it is in the reader of the given device.
+pub fn book_annotations(dir: &Path, info: &Info, device: &Device) -> Option<BookAnnotations> {
+    if !has_annotations(info) {
+        return None;
+    }
+
+    let reader = info.reader.as_ref().unwrap();
+    let toc = open(dir.join(&info.file.path)).and_then(|mut doc| {
+        if doc.is_reflowable() {
+            let (width, height, font_size) = reader.layout.unwrap_or_else(|| {
+                reader_layout(device, reader.font_size)
+            });
+            doc.layout(width, height, font_size);
+        }
+        doc.toc()
+    }).unwrap_or_default();
+
+    let chapter = |page: usize, anchor: Option<&Anchor>| {
+        anchor.and_then(|a| chapter_of_anchor(&toc, a))
+              .or_else(|| chapter_at(&toc, page))
+              .map(|c| c.title).unwrap_or_default()
+    };
+
+    let mut annotations: Vec<ExportedAnnotation> = reader.annotations.iter().map(|a| {
+        ExportedAnnotation {
+            page: a.page + 1,
+            chapter: chapter(a.page, a.anchor.as_ref()),
+            text: a.text.clone(),
+            note: a.note.clone(),
+        }
+    }).collect();
+    annotations.sort_by_key(|a| a.page);
+
+    let bookmarks = reader.bookmarks.iter().map(|&page| {
+        ExportedBookmark {
+            page: page + 1,
+            chapter: chapter(page, reader.bookmark_anchors.get(&page)),
+        }
+    }).collect();
+
+    Some(BookAnnotations {
+        title: info.title.clone(),
+        author: info.author.clone(),
+        path: info.file.path.clone(),
+        annotations,
+        bookmarks,
+    })
+}
+
+// The books are laid out as they are in the reader of the given device, with their own font size.
+// The pages of the books of the previous index that still have the same layout are reused.
+pub fn index_content(dir: &Path, metadata: &Metadata, previous: Option<FulltextIndex>,
+                     device: &Device) -> FulltextIndex {
+    let mut index = FulltextIndex::default();
+    let previous = previous.unwrap_or_default();
+
+    for info in metadata {
+        let font_size = info.reader.as_ref().and_then(|r| r.font_size);
+        let indexed = previous.books.iter().position(|b| {
+            b.path == info.file.path &&
+            b.same_layout(&IndexedBook::new(b.path.clone(), b.reflowable, device, font_size))
+        });
+
+        if let Some(old) = indexed {
+            let n = index.add_book(previous.books[old].clone());
+            for (page, words) in previous.book_pages(old as u32) {
+                index.add_page(n, page, &words);
+            }
+            continue;
+        }
+
+        if let Some(mut doc) = open(dir.join(&info.file.path)) {
+            println!("{}", info.file.path.display());
+            let book = IndexedBook::new(info.file.path.clone(), doc.is_reflowable(), device, font_size);
+            if book.reflowable {
+                let (width, height, font_size) = reader_layout(device, font_size);
+                doc.layout(width, height, font_size);
+            }
+            let n = index.add_book(book);
+            for page in 0..doc.pages_count() {
+                index.add_page(n, page as u32, &page_words(doc.as_ref(), page));
+            }
+        }
+    }
+
+    index
+}
+
+pub fn markdown_from_book(book: &BookAnnotations) -> String {
+    let mut text = if book.title.is_empty() {
+        format!("# {}\n", book.path.display())
+    } else {
+        format!("# {}\n", book.title)
+    };
+
+    if !book.author.is_empty() {
+        text += &format!("\n*{}*\n", book.author);
+    }
+
+    if !book.annotations.is_empty() {
+        text += "\n## Highlights\n";
+        for a in &book.annotations {
+            text += &format!("\n### {}\n\n", location_label(a.page, &a.chapter));
+            text += &format!("> {}\n", a.text);
+            if !a.note.is_empty() {
+                text += &format!("\n{}\n", a.note);
+            }
+        }
+    }
+
+    if !book.bookmarks.is_empty() {
+        text += "\n## Bookmarks\n\n";
+        for b in &book.bookmarks {
+            text += &format!("- {}\n", location_label(b.page, &b.chapter));
+        }
+    }
+
+    text
+}
+
+fn location_label(page: usize, chapter: &str) -> String {
+    if chapter.is_empty() {
+        format!("Page {}", page)
+    } else {
+        format!("Page {} — {}", page, chapter)
+    }
+}
+
+pub fn markdown_file_name(info: &Info) -> PathBuf {
+    let name = file_name_from_info(info);
+    if name.is_empty() {
+        PathBuf::from(info.file.path.file_name().unwrap_or_default()).with_extension("md")
+    } else {
+        PathBuf::from(name).with_extension("md")
+    }
+}
+
 pub fn file_name_from_info(info: &Info) -> String {
     if info.title.is_empty() {
         return "".to_string();
@@ -263,34 +498,3 @@ pub fn label_from_path(path: &Path) -> String {
     path.file_stem().and_then(|p| p.to_str())
         .map(|t| t.replace(|c: char| !c.is_alphanumeric() && c != '-' && c != '\'', " ")).unwrap_or_default()
 }
-
-pub fn find_files(root: &Path, dir: &Path) -> Result<Vec<FileInfo>> {
-    let mut result = Vec::new();
-
-    for entry in fs::read_dir(dir).chain_err(|| "Can't read directory.")? {
-        let entry = entry.chain_err(|| "Can't read directory entry.")?;
-        let path = entry.path();
-
-        if path.is_dir() {
-            result.extend_from_slice(&find_files(root, path.as_path())?);
-        } else {
-            if entry.file_name().to_string_lossy().starts_with('.') {
-                continue;
-            }
-
-            let relat = path.strip_prefix(root).unwrap().to_path_buf();
-            let kind = file_kind(path).unwrap_or_default();
-            let size = entry.metadata().map(|m| m.len()).unwrap_or_default();
-
-            result.push(
-                FileInfo {
-                    path: relat,
-                    kind,
-                    size,
-                }
-            );
-        }
-    }
-
-    Ok(result)
-}
@@ -163,6 +163,17 @@ pub fn parse_raw_events(paths: &[String], tx: &Sender<InputEvent>) -> Result<()>
     Ok(())
 }
 
+// Converts device coordinates into screen coordinates.
+fn rotate_position(pt: Point, rotation: i8, dims: (u32, u32)) -> Point {
+    let (width, height) = (dims.0 as i32, dims.1 as i32);
+    match rotation {
+        1 => pt!(pt.y, width - 1 - pt.x),
+        2 => pt!(width - 1 - pt.x, height - 1 - pt.y),
+        3 => pt!(height - 1 - pt.y, pt.x),
+        _ => pt,
+    }
+}
+
 pub fn device_events(rx: Receiver<InputEvent>, dims: (u32, u32)) -> Receiver<DeviceEvent> {
     let (ty, ry) = mpsc::channel();
     thread::spawn(move || parse_device_events(&rx, &ty, dims));
@@ -193,6 +204,7 @@ pub fn parse_device_events(rx: &Receiver<InputEvent>, ty: &Sender<DeviceEvent>,
             }
         } else if evt.kind == EV_SYN {
             if evt.code == tc.report {
+                let screen_position = rotate_position(position, CURRENT_DEVICE.rotation(), dims);
                 if let Some(&p) = fingers.get(&id) {
                     if pressure > 0 {
                         if p != position {
@@ -200,7 +212,7 @@ pub fn parse_device_events(rx: &Receiver<InputEvent>, ty: &Sender<DeviceEvent>,
                                 id: id,
                                 time: seconds(evt.time),
                                 status: FingerStatus::Motion,
-                                position: position,
+                                position: screen_position,
                             }).unwrap();
                         }
                     } else {
@@ -208,7 +220,7 @@ pub fn parse_device_events(rx: &Receiver<InputEvent>, ty: &Sender<DeviceEvent>,
                             id: id,
                             time: seconds(evt.time),
                             status: FingerStatus::Up,
-                            position: position,
+                            position: screen_position,
                         }).unwrap();
                         fingers.remove(&id);
                     }
@@ -217,7 +229,7 @@ pub fn parse_device_events(rx: &Receiver<InputEvent>, ty: &Sender<DeviceEvent>,
                         id: id,
                         time: seconds(evt.time),
                         status: FingerStatus::Down,
-                        position: position,
+                        position: screen_position,
                     }).unwrap();
                     fingers.insert(id, position);
                 }
@@ -0,0 +1,137 @@
+use std::fs;
+use std::thread;
+use std::sync::mpsc::Sender;
+use std::path::{self, Path, PathBuf};
+use fnv::FnvHashSet;
+use metadata::{Info, FileInfo, Metadata};
+use document::{file_kind, open, ALLOWED_KINDS};
+use view::Event;
+use symbolic_path;
+use errors::*;
+
+#[cfg(feature = "importer")]
+pub fn import(dir: &Path, metadata: &Metadata) -> Result<Metadata> {
+    let known: FnvHashSet<PathBuf> = metadata.iter()
+                                             .map(|info| info.file.path.clone())
+                                             .collect();
+    import_unknown(dir, &known)
+}
+
+fn import_unknown(dir: &Path, known: &FnvHashSet<PathBuf>) -> Result<Metadata> {
+    let files = find_files(dir, dir)?;
+    let mut metadata = Vec::new();
+
+    for file_info in &files {
+        if !known.contains(&file_info.path) {
+            let mut info = Info::default();
+            info.file = file_info.clone();
+            if let Some(p) = info.file.path.parent() {
+                let categ = p.to_string_lossy()
+                             .replace(symbolic_path::PATH_SEPARATOR, "")
+                             .replace(path::MAIN_SEPARATOR, &symbolic_path::PATH_SEPARATOR.to_string());
+                if !categ.is_empty() {
+                    info.categories = [categ].iter().cloned().collect();
+                }
+            }
+            metadata.push(info);
+        }
+    }
+
+    Ok(metadata)
+}
+
+pub fn find_files(root: &Path, dir: &Path) -> Result<Vec<FileInfo>> {
+    let mut result = Vec::new();
+
+    for entry in fs::read_dir(dir).chain_err(|| "Can't read directory.")? {
+        let entry = entry.chain_err(|| "Can't read directory entry.")?;
+        let path = entry.path();
+
+        if path.is_dir() {
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            result.extend_from_slice(&find_files(root, path.as_path())?);
+        } else {
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+
+            let relat = path.strip_prefix(root).unwrap().to_path_buf();
+            let kind = file_kind(path).unwrap_or_default();
+            let size = entry.metadata().map(|m| m.len()).unwrap_or_default();
+
+            result.push(
+                FileInfo {
+                    path: relat,
+                    kind,
+                    size,
+                }
+            );
+        }
+    }
+
+    Ok(result)
+}
+
+// Fills the missing title and author, if `title` is set, and the missing ISBN, if `isbn`
+// is set, of the given entries. Each document is opened once.
+pub fn extract_info(dir: &Path, metadata: &mut Metadata, title: bool, isbn: bool) {
+    for info in metadata.iter_mut() {
+        let title = title && info.title.is_empty();
+        let isbn = isbn && info.isbn.is_empty();
+
+        if !title && !isbn {
+            continue;
+        }
+
+        let path = dir.join(&info.file.path);
+
+        if let Some(doc) = open(&path) {
+            if title {
+                info.title = doc.title().unwrap_or_default();
+                info.author = doc.author().unwrap_or_default();
+            }
+            if isbn {
+                info.isbn = doc.isbn().unwrap_or_default();
+            }
+        }
+    }
+}
+
+// Finds the documents of the library that aren't referenced by the metadata,
+// and the referenced documents whose file is gone. Doesn't require a network connection.
+// Returns the new entries and the paths of the missing files.
+pub fn refresh(dir: &Path, known: &FnvHashSet<PathBuf>) -> Result<(Metadata, Vec<PathBuf>)> {
+    let mut imported = import_unknown(dir, known)?;
+    imported.retain(|info| ALLOWED_KINDS.contains(info.file.kind.as_str()));
+    extract_info(dir, &mut imported, true, true);
+
+    let missing = known.iter()
+                       .filter(|path| !dir.join(path).exists())
+                       .cloned()
+                       .collect();
+
+    Ok((imported, missing))
+}
+
+// Runs `refresh` on another thread, the result is sent as an `Imported` event.
+pub fn refresh_in_background(dir: &Path, metadata: &Metadata, hub: &Sender<Event>) {
+    let dir = dir.to_path_buf();
+    let known: FnvHashSet<PathBuf> = metadata.iter()
+                                             .map(|info| info.file.path.clone())
+                                             .collect();
+    let hub = hub.clone();
+
+    thread::spawn(move || {
+        match refresh(&dir, &known) {
+            Ok((imported, missing)) => {
+                hub.send(Event::Imported(Box::new(imported), missing)).unwrap();
+            },
+            Err(e) => {
+                eprintln!("Can't import new books: {}.", e);
+                hub.send(Event::Notify("Can't read the library.".to_string())).unwrap();
+            },
+        }
+    });
+}
@@ -18,6 +18,7 @@ extern crate regex;
 extern crate chrono;
 extern crate fnv;
 extern crate png;
+extern crate libflate;
 extern crate isbn;
 extern crate titlecase;
 #[cfg(feature = "importer")]
@@ -42,6 +43,10 @@ mod color;
 mod device;
 #[cfg(feature = "importer")]
 mod importer;
+#[cfg(feature = "importer")]
+mod merge;
+#[cfg(feature = "importer")]
+mod sync;
 mod frontlight;
 mod framebuffer;
 mod input;
@@ -51,6 +56,9 @@ mod document;
 mod metadata;
 mod symbolic_path;
 mod settings;
+mod dictionary;
+mod fulltext;
+mod library;
 mod view;
 mod font;
 mod app;
@@ -0,0 +1,205 @@
+use std::path::PathBuf;
+use fnv::FnvHashMap;
+use chrono::{DateTime, Local};
+use metadata::{Info, Metadata};
+use errors::*;
+
+// How to settle a field that has two different non-empty values.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MergePolicy {
+    Ours,
+    Theirs,
+    Longest,
+    // The entry that was opened most recently wins.
+    Newer,
+}
+
+impl MergePolicy {
+    pub fn from_name(name: &str) -> Result<MergePolicy> {
+        match name.to_lowercase().as_ref() {
+            "ours" => Ok(MergePolicy::Ours),
+            "theirs" => Ok(MergePolicy::Theirs),
+            "longest" => Ok(MergePolicy::Longest),
+            "newer" => Ok(MergePolicy::Newer),
+            _ => Err(Error::from(format!("Unknown merge policy: {}.", name))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Conflict {
+    pub path: PathBuf,
+    pub field: &'static str,
+    pub ours: String,
+    pub theirs: String,
+    pub kept: String,
+}
+
+fn text_fields(info: &mut Info) -> Vec<(&'static str, &mut String)> {
+    vec![("title", &mut info.title),
+         ("subtitle", &mut info.subtitle),
+         ("author", &mut info.author),
+         ("year", &mut info.year),
+         ("language", &mut info.language),
+         ("publisher", &mut info.publisher),
+         ("series", &mut info.series),
+         ("edition", &mut info.edition),
+         ("volume", &mut info.volume),
+         ("number", &mut info.number),
+         ("isbn", &mut info.isbn)]
+}
+
+// Books that were never opened are dated by their addition.
+fn last_used(info: &Info) -> DateTime<Local> {
+    info.reader.as_ref().map_or(info.added, |r| r.opened)
+}
+
+fn reading_state(info: &Info) -> String {
+    match info.reader {
+        Some(ref r) if r.finished => "finished".to_string(),
+        Some(ref r) => format!("page {} of {}", r.current_page + 1, r.pages_count),
+        None => "new".to_string(),
+    }
+}
+
+// Merges `theirs` into `ours`, and returns the conflicting fields.
+pub fn merge_info(ours: &mut Info, mut theirs: Info, policy: MergePolicy) -> Vec<Conflict> {
+    let mut conflicts = Vec::new();
+    let path = ours.file.path.clone();
+    let theirs_is_newer = last_used(&theirs) > last_used(ours);
+
+    for ((field, a), (_, b)) in text_fields(ours).into_iter().zip(text_fields(&mut theirs)) {
+        if b.is_empty() || a == b {
+            continue;
+        }
+        if a.is_empty() {
+            *a = b.clone();
+            continue;
+        }
+        let take_theirs = match policy {
+            MergePolicy::Ours => false,
+            MergePolicy::Theirs => true,
+            MergePolicy::Longest => b.chars().count() > a.chars().count(),
+            MergePolicy::Newer => theirs_is_newer,
+        };
+        let ours_value = a.clone();
+        if take_theirs {
+            *a = b.clone();
+        }
+        conflicts.push(Conflict { path: path.clone(), field, ours: ours_value,
+                                  theirs: b.clone(), kept: a.clone() });
+    }
+
+    ours.categories.append(&mut theirs.categories);
+
+    if theirs.added < ours.added {
+        ours.added = theirs.added;
+    }
+
+    // The reading state isn't a text: the longest policy falls back to the newest state.
+    let reading_conflict = ours.reader.is_some() && theirs.reader.is_some() &&
+                           reading_state(ours) != reading_state(&theirs);
+    let take_theirs = match policy {
+        MergePolicy::Ours => ours.reader.is_none(),
+        MergePolicy::Theirs => theirs.reader.is_some(),
+        MergePolicy::Longest | MergePolicy::Newer => theirs.reader.is_some() &&
+                                                     (ours.reader.is_none() || theirs_is_newer),
+    };
+    if reading_conflict {
+        let (ours_state, theirs_state) = (reading_state(ours), reading_state(&theirs));
+        let kept = if take_theirs { theirs_state.clone() } else { ours_state.clone() };
+        conflicts.push(Conflict { path: path.clone(), field: "reader", ours: ours_state,
+                                  theirs: theirs_state, kept });
+    }
+    if take_theirs {
+        ours.reader = theirs.reader.take();
+    }
+
+    conflicts
+}
+
+// Entries are identified by their path. The entries of `theirs` that aren't in `ours` are appended.
+pub fn merge(ours: &mut Metadata, theirs: Metadata, policy: MergePolicy) -> Vec<Conflict> {
+    let mut indices: FnvHashMap<PathBuf, usize> = ours.iter().enumerate()
+                                                      .map(|(i, info)| (info.file.path.clone(), i))
+                                                      .collect();
+    let mut conflicts = Vec::new();
+
+    for info in theirs {
+        if let Some(&index) = indices.get(&info.file.path) {
+            conflicts.extend(merge_info(&mut ours[index], info, policy));
+        } else {
+            indices.insert(info.file.path.clone(), ours.len());
+            ours.push(info);
+        }
+    }
+
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use metadata::ReaderInfo;
+
+    fn book(path: &str, title: &str, author: &str) -> Info {
+        let mut info = Info::default();
+        info.file.path = PathBuf::from(path);
+        info.title = title.to_string();
+        info.author = author.to_string();
+        info
+    }
+
+    fn opened(info: &mut Info, days_ago: i64, current_page: usize) {
+        info.reader = Some(ReaderInfo {
+            opened: Local::now() - Duration::days(days_ago),
+            current_page,
+            pages_count: 100,
+            .. Default::default()
+        });
+    }
+
+    #[test]
+    fn test_merge_entries() {
+        let mut ours = vec![book("a.pdf", "Alpha", ""), book("b.pdf", "Beta", "")];
+        let theirs = vec![book("b.pdf", "", "Bob"), book("c.pdf", "Gamma", ""), book("c.pdf", "", "Carol")];
+        let conflicts = merge(&mut ours, theirs, MergePolicy::Ours);
+        assert!(conflicts.is_empty());
+        assert_eq!(ours.len(), 3);
+        assert_eq!((ours[1].title.as_str(), ours[1].author.as_str()), ("Beta", "Bob"));
+        assert_eq!((ours[2].title.as_str(), ours[2].author.as_str()), ("Gamma", "Carol"));
+    }
+
+    #[test]
+    fn test_policies() {
+        let a = book("a.pdf", "Dune", "Herbert");
+        let b = book("a.pdf", "Dune Messiah", "Frank Herbert");
+
+        let mut info = a.clone();
+        let conflicts = merge_info(&mut info, b.clone(), MergePolicy::Ours);
+        assert_eq!(info.title, "Dune");
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(conflicts[0], Conflict { path: PathBuf::from("a.pdf"), field: "title",
+                                            ours: "Dune".to_string(), theirs: "Dune Messiah".to_string(),
+                                            kept: "Dune".to_string() });
+
+        let mut info = a.clone();
+        merge_info(&mut info, b.clone(), MergePolicy::Longest);
+        assert_eq!(info.author, "Frank Herbert");
+
+        let (mut a, mut b) = (a, b);
+        opened(&mut a, 3, 10);
+        opened(&mut b, 1, 42);
+        let mut info = a.clone();
+        let conflicts = merge_info(&mut info, b.clone(), MergePolicy::Newer);
+        assert_eq!(info.title, "Dune Messiah");
+        assert_eq!(info.reader.map(|r| r.current_page), Some(42));
+        assert_eq!(conflicts.last().map(|c| c.field), Some("reader"));
+
+        let mut info = b.clone();
+        merge_info(&mut info, a, MergePolicy::Newer);
+        assert_eq!(info.title, "Dune Messiah");
+        assert_eq!(info.reader.map(|r| r.current_page), Some(42));
+    }
+}
@@ -1,11 +1,14 @@
 extern crate serde_json;
 
 use std::path::PathBuf;
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, BTreeMap};
 use std::cmp::Ordering;
 use chrono::{Local, DateTime};
 use fnv::FnvHashMap;
-use regex::Regex;
+use regex::{self, Regex};
+use geom::Rectangle;
+use document::Anchor;
+use symbolic_path::SymbolicPath;
 
 pub const METADATA_FILENAME: &str = ".metadata.json";
 pub const IMPORTED_MD_FILENAME: &str = ".metadata-imported.json";
@@ -69,6 +72,15 @@ impl Default for FileInfo {
     }
 }
 
+// The custom zoom factor is relative to the fit to page scale.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ZoomMode {
+    FitToPage,
+    FitToWidth,
+    Custom(f32),
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Margin {
     pub top: f32,
@@ -100,9 +112,53 @@ pub struct ReaderInfo {
     pub cropping_margins: FnvHashMap<usize, Margin>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub font_size: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anchor: Option<Anchor>,
+    // The width, height and font size, in pixels, of the layout in which the
+    // annotations of reflowable documents were placed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layout: Option<(f32, f32, f32)>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zoom_mode: Option<ZoomMode>,
+    #[serde(skip_serializing_if = "::std::ops::Not::not")]
+    pub repage: bool,
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    pub bookmarks: BTreeSet<usize>,
+    // The anchors of the bookmarks of reflowable documents, by page.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub bookmark_anchors: BTreeMap<usize, Anchor>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub annotations: Vec<Annotation>,
     pub finished: bool,
 }
 
+// The rectangles are given in page coordinates, at scale 1. In reflowable documents,
+// the anchor points to the first word, and the page and the rectangles are
+// recomputed from it when the layout changes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Annotation {
+    pub page: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anchor: Option<Anchor>,
+    pub text: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub note: String,
+    pub rects: Vec<Rectangle>,
+}
+
+impl Default for Annotation {
+    fn default() -> Self {
+        Annotation {
+            page: 0,
+            anchor: None,
+            text: String::default(),
+            note: String::default(),
+            rects: Vec::new(),
+        }
+    }
+}
+
 
 impl ReaderInfo {
     pub fn progress(&self) -> f32 {
@@ -159,7 +215,14 @@ impl Default for ReaderInfo {
             current_page: 0,
             pages_count: 1,
             font_size: None,
+            anchor: None,
+            layout: None,
+            zoom_mode: None,
+            repage: false,
             cropping_margins: FnvHashMap::default(),
+            bookmarks: BTreeSet::new(),
+            bookmark_anchors: BTreeMap::new(),
+            annotations: Vec::new(),
             finished: false,
         }
     }
@@ -280,6 +343,223 @@ pub fn make_query(query: &str) -> Option<Regex> {
     Regex::new(&format!("(?i){}", query)).ok()
 }
 
+// Matches the given text literally. The text is lowercased first,
+// since only the lowercase vowels match their accented forms.
+pub fn make_literal_query(text: &str) -> Option<Regex> {
+    make_query(&regex::escape(&text.to_lowercase()))
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Field {
+    Title,
+    Author,
+    Series,
+    Publisher,
+    Language,
+    Year,
+    Kind,
+    Category,
+    Path,
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Field> {
+        match name {
+            "title" => Some(Field::Title),
+            "author" => Some(Field::Author),
+            "series" => Some(Field::Series),
+            "publisher" => Some(Field::Publisher),
+            "lang" | "language" => Some(Field::Language),
+            "year" => Some(Field::Year),
+            "kind" => Some(Field::Kind),
+            "category" => Some(Field::Category),
+            "path" => Some(Field::Path),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Predicate {
+    // Without a field, the title, subtitle, author, categories and path are searched.
+    Text(Option<Field>, Regex),
+    // An inclusive range of years.
+    Years(i32, i32),
+    New,
+    Reading,
+    Finished,
+}
+
+#[derive(Debug, Clone)]
+struct Term {
+    predicate: Predicate,
+    negated: bool,
+}
+
+// A home search query, e.g. `author:tolkien year:>1950 -"the hobbit" OR lang:french`.
+// A book matches if it matches all the terms of one of the alternatives.
+#[derive(Debug, Clone)]
+pub struct BookQuery {
+    alternatives: Vec<Vec<Term>>,
+}
+
+impl BookQuery {
+    pub fn new(text: &str) -> Option<BookQuery> {
+        let mut alternatives = vec![Vec::new()];
+
+        for (token, quoted) in tokenize(text) {
+            if !quoted && (token == "OR" || token == "|") {
+                alternatives.push(Vec::new());
+                continue;
+            }
+
+            let (negated, token) = if token.len() > 1 && token.starts_with('-') {
+                (true, &token[1..])
+            } else {
+                (false, &token[..])
+            };
+
+            let field = token.find(':').and_then(|i| {
+                Field::from_name(&token[..i].to_lowercase())
+                      .map(|f| (f, &token[i+1..]))
+            });
+
+            let predicate = match field {
+                Some((Field::Year, value)) => year_predicate(value)?,
+                Some((field, value)) => Predicate::Text(Some(field), make_literal_query(value)?),
+                None if token.to_lowercase().starts_with("status:") => {
+                    match token[7..].to_lowercase().as_ref() {
+                        "new" => Predicate::New,
+                        "reading" => Predicate::Reading,
+                        "finished" => Predicate::Finished,
+                        _ => return None,
+                    }
+                },
+                None => Predicate::Text(None, make_literal_query(token)?),
+            };
+
+            alternatives.last_mut().unwrap().push(Term { predicate, negated });
+        }
+
+        alternatives.retain(|terms| !terms.is_empty());
+
+        Some(BookQuery { alternatives })
+    }
+
+    pub fn is_match(&self, info: &Info) -> bool {
+        self.alternatives.is_empty() ||
+        self.alternatives.iter().any(|terms| terms.iter().all(|t| t.is_match(info)))
+    }
+}
+
+impl Term {
+    fn is_match(&self, info: &Info) -> bool {
+        let result = match self.predicate {
+            Predicate::Text(None, ref re) => {
+                re.is_match(&info.title) || re.is_match(&info.subtitle) ||
+                re.is_match(&info.author) || info.categories.iter().any(|c| re.is_match(c)) ||
+                re.is_match(&info.file.path.to_string_lossy())
+            },
+            Predicate::Text(Some(field), ref re) => {
+                match field {
+                    Field::Title => re.is_match(&info.title) || re.is_match(&info.subtitle),
+                    Field::Author => re.is_match(&info.author),
+                    Field::Series => re.is_match(&info.series),
+                    Field::Publisher => re.is_match(&info.publisher),
+                    Field::Language => re.is_match(&info.language),
+                    Field::Year => re.is_match(&info.year),
+                    Field::Kind => re.is_match(&info.file.kind),
+                    Field::Category => info.categories.iter().any(|c| re.is_match(c)),
+                    Field::Path => re.is_match(&info.file.path.to_string_lossy()),
+                }
+            },
+            Predicate::Years(min, max) => {
+                info.year.chars().take_while(|c| c.is_digit(10)).collect::<String>()
+                    .parse::<i32>().map(|y| y >= min && y <= max).unwrap_or(false)
+            },
+            Predicate::New => info.reader.is_none(),
+            Predicate::Reading => info.reader.as_ref().map_or(false, |r| !r.finished),
+            Predicate::Finished => info.reader.as_ref().map_or(false, |r| r.finished),
+        };
+        result != self.negated
+    }
+}
+
+// Accepts `>Y`, `>=Y`, `<Y`, `<=Y` and `Y1..Y2`, anything else is matched against the text.
+fn year_predicate(value: &str) -> Option<Predicate> {
+    let parse = |v: &str| v.trim().parse::<i32>().ok();
+    if value.starts_with(">=") {
+        parse(&value[2..]).map(|y| Predicate::Years(y, i32::max_value()))
+    } else if value.starts_with("<=") {
+        parse(&value[2..]).map(|y| Predicate::Years(i32::min_value(), y))
+    } else if value.starts_with('>') {
+        parse(&value[1..]).map(|y| Predicate::Years(y + 1, i32::max_value()))
+    } else if value.starts_with('<') {
+        parse(&value[1..]).map(|y| Predicate::Years(i32::min_value(), y - 1))
+    } else if let Some(i) = value.find("..") {
+        Some(Predicate::Years(parse(&value[..i])?, parse(&value[i+2..])?))
+    } else {
+        make_literal_query(value).map(|re| Predicate::Text(Some(Field::Year), re))
+    }
+}
+
+// Splits the query on white space, except within double quotes.
+// Each token is paired with whether it contained quotes.
+fn tokenize(text: &str) -> Vec<(String, bool)> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut in_quotes = false;
+    let mut quoted = false;
+
+    for c in text.chars() {
+        if c == '"' {
+            in_quotes = !in_quotes;
+            quoted = true;
+        } else if c.is_whitespace() && !in_quotes {
+            if !token.is_empty() {
+                tokens.push((token, quoted));
+                token = String::new();
+            }
+            quoted = false;
+        } else {
+            token.push(c);
+        }
+    }
+
+    if !token.is_empty() {
+        tokens.push((token, quoted));
+    }
+
+    tokens
+}
+
+// Gives the new name of `categ` when the category `from` is renamed `to`.
+// The descendants of `from` follow it.
+pub fn renamed_category(categ: &str, from: &str, to: &str) -> Option<String> {
+    if categ == from {
+        Some(to.to_string())
+    } else if categ.is_descendant_of(from) {
+        Some(format!("{}{}", to, &categ[from.len()..]))
+    } else {
+        None
+    }
+}
+
+pub fn rename_category(metadata: &mut Metadata, from: &str, to: &str) {
+    for info in metadata.iter_mut() {
+        info.categories = info.categories.iter().map(|c| {
+            renamed_category(c, from, to).unwrap_or_else(|| c.clone())
+        }).collect();
+    }
+}
+
+// The descendants of the category are also removed.
+pub fn remove_category(metadata: &mut Metadata, categ: &str) {
+    for info in metadata.iter_mut() {
+        info.categories.retain(|c| c != categ && !c.is_descendant_of(categ));
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
 pub enum SortMethod {
     Opened,
@@ -403,3 +683,81 @@ lazy_static! {
         p
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn book(title: &str, author: &str, year: &str, kind: &str) -> Info {
+        let mut info = Info::default();
+        info.title = title.to_string();
+        info.author = author.to_string();
+        info.year = year.to_string();
+        info.file.kind = kind.to_string();
+        info.file.path = PathBuf::from(format!("{}.{}", title, kind));
+        info
+    }
+
+    #[test]
+    fn test_rename_category() {
+        let mut metadata = vec![book("A", "", "", "pdf"), book("B", "", "", "pdf")];
+        metadata[0].categories = ["sf", "sf.cyberpunk", "fantasy"].iter().map(|c| c.to_string()).collect();
+        metadata[1].categories = ["sfx"].iter().map(|c| c.to_string()).collect();
+        rename_category(&mut metadata, "sf", "fiction.sf");
+        assert_eq!(metadata[0].categories.iter().collect::<Vec<_>>(),
+                   vec!["fantasy", "fiction.sf", "fiction.sf.cyberpunk"]);
+        assert_eq!(metadata[1].categories.iter().collect::<Vec<_>>(), vec!["sfx"]);
+        remove_category(&mut metadata, "fiction");
+        assert_eq!(metadata[0].categories.iter().collect::<Vec<_>>(), vec!["fantasy"]);
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(tokenize(r#"author:tolkien  "the hobbit" -title:"x y""#),
+                   vec![("author:tolkien".to_string(), false),
+                        ("the hobbit".to_string(), true),
+                        ("-title:x y".to_string(), true)]);
+    }
+
+    #[test]
+    fn test_fields() {
+        let hobbit = book("The Hobbit", "J. R. R. Tolkien", "1937", "epub");
+        let silmarillion = book("The Silmarillion", "J. R. R. Tolkien", "1977", "djvu");
+        let query = BookQuery::new("author:TOLKIEN year:>1950").unwrap();
+        assert!(!query.is_match(&hobbit));
+        assert!(query.is_match(&silmarillion));
+        let query = BookQuery::new("tolkien -kind:djvu").unwrap();
+        assert!(query.is_match(&hobbit));
+        assert!(!query.is_match(&silmarillion));
+        let query = BookQuery::new("year:1930..1940").unwrap();
+        assert!(query.is_match(&hobbit));
+        assert!(!query.is_match(&silmarillion));
+    }
+
+    #[test]
+    fn test_accents_and_phrases() {
+        let etranger = book("L’Étranger", "Albert Camus", "1942", "epub");
+        assert!(BookQuery::new("albert camus").unwrap().is_match(&etranger));
+        assert!(BookQuery::new("title:etranger").unwrap().is_match(&etranger));
+        assert!(BookQuery::new("ETRANGER").unwrap().is_match(&etranger));
+        assert!(BookQuery::new("title:ÉTRANGER").unwrap().is_match(&etranger));
+        assert!(!BookQuery::new(r#""camus albert""#).unwrap().is_match(&etranger));
+    }
+
+    #[test]
+    fn test_alternatives() {
+        let hobbit = book("The Hobbit", "J. R. R. Tolkien", "1937", "epub");
+        let mut dune = book("Dune", "Frank Herbert", "1965", "pdf");
+        dune.reader = Some(ReaderInfo::default());
+        let query = BookQuery::new("status:new OR herbert").unwrap();
+        assert!(query.is_match(&hobbit));
+        assert!(query.is_match(&dune));
+        let query = BookQuery::new("status:reading | hobbit kind:pdf").unwrap();
+        assert!(!query.is_match(&hobbit));
+        assert!(query.is_match(&dune));
+        assert!(BookQuery::new("status:lost").is_none());
+        assert!(BookQuery::new("year:>next").is_none());
+        assert!(BookQuery::new("").unwrap().is_match(&dune));
+    }
+}
@@ -1,4 +1,6 @@
 use std::path::PathBuf;
+use std::collections::BTreeSet;
+use metadata::SortMethod;
 
 pub const SETTINGS_PATH: &str = "settings.json";
 
@@ -8,6 +10,33 @@ pub struct Settings {
     pub library_path: PathBuf,
     pub refresh_every: Option<u8>,
     pub summary_size: u8,
+    // Padding added around the content when cropping automatically, relative to the page.
+    pub auto_crop_padding: f32,
+    pub shelf_layout: ShelfLayout,
+    // Look for new and missing files in the library when the application starts.
+    pub import_on_startup: bool,
+    // Move the file of a book into the directory matching its category when it's moved.
+    pub relocate_files: bool,
+    pub collections: Vec<Collection>,
+}
+
+// A saved state of the home screen's filters.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Collection {
+    pub name: String,
+    pub query: String,
+    pub selected_categories: BTreeSet<String>,
+    pub negated_categories: BTreeSet<String>,
+    pub sort_method: SortMethod,
+    pub reverse_order: bool,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ShelfLayout {
+    List,
+    Covers,
 }
 
 impl Default for Settings {
@@ -16,6 +45,11 @@ impl Default for Settings {
             library_path: PathBuf::from("/mnt/onboard/books"),
             refresh_every: Some(24),
             summary_size: 1,
+            auto_crop_padding: 0.01,
+            shelf_layout: ShelfLayout::List,
+            import_on_startup: false,
+            relocate_files: false,
+            collections: Vec::new(),
         }
     }
 }
@@ -0,0 +1,235 @@
+use std::fs::File;
+use std::io::Read;
+use std::hash::Hasher;
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+use fnv::{FnvHashMap, FnvHashSet, FnvHasher};
+use metadata::{Metadata, ReaderInfo, Annotation};
+use document::Anchor;
+
+// Identifies a file by its content, so that renamed files can be matched.
+pub fn content_hash(path: &Path) -> Option<u64> {
+    let mut file = File::open(path).ok()?;
+    let mut hasher = FnvHasher::default();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        match file.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => hasher.write(&buf[..n]),
+            Err(_) => return None,
+        }
+    }
+    Some(hasher.finish())
+}
+
+type AnnotationKey = (Option<Anchor>, usize, String, String);
+
+// The page is only relevant when the annotation has no anchor.
+fn annotation_key(a: &Annotation) -> AnnotationKey {
+    let page = if a.anchor.is_some() { 0 } else { a.page };
+    (a.anchor.clone(), page, a.text.clone(), a.note.clone())
+}
+
+// Combines the reading states of the same book. The position and the finished state
+// come from the most recently opened state, or from the furthest one, and everything
+// else is united.
+pub fn reconcile(readers: &[&ReaderInfo], furthest: bool) -> ReaderInfo {
+    let newest = readers.iter().max_by_key(|r| r.opened).unwrap();
+    let position = if furthest {
+        readers.iter().max_by(|a, b| a.progress().partial_cmp(&b.progress())
+                                      .unwrap_or(Ordering::Equal)).unwrap()
+    } else {
+        newest
+    };
+
+    let mut result = (*newest).clone();
+    result.current_page = position.current_page;
+    result.pages_count = position.pages_count;
+    result.anchor = position.anchor.clone();
+    result.font_size = position.font_size;
+    result.finished = position.finished;
+    // The annotations coming from other layouts will be placed again by the reader.
+    result.layout = if readers.iter().all(|r| r.layout == position.layout) {
+        position.layout
+    } else {
+        None
+    };
+
+    let mut annotations: FnvHashSet<AnnotationKey> = result.annotations.iter()
+        .map(annotation_key).collect();
+
+    for r in readers {
+        result.bookmarks.extend(r.bookmarks.iter().cloned());
+        for (page, anchor) in &r.bookmark_anchors {
+            result.bookmark_anchors.entry(*page).or_insert_with(|| anchor.clone());
+        }
+        for (index, margin) in &r.cropping_margins {
+            result.cropping_margins.entry(*index).or_insert_with(|| margin.clone());
+        }
+        for a in &r.annotations {
+            if annotations.insert(annotation_key(a)) {
+                result.annotations.push(a.clone());
+            }
+        }
+    }
+
+    result
+}
+
+fn find(parents: &[usize], mut group: usize) -> usize {
+    while parents[group] != group {
+        group = parents[group];
+    }
+    group
+}
+
+// Reconciles the reading states of the books shared by several libraries.
+// The entries are matched by path, and the entries that are missing from some libraries
+// are matched by `content_hash`, which receives the index of the library and the path of the file.
+// Returns the number of books whose state was reconciled.
+pub fn sync<F>(metadata: &mut [Metadata], furthest: bool, mut content_hash: F) -> usize
+    where F: FnMut(usize, &Path) -> Option<u64> {
+    let mut by_path: FnvHashMap<PathBuf, usize> = FnvHashMap::default();
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+
+    for md in metadata.iter() {
+        groups.push(md.iter().map(|info| {
+            let count = by_path.len();
+            *by_path.entry(info.file.path.clone()).or_insert(count)
+        }).collect());
+    }
+
+    let count = by_path.len();
+    let mut presence = vec![FnvHashSet::default(); count];
+
+    for (k, library_groups) in groups.iter().enumerate() {
+        for &g in library_groups {
+            presence[g].insert(k);
+        }
+    }
+
+    let mut parents: Vec<usize> = (0..count).collect();
+    let mut by_hash: FnvHashMap<u64, usize> = FnvHashMap::default();
+
+    for (k, md) in metadata.iter().enumerate() {
+        for (i, info) in md.iter().enumerate() {
+            let g = groups[k][i];
+            if presence[g].len() == metadata.len() {
+                continue;
+            }
+            if let Some(hash) = content_hash(k, &info.file.path) {
+                let other = *by_hash.entry(hash).or_insert(g);
+                let (a, b) = (find(&parents, g), find(&parents, other));
+                if a != b {
+                    parents[a] = b;
+                }
+            }
+        }
+    }
+
+    let mut members: FnvHashMap<usize, Vec<(usize, usize)>> = FnvHashMap::default();
+
+    for (k, library_groups) in groups.iter().enumerate() {
+        for (i, &g) in library_groups.iter().enumerate() {
+            members.entry(find(&parents, g)).or_insert_with(Vec::new).push((k, i));
+        }
+    }
+
+    let mut synced = 0;
+
+    for entries in members.values().filter(|e| e.len() > 1) {
+        let reader = {
+            let readers: Vec<&ReaderInfo> = entries.iter()
+                                                   .filter_map(|&(k, i)| metadata[k][i].reader.as_ref())
+                                                   .collect();
+            if readers.is_empty() {
+                continue;
+            }
+            reconcile(&readers, furthest)
+        };
+        for &(k, i) in entries {
+            metadata[k][i].reader = Some(reader.clone());
+        }
+        synced += 1;
+    }
+
+    synced
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, Duration};
+    use metadata::{Info, Margin};
+
+    fn book(path: &str, days_ago: Option<i64>, current_page: usize) -> Info {
+        let mut info = Info::default();
+        info.file.path = PathBuf::from(path);
+        info.reader = days_ago.map(|d| ReaderInfo {
+            opened: Local::now() - Duration::days(d),
+            current_page,
+            pages_count: 100,
+            .. Default::default()
+        });
+        info
+    }
+
+    #[test]
+    fn test_reconcile() {
+        let mut a = book("a.pdf", Some(1), 10).reader.unwrap();
+        let mut b = book("a.pdf", Some(2), 50).reader.unwrap();
+        a.bookmarks.insert(3);
+        a.bookmark_anchors.insert(3, Anchor { chapter: None, offset: 40 });
+        b.bookmarks.insert(7);
+        b.finished = true;
+        b.cropping_margins.insert(0, Margin::new(0.1, 0.1, 0.1, 0.1));
+        let r = reconcile(&[&a, &b], false);
+        assert_eq!(r.current_page, 10);
+        assert_eq!(r.opened, a.opened);
+        assert!(!r.finished);
+        assert_eq!(r.bookmarks.iter().cloned().collect::<Vec<usize>>(), vec![3, 7]);
+        assert_eq!(r.bookmark_anchors.keys().cloned().collect::<Vec<usize>>(), vec![3]);
+        assert!(r.cropping_margins.contains_key(&0));
+        let r = reconcile(&[&a, &b], true);
+        assert_eq!(r.current_page, 50);
+        assert!(r.finished);
+    }
+
+    #[test]
+    fn test_reconcile_annotations() {
+        let mut a = book("a.epub", Some(1), 10).reader.unwrap();
+        let mut b = book("a.epub", Some(2), 20).reader.unwrap();
+        let anchor = Anchor { chapter: Some(2), offset: 120 };
+        a.layout = Some((600.0, 800.0, 25.0));
+        b.layout = Some((1080.0, 1440.0, 40.0));
+        a.annotations.push(Annotation { page: 12, anchor: Some(anchor.clone()),
+                                        text: "fox".to_string(), .. Default::default() });
+        b.annotations.push(Annotation { page: 7, anchor: Some(anchor),
+                                        text: "fox".to_string(), .. Default::default() });
+        b.annotations.push(Annotation { page: 7, text: "dog".to_string(), .. Default::default() });
+        let r = reconcile(&[&a, &b], false);
+        assert_eq!(r.annotations.len(), 2);
+        assert_eq!(r.layout, None);
+        assert_eq!(reconcile(&[&a, &a.clone()], false).layout, a.layout);
+    }
+
+    #[test]
+    fn test_sync() {
+        let mut metadata = vec![vec![book("a.pdf", Some(3), 10), book("b.pdf", None, 0)],
+                                vec![book("a.pdf", Some(1), 20), book("renamed.pdf", Some(2), 30)],
+                                vec![book("c.pdf", Some(1), 5)]];
+        let synced = sync(&mut metadata, false, |_, path| {
+            match path.to_str() {
+                Some("a.pdf") => Some(1),
+                Some("b.pdf") | Some("renamed.pdf") => Some(2),
+                _ => Some(3),
+            }
+        });
+        assert_eq!(synced, 2);
+        let page = |k: usize, i: usize| metadata[k][i].reader.as_ref().map(|r| r.current_page);
+        assert_eq!(page(0, 0), Some(20));
+        assert_eq!(page(1, 0), Some(20));
+        assert_eq!(page(0, 1), Some(30));
+        assert_eq!(page(2, 0), Some(5));
+    }
+}
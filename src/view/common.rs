@@ -2,6 +2,7 @@ use view::{View, Event, Hub, ViewId, EntryId, EntryKind};
 use framebuffer::UpdateMode;
 use geom::{Point, Rectangle};
 use view::menu::Menu;
+use device::CURRENT_DEVICE;
 use app::Context;
 
 pub fn shift(view: &mut View, delta: &Point) {
@@ -44,6 +45,13 @@ pub fn toggle_main_menu(view: &mut View, rect: Rectangle, enable: Option<bool>,
                                             EntryId::ToggleMonochrome,
                                             context.monochrome),
                         EntryKind::Separator,
+                        EntryKind::RadioButton("Portrait".to_string(),
+                                               EntryId::SetRotation(0),
+                                               CURRENT_DEVICE.rotation() % 2 == 0),
+                        EntryKind::RadioButton("Landscape".to_string(),
+                                               EntryId::SetRotation(1),
+                                               CURRENT_DEVICE.rotation() % 2 == 1),
+                        EntryKind::Separator,
                         EntryKind::Command("Take Screenshot".to_string(),
                                            EntryId::TakeScreenshot),
                         EntryKind::Separator,
@@ -0,0 +1,135 @@
+use framebuffer::Framebuffer;
+use font::{Fonts, font_from_style, NORMAL_STYLE};
+use geom::{Rectangle, CornerSpec, BorderSpec, halves};
+use view::{View, Event, Hub, Bus, ViewId};
+use view::{THICKNESS_LARGE, BORDER_RADIUS_SMALL};
+use view::rounded_button::RoundedButton;
+use gesture::GestureEvent;
+use unit::scale_by_dpi;
+use color::{BLACK, WHITE, TEXT_NORMAL};
+use device::{CURRENT_DEVICE, BAR_SIZES};
+use app::Context;
+
+// A question, centered on the screen, with a cancel and a validate button.
+// The given event is sent when the user validates.
+pub struct Confirmation {
+    rect: Rectangle,
+    children: Vec<Box<View>>,
+    id: ViewId,
+    text: String,
+    event: Event,
+}
+
+impl Confirmation {
+    pub fn new(id: ViewId, text: String, event: Event, fonts: &mut Fonts) -> Confirmation {
+        let mut children = Vec::new();
+        let dpi = CURRENT_DEVICE.dpi;
+        let (width, height) = CURRENT_DEVICE.screen_dims();
+        let &(small_height, _) = BAR_SIZES.get(&(height, dpi)).unwrap();
+        let font = font_from_style(fonts, &NORMAL_STYLE, dpi);
+        let x_height = font.x_heights.0 as i32;
+        let padding = font.em() as i32;
+        let button_diameter = small_height as i32;
+
+        let max_text_width = width as i32 - 4 * padding;
+        let text_width = (font.plan(&text, None, None).width as i32).min(max_text_width)
+                                                                   .max(3 * button_diameter);
+        let (small_half_width, big_half_width) = halves(text_width + 2 * padding);
+        let (small_half_height, big_half_height) = halves(5 * x_height + button_diameter + padding);
+        let (x, y) = (width as i32 / 2, height as i32 / 2);
+
+        let rect = rect![x - small_half_width, y - small_half_height,
+                         x + big_half_width, y + big_half_height];
+
+        let cancel_button = RoundedButton::new("close",
+                                               rect![rect.min.x + padding,
+                                                     rect.max.y - padding - button_diameter,
+                                                     rect.min.x + padding + button_diameter,
+                                                     rect.max.y - padding],
+                                               Event::Cancel);
+        children.push(Box::new(cancel_button) as Box<View>);
+
+        let validate_button = RoundedButton::new("check_mark-large",
+                                                 rect![rect.max.x - padding - button_diameter,
+                                                       rect.max.y - padding - button_diameter,
+                                                       rect.max.x - padding,
+                                                       rect.max.y - padding],
+                                                 Event::Validate);
+        children.push(Box::new(validate_button) as Box<View>);
+
+        Confirmation {
+            rect,
+            children,
+            id,
+            text,
+            event,
+        }
+    }
+}
+
+impl View for Confirmation {
+    fn handle_event(&mut self, evt: &Event, hub: &Hub, bus: &mut Bus, _context: &mut Context) -> bool {
+        match *evt {
+            Event::Validate => {
+                bus.push_back(self.event.clone());
+                hub.send(Event::Close(self.id)).unwrap();
+                true
+            },
+            Event::Cancel => {
+                hub.send(Event::Close(self.id)).unwrap();
+                true
+            },
+            Event::Gesture(GestureEvent::Tap { ref center, .. }) if !self.rect.includes(center) => {
+                hub.send(Event::Close(self.id)).unwrap();
+                true
+            },
+            Event::Gesture(..) => true,
+            _ => false,
+        }
+    }
+
+    fn render(&self, fb: &mut Framebuffer, fonts: &mut Fonts) {
+        let dpi = CURRENT_DEVICE.dpi;
+        let border_radius = scale_by_dpi(BORDER_RADIUS_SMALL, dpi) as i32;
+        let border_thickness = scale_by_dpi(THICKNESS_LARGE, dpi) as u16;
+        fb.draw_rounded_rectangle_with_border(&self.rect,
+                                              &CornerSpec::Uniform(border_radius),
+                                              &BorderSpec { thickness: border_thickness,
+                                                            color: BLACK },
+                                              &WHITE);
+
+        let font = font_from_style(fonts, &NORMAL_STYLE, dpi);
+        let x_height = font.x_heights.0 as i32;
+        let padding = font.em() as i32;
+        let max_width = self.rect.width() as i32 - 2 * padding;
+        let plan = font.plan(&self.text, Some(max_width as u32), None);
+        let dx = (self.rect.width() as i32 - plan.width as i32) / 2;
+        let pt = pt!(self.rect.min.x + dx, self.rect.min.y + 3 * x_height);
+
+        font.render(fb, TEXT_NORMAL[1], &plan, &pt);
+    }
+
+    fn is_background(&self) -> bool {
+        true
+    }
+
+    fn id(&self) -> Option<ViewId> {
+        Some(self.id)
+    }
+
+    fn rect(&self) -> &Rectangle {
+        &self.rect
+    }
+
+    fn rect_mut(&mut self) -> &mut Rectangle {
+        &mut self.rect
+    }
+
+    fn children(&self) -> &Vec<Box<View>> {
+        &self.children
+    }
+
+    fn children_mut(&mut self) -> &mut Vec<Box<View>> {
+        &mut self.children
+    }
+}
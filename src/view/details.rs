@@ -0,0 +1,331 @@
+use std::path::{Path, PathBuf};
+use device::{CURRENT_DEVICE, BAR_SIZES};
+use framebuffer::{Framebuffer, UpdateMode};
+use view::{View, Event, Hub, Bus, ViewId, Align, THICKNESS_MEDIUM};
+use view::icon::Icon;
+use view::label::Label;
+use view::filler::Filler;
+use view::input_field::InputField;
+use view::keyboard::{Keyboard, DEFAULT_LAYOUT};
+use gesture::GestureEvent;
+use metadata::{Info, Status};
+use document::HumanSize;
+use font::{Fonts, font_from_style, NORMAL_STYLE};
+use geom::{Rectangle, Dir, halves};
+use color::{BLACK, WHITE};
+use unit::scale_by_dpi;
+use app::Context;
+
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+// The names of the editable fields, in display order.
+const FIELDS: [&str; 11] = ["Title", "Subtitle", "Author", "Year", "Language", "Publisher",
+                            "Series", "Edition", "Volume", "Number", "ISBN"];
+
+fn field_mut(info: &mut Info, index: usize) -> &mut String {
+    match index {
+        0 => &mut info.title,
+        1 => &mut info.subtitle,
+        2 => &mut info.author,
+        3 => &mut info.year,
+        4 => &mut info.language,
+        5 => &mut info.publisher,
+        6 => &mut info.series,
+        7 => &mut info.edition,
+        8 => &mut info.volume,
+        9 => &mut info.number,
+        _ => &mut info.isbn,
+    }
+}
+
+enum Row {
+    Field(usize),
+    Stat(&'static str, String),
+}
+
+fn stat_rows(info: &Info) -> Vec<Row> {
+    let mut rows = vec![Row::Stat("Path", info.file.path.to_string_lossy().into_owned()),
+                        Row::Stat("Kind", info.file.kind.to_uppercase()),
+                        Row::Stat("Size", info.file.size.human_size()),
+                        Row::Stat("Added", info.added.format(DATE_FORMAT).to_string()),
+                        Row::Stat("Categories", info.categories.iter().cloned()
+                                                    .collect::<Vec<String>>().join(", "))];
+    let status = match info.status() {
+        Status::New => "New".to_string(),
+        Status::Reading(progress) => format!("Reading ({:.0}%)", 100.0 * progress),
+        Status::Finished => "Finished".to_string(),
+    };
+    rows.push(Row::Stat("Status", status));
+    if let Some(ref r) = info.reader {
+        rows.push(Row::Stat("Opened", r.opened.format(DATE_FORMAT).to_string()));
+        rows.push(Row::Stat("Page", format!("{} of {}", r.current_page + 1, r.pages_count)));
+        rows.push(Row::Stat("Bookmarks", r.bookmarks.len().to_string()));
+        rows.push(Row::Stat("Annotations", r.annotations.len().to_string()));
+    }
+    rows
+}
+
+// Shows the metadata and reading statistics of a book.
+// The edited fields are written back into the metadata when the focus changes.
+pub struct Details {
+    rect: Rectangle,
+    children: Vec<Box<View>>,
+    path: PathBuf,
+    rows: Vec<Row>,
+    // The index of the first visible row.
+    first_row: usize,
+    rows_per_page: usize,
+    focus: Option<ViewId>,
+}
+
+impl Details {
+    pub fn new(rect: Rectangle, path: &Path, hub: &Hub, context: &mut Context) -> Option<Details> {
+        let rows = {
+            let info = context.metadata.iter().find(|info| info.file.path == path)?;
+            let mut rows = (0..FIELDS.len()).map(Row::Field).collect::<Vec<Row>>();
+            rows.append(&mut stat_rows(info));
+            rows
+        };
+
+        let mut details = Details {
+            rect,
+            children: vec![],
+            path: path.to_path_buf(),
+            rows,
+            first_row: 0,
+            rows_per_page: 1,
+            focus: None,
+        };
+
+        details.update(context);
+        hub.send(Event::Render(rect, UpdateMode::Full)).unwrap();
+
+        Some(details)
+    }
+
+    fn update(&mut self, context: &mut Context) {
+        let mut info = match context.metadata.iter().find(|info| info.file.path == self.path) {
+            Some(info) => info.clone(),
+            None => return,
+        };
+
+        let dpi = CURRENT_DEVICE.dpi;
+        let (_, height) = CURRENT_DEVICE.screen_dims();
+        let &(small_height, big_height) = BAR_SIZES.get(&(height, dpi)).unwrap();
+        let (small_height, big_height) = (small_height as i32, big_height as i32);
+        let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
+        let (small_thickness, big_thickness) = halves(thickness);
+        let padding = font_from_style(&mut context.fonts, &NORMAL_STYLE, dpi).em() as i32;
+        let rect = self.rect;
+
+        self.children.clear();
+
+        let back_icon = Icon::new("back",
+                                  rect![rect.min.x, rect.min.y,
+                                        rect.min.x + small_height, rect.min.y + small_height - small_thickness],
+                                  WHITE,
+                                  Align::Center,
+                                  Event::Back);
+        self.children.push(Box::new(back_icon) as Box<View>);
+
+        let title_label = Label::new(rect![rect.min.x + small_height, rect.min.y,
+                                           rect.max.x - small_height, rect.min.y + small_height - small_thickness],
+                                     info.title(),
+                                     Align::Center);
+        self.children.push(Box::new(title_label) as Box<View>);
+
+        let filler = Filler::new(rect![rect.max.x - small_height, rect.min.y,
+                                       rect.max.x, rect.min.y + small_height - small_thickness],
+                                 WHITE);
+        self.children.push(Box::new(filler) as Box<View>);
+
+        let separator = Filler::new(rect![rect.min.x, rect.min.y + small_height - small_thickness,
+                                          rect.max.x, rect.min.y + small_height + big_thickness],
+                                    BLACK);
+        self.children.push(Box::new(separator) as Box<View>);
+
+        let min_y = rect.min.y + small_height + big_thickness;
+        let mut max_y = rect.max.y - small_height - small_thickness;
+        let mut keyboard = None;
+
+        if self.focus.is_some() {
+            let mut kb_rect = rect![rect.min.x, max_y - 3 * big_height + thickness,
+                                    rect.max.x, max_y];
+            keyboard = Some(Keyboard::new(&mut kb_rect, DEFAULT_LAYOUT.clone()));
+            max_y = kb_rect.min.y - thickness;
+        }
+
+        let row_height = small_height;
+        self.rows_per_page = ((max_y - min_y) / row_height).max(1) as usize;
+
+        // Keep the focused field visible.
+        if let Some(ViewId::DetailsInput(index)) = self.focus {
+            if index < self.first_row || index >= self.first_row + self.rows_per_page {
+                self.first_row = index - index % self.rows_per_page;
+            }
+        }
+
+        let name_width = rect.width() as i32 / 3;
+        let inset = small_height / 8;
+        let last_row = self.rows.len().min(self.first_row + self.rows_per_page);
+        let mut y = min_y;
+
+        for row in &self.rows[self.first_row..last_row] {
+            let name = match *row {
+                Row::Field(index) => FIELDS[index],
+                Row::Stat(name, _) => name,
+            };
+            let name_label = Label::new(rect![rect.min.x, y, rect.min.x + name_width, y + row_height],
+                                        name.to_string(),
+                                        Align::Left(padding));
+            self.children.push(Box::new(name_label) as Box<View>);
+
+            match *row {
+                Row::Field(index) => {
+                    let filler = Filler::new(rect![rect.min.x + name_width, y, rect.max.x, y + row_height],
+                                             WHITE);
+                    self.children.push(Box::new(filler) as Box<View>);
+                    let mut input = InputField::new(rect![rect.min.x + name_width, y + inset,
+                                                          rect.max.x - padding, y + row_height - inset],
+                                                    ViewId::DetailsInput(index),
+                                                    true,
+                                                    None);
+                    input.set_text(field_mut(&mut info, index));
+                    self.children.push(Box::new(input) as Box<View>);
+                },
+                Row::Stat(_, ref value) => {
+                    let value_label = Label::new(rect![rect.min.x + name_width, y, rect.max.x, y + row_height],
+                                                 value.clone(),
+                                                 Align::Left(padding));
+                    self.children.push(Box::new(value_label) as Box<View>);
+                },
+            }
+
+            y += row_height;
+        }
+
+        if y < max_y {
+            let filler = Filler::new(rect![rect.min.x, y, rect.max.x, max_y], WHITE);
+            self.children.push(Box::new(filler) as Box<View>);
+        }
+
+        if let Some(keyboard) = keyboard {
+            let separator = Filler::new(rect![rect.min.x, max_y,
+                                              rect.max.x, max_y + thickness],
+                                        BLACK);
+            self.children.push(Box::new(separator) as Box<View>);
+            self.children.push(Box::new(keyboard) as Box<View>);
+        }
+
+        let separator = Filler::new(rect![rect.min.x, rect.max.y - small_height - small_thickness,
+                                          rect.max.x, rect.max.y - small_height + big_thickness],
+                                    BLACK);
+        self.children.push(Box::new(separator) as Box<View>);
+
+        let page_label = Label::new(rect![rect.min.x, rect.max.y - small_height + big_thickness,
+                                          rect.max.x, rect.max.y],
+                                    format!("{}/{}", self.current_page() + 1, self.pages_count()),
+                                    Align::Center);
+        self.children.push(Box::new(page_label) as Box<View>);
+    }
+
+    // The first row isn't necessarily aligned on a page boundary.
+    fn current_page(&self) -> usize {
+        (self.first_row + self.rows_per_page - 1) / self.rows_per_page
+    }
+
+    fn pages_count(&self) -> usize {
+        let rows_after = self.rows.len() - self.first_row;
+        self.current_page() + (rows_after + self.rows_per_page - 1) / self.rows_per_page
+    }
+
+    fn turn_page(&mut self, next: bool, hub: &Hub, context: &mut Context) {
+        let first_row = if next {
+            self.first_row + self.rows_per_page
+        } else {
+            self.first_row.saturating_sub(self.rows_per_page)
+        };
+        if first_row < self.rows.len() && first_row != self.first_row {
+            self.save_fields(context);
+            self.first_row = first_row;
+            if self.focus.is_some() {
+                self.focus = None;
+                hub.send(Event::Focus(None)).unwrap();
+            }
+            self.update(context);
+            hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+        }
+    }
+
+    fn save_fields(&self, context: &mut Context) {
+        if let Some(info) = context.metadata.iter_mut().find(|info| info.file.path == self.path) {
+            for child in &self.children {
+                if let Some(ViewId::DetailsInput(index)) = child.id() {
+                    if let Some(input) = child.downcast_ref::<InputField>() {
+                        *field_mut(info, index) = input.text().trim().to_string();
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl View for Details {
+    fn handle_event(&mut self, evt: &Event, hub: &Hub, _bus: &mut Bus, context: &mut Context) -> bool {
+        match *evt {
+            Event::Gesture(GestureEvent::Swipe { dir, ref start, .. }) if self.rect.includes(start) => {
+                match dir {
+                    Dir::West => self.turn_page(true, hub, context),
+                    Dir::East => self.turn_page(false, hub, context),
+                    _ => (),
+                }
+                true
+            },
+            Event::Gesture(GestureEvent::Tap { .. }) if self.focus.is_some() => {
+                hub.send(Event::Focus(None)).unwrap();
+                true
+            },
+            Event::Focus(focus) => {
+                if self.focus != focus {
+                    self.save_fields(context);
+                    self.focus = focus;
+                    self.update(context);
+                    hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+                }
+                false
+            },
+            Event::Submit(ViewId::DetailsInput(_), _) => {
+                hub.send(Event::Focus(None)).unwrap();
+                true
+            },
+            Event::Back => {
+                self.save_fields(context);
+                false
+            },
+            _ => false,
+        }
+    }
+
+    fn render(&self, _fb: &mut Framebuffer, _fonts: &mut Fonts) {
+    }
+
+    fn id(&self) -> Option<ViewId> {
+        Some(ViewId::Details)
+    }
+
+    fn rect(&self) -> &Rectangle {
+        &self.rect
+    }
+
+    fn rect_mut(&mut self) -> &mut Rectangle {
+        &mut self.rect
+    }
+
+    fn children(&self) -> &Vec<Box<View>> {
+        &self.children
+    }
+
+    fn children_mut(&mut self) -> &mut Vec<Box<View>> {
+        &mut self.children
+    }
+}
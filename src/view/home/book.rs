@@ -1,41 +1,70 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use fnv::FnvHashMap;
 use device::CURRENT_DEVICE;
-use framebuffer::{Framebuffer, UpdateMode};
+use framebuffer::{Framebuffer, UpdateMode, Pixmap};
 use view::{View, Event, Hub, Bus};
 use font::{MD_TITLE, MD_AUTHOR, MD_YEAR, MD_KIND, MD_SIZE};
-use color::{TEXT_NORMAL, TEXT_INVERTED_HARD};
+use color::{TEXT_NORMAL, TEXT_INVERTED_HARD, SEPARATOR_NORMAL};
 use gesture::GestureEvent;
 use metadata::Info;
 use unit::scale_by_dpi;
 use document::HumanSize;
 use font::{Fonts, font_from_style};
-use geom::{Rectangle, halves};
+use geom::{Rectangle, BorderSpec, halves};
 use app::Context;
 
+const COVER_PADDING: f32 = 12.0;
+
+// The covers of the books, indexed by path.
+pub type Covers = Arc<Mutex<FnvHashMap<PathBuf, Pixmap>>>;
+
 pub struct Book {
     rect: Rectangle,
     children: Vec<Box<View>>,
     info: Info,
+    // The page of a full-text search match.
+    page: Option<usize>,
+    covers: Option<Covers>,
     active: bool,
 }
 
 impl Book {
-    pub fn new(rect: Rectangle, info: Info) -> Book {
+    pub fn new(rect: Rectangle, info: Info, page: Option<usize>, covers: Option<Covers>) -> Book {
         Book {
             rect,
             children: vec![],
             info,
+            page,
+            covers,
             active: false,
         }
     }
 }
 
+// The box in which the cover of a book fits, given the rectangle of its row.
+pub fn cover_dims(rect: &Rectangle) -> (u32, u32) {
+    let padding = scale_by_dpi(COVER_PADDING, CURRENT_DEVICE.dpi) as u32;
+    let height = rect.height().saturating_sub(2 * padding);
+    (3 * height / 4, height)
+}
+
 impl View for Book {
-    fn handle_event(&mut self, evt: &Event, hub: &Hub, _bus: &mut Bus, _context: &mut Context) -> bool {
+    fn handle_event(&mut self, evt: &Event, hub: &Hub, bus: &mut Bus, _context: &mut Context) -> bool {
         match *evt {
             Event::Gesture(GestureEvent::Tap { ref center, .. }) if self.rect.includes(center) => {
                 self.active = true;
                 hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
-                hub.send(Event::Open(Box::new(self.info.clone()))).unwrap();
+                let info = Box::new(self.info.clone());
+                if let Some(page) = self.page {
+                    hub.send(Event::OpenAt(info, page)).unwrap();
+                } else {
+                    hub.send(Event::Open(info)).unwrap();
+                }
+                true
+            },
+            Event::Gesture(GestureEvent::HoldFinger(ref center)) if self.rect.includes(center) => {
+                bus.push_back(Event::ToggleBookMenu(self.rect, Box::new(self.info.clone())));
                 true
             },
             Event::Invalid(ref info) => {
@@ -62,27 +91,48 @@ impl View for Book {
 
         fb.draw_rectangle(&self.rect, scheme[0]);
 
+        // The text is laid out to the right of the cover.
+        let rect = if let Some(ref covers) = self.covers {
+            let padding = scale_by_dpi(COVER_PADDING, dpi) as i32;
+            let (width, height) = cover_dims(&self.rect);
+            let frame = rect![self.rect.min.x + padding, self.rect.min.y + padding,
+                              self.rect.min.x + padding + width as i32,
+                              self.rect.min.y + padding + height as i32];
+            if let Some(pixmap) = covers.lock().unwrap().get(&self.info.file.path) {
+                let pt = pt!(frame.min.x + (width as i32 - pixmap.width) / 2,
+                             frame.min.y + (height as i32 - pixmap.height) / 2);
+                fb.draw_pixmap(pixmap, &pt);
+            } else {
+                fb.draw_rectangle_outline(&frame, &BorderSpec { thickness: 1, color: SEPARATOR_NORMAL });
+            }
+            rect![frame.max.x, self.rect.min.y, self.rect.max.x, self.rect.max.y]
+        } else {
+            self.rect
+        };
+
         let title = self.info.title();
         let author = self.info.author();
-        let year = &self.info.year;
+        // The page of a match takes the place of the year.
+        let year = self.page.map(|p| format!("p. {}", p + 1))
+                       .unwrap_or_else(|| self.info.year.clone());
         let file_info = &self.info.file;
 
         let (x_height, padding, baseline) = {
             let font = font_from_style(fonts, &MD_TITLE, dpi);
             let x_height = font.x_heights.0 as i32;
-            (x_height, font.em() as i32, (self.rect.height() as i32 - 2 * x_height) / 3)
+            (x_height, font.em() as i32, (rect.height() as i32 - 2 * x_height) / 3)
         };
 
         let (small_half_padding, big_half_padding) = halves(padding);
-        let first_width = 3 * self.rect.width() as i32 / 4;
-        let second_width = (self.rect.width() as i32 - first_width) / 2;
+        let first_width = 3 * rect.width() as i32 / 4;
+        let second_width = (rect.width() as i32 - first_width) / 2;
         let width = first_width - padding - small_half_padding;
 
         // Author
         let author_width = {
             let font = font_from_style(fonts, &MD_AUTHOR, dpi);
             let plan = font.plan(author, Some(width as u32), None);
-            let pt = pt!(self.rect.min.x + padding, self.rect.max.y - baseline);
+            let pt = pt!(rect.min.x + padding, rect.max.y - baseline);
             font.render(fb, scheme[1], &plan, &pt);
             plan.width as i32
         };
@@ -100,8 +150,8 @@ impl View for Book {
                         let mut plan2 = plan.split_off(index, usable_width);
                         let max_width = available - padding;
                         font.crop_right(&mut plan2, max_width as u32);
-                        let pt = pt!(self.rect.min.x + first_width - small_half_padding - plan2.width as i32,
-                                     self.rect.max.y - baseline);
+                        let pt = pt!(rect.min.x + first_width - small_half_padding - plan2.width as i32,
+                                     rect.max.y - baseline);
                         font.render(fb, scheme[1], &plan2, &pt);
                     } else {
                         font.crop_right(&mut plan, width as u32);
@@ -110,18 +160,18 @@ impl View for Book {
                     font.crop_right(&mut plan, width as u32);
                 }
             }
-            let pt = self.rect.min + pt!(padding, baseline + x_height);
+            let pt = rect.min + pt!(padding, baseline + x_height);
             font.render(fb, scheme[1], &plan, &pt);
         }
 
         // Year
         {
             let font = font_from_style(fonts, &MD_YEAR, dpi);
-            let plan = font.plan(year, None, None);
+            let plan = font.plan(&year, None, None);
             let dx = (second_width - padding - plan.width as i32) / 2;
-            let dy = (self.rect.height() as i32 - font.x_heights.1 as i32) / 2;
-            let pt = pt!(self.rect.min.x + first_width + big_half_padding + dx,
-                         self.rect.max.y - dy);
+            let dy = (rect.height() as i32 - font.x_heights.1 as i32) / 2;
+            let pt = pt!(rect.min.x + first_width + big_half_padding + dx,
+                         rect.max.y - dy);
             font.render(fb, scheme[1], &plan, &pt);
         }
 
@@ -132,8 +182,8 @@ impl View for Book {
             let mut plan = font.plan(&kind, None, None);
             let letter_spacing = scale_by_dpi(3.0, dpi) as u32;
             plan.space_out(letter_spacing);
-            let pt = pt!(self.rect.max.x - padding - plan.width as i32,
-                         self.rect.min.y + baseline + x_height);
+            let pt = pt!(rect.max.x - padding - plan.width as i32,
+                         rect.min.y + baseline + x_height);
             font.render(fb, scheme[1], &plan, &pt);
         }
 
@@ -142,8 +192,8 @@ impl View for Book {
             let size = file_info.size.human_size();
             let font = font_from_style(fonts, &MD_SIZE, dpi);
             let plan = font.plan(&size, None, None);
-            let pt = pt!(self.rect.max.x - padding - plan.width as i32,
-                         self.rect.max.y - baseline);
+            let pt = pt!(rect.max.x - padding - plan.width as i32,
+                         rect.max.y - baseline);
             font.render(fb, scheme[1], &plan, &pt);
         }
     }
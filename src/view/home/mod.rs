@@ -7,29 +7,43 @@ mod summary;
 mod category;
 mod shelf;
 mod book;
-mod search_bar;
 mod bottom_bar;
 
 use std::f32;
-use std::sync::mpsc;
+use std::thread;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::collections::BTreeSet;
+use fnv::FnvHashMap;
 use chrono::Local;
-use metadata::{Metadata, SortMethod, sort};
+use metadata::{Info, Metadata, SortMethod, BookQuery, sort};
+use metadata::{renamed_category, rename_category, remove_category};
+use fulltext::{FulltextIndex, IndexedBook, FULLTEXT_INDEX_FILENAME};
+use fulltext::{phrase_words, contains_phrase, open_indexed, page_words};
 use framebuffer::{Framebuffer, UpdateMode};
 use view::{View, Event, Hub, Bus, ViewId, EntryId, EntryKind, THICKNESS_MEDIUM};
 use view::filler::Filler;
 use self::top_bar::TopBar;
 use self::summary::Summary;
 use self::shelf::Shelf;
-use self::search_bar::SearchBar;
+use view::search_bar::SearchBar;
 use view::common::{shift, locate, locate_by_id, toggle_main_menu};
 use view::keyboard::{Keyboard, DEFAULT_LAYOUT};
 use view::go_to_page::GoToPage;
 use view::menu::Menu;
+use view::notification::Notification;
+use view::confirmation::Confirmation;
+use view::named_input::NamedInput;
 use self::bottom_bar::BottomBar;
 use device::{CURRENT_DEVICE, BAR_SIZES};
-use symbolic_path::SymbolicPath;
-use helpers::save_json;
+use symbolic_path::{SymbolicPath, PATH_SEPARATOR};
+use settings::{ShelfLayout, Collection};
+use helpers::{load_json, save_json};
+use library::refresh_in_background;
+use document::thumbnail::thumbnails_dir;
 use unit::scale_by_dpi;
 use app::Context;
 use color::BLACK;
@@ -37,6 +51,13 @@ use geom::{Rectangle, CycleDir, halves, small_half};
 use font::Fonts;
 use errors::*;
 
+// The object of the category and book menus.
+#[derive(Debug, Clone)]
+enum Target {
+    Category(String),
+    Book(PathBuf),
+}
+
 #[derive(Debug)]
 pub struct Home {
     rect: Rectangle,
@@ -49,6 +70,13 @@ pub struct Home {
     sort_method: SortMethod,
     reverse_order: bool,
     visible_books: Metadata,
+    // The pages of the full-text matches, parallel to the visible books.
+    visible_pages: Vec<usize>,
+    content_search: bool,
+    content_matches: FnvHashMap<PathBuf, Vec<usize>>,
+    fulltext: Option<FulltextIndex>,
+    search_running: Option<Arc<AtomicBool>>,
+    target: Option<Target>,
     visible_categories: BTreeSet<String>,
     selected_categories: BTreeSet<String>,
     negated_categories: BTreeSet<String>,
@@ -61,7 +89,7 @@ impl Home {
         let metadata = &mut context.metadata;
         let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
         let (small_thickness, big_thickness) = halves(thickness);
-        let (_, height) = CURRENT_DEVICE.dims;
+        let (_, height) = CURRENT_DEVICE.screen_dims();
         let &(small_height, big_height) = BAR_SIZES.get(&(height, dpi)).unwrap();
 
         let mut children = Vec::new();
@@ -122,9 +150,12 @@ impl Home {
         children.push(Box::new(separator) as Box<View>);
 
         let mut shelf = Shelf::new(rect![rect.min.x, s_max_y + thickness,
-                                         rect.max.x, rect.max.y - small_height as i32 - small_thickness]);
+                                         rect.max.x, rect.max.y - small_height as i32 - small_thickness],
+                                   context.settings.library_path.clone(),
+                                   context.settings.shelf_layout,
+                                   hub);
 
-        shelf.update(&visible_books[..max_lines], &tx);
+        shelf.update(&visible_books[..max_lines], &[], &tx);
 
         children.push(Box::new(shelf) as Box<View>);
 
@@ -154,6 +185,12 @@ impl Home {
             sort_method,
             reverse_order,
             visible_books: visible_books,
+            visible_pages: Vec::new(),
+            content_search: false,
+            content_matches: FnvHashMap::default(),
+            fulltext: None,
+            search_running: None,
+            target: None,
             visible_categories: visible_categories,
             selected_categories: selected_categories,
             negated_categories: negated_categories,
@@ -164,14 +201,14 @@ impl Home {
         let fonts = &mut context.fonts;
         let metadata = &mut context.metadata;
 
-        self.visible_books = metadata.iter().filter(|info| {
-            // TODO: case insensitive, scoped queries, etc.
-            // NOTE: self.query.is_empty() isn't necessary here, because x.find("") is Some(0)
-            (self.query.is_empty() || info.title.find(&self.query).is_some() ||
-                                      info.subtitle.find(&self.query).is_some() ||
-                                      info.author.find(&self.query).is_some() ||
-                                      info.categories.iter().any(|c| c.find(&self.query).is_some()) ||
-                                      info.file.path.to_str().and_then(|s| s.find(&self.query)).is_some()) &&
+        // The full-text matches are filtered by the categories, like the books.
+        let content_search = self.content_search && !self.query.is_empty();
+
+        // An invalid query matches nothing.
+        let query = BookQuery::new(if content_search { "" } else { &self.query });
+
+        let books: Vec<&Info> = metadata.iter().filter(|info| {
+            query.as_ref().map_or(false, |q| q.is_match(info)) &&
                 (self.selected_categories.is_subset(&info.categories) ||
                  self.selected_categories.iter()
                                          .all(|s| info.categories
@@ -179,7 +216,23 @@ impl Home {
                 (self.negated_categories.is_empty() ||
                  (self.negated_categories.is_disjoint(&info.categories) &&
                   info.categories.iter().all(|c| c.ancestors().all(|a| !self.negated_categories.contains(a)))))
-        }).cloned().collect();
+        }).collect();
+
+        self.visible_books.clear();
+        self.visible_pages.clear();
+
+        if content_search {
+            for info in books {
+                if let Some(pages) = self.content_matches.get(&info.file.path) {
+                    for &page in pages {
+                        self.visible_books.push(info.clone());
+                        self.visible_pages.push(page);
+                    }
+                }
+            }
+        } else {
+            self.visible_books = books.into_iter().cloned().collect();
+        }
 
         self.visible_categories = self.visible_books.iter()
                                       .flat_map(|info| info.categories.clone()).collect();
@@ -216,7 +269,7 @@ impl Home {
         if reset_page  {
             self.current_page = 0;
         } else if self.current_page >= self.pages_count {
-            self.current_page = self.pages_count - 1;
+            self.current_page = self.pages_count.saturating_sub(1);
         }
 
         if update {
@@ -316,7 +369,7 @@ impl Home {
 
     fn update_shelf(&mut self, was_resized: bool, hub: &Hub) {
         let dpi = CURRENT_DEVICE.dpi;
-        let (_, height) = CURRENT_DEVICE.dims;
+        let (_, height) = CURRENT_DEVICE.screen_dims();
         let &(_, big_height) = BAR_SIZES.get(&(height, dpi)).unwrap();
         let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
 
@@ -347,7 +400,13 @@ impl Home {
         let index_lower = self.current_page * max_lines;
         let index_upper = (index_lower + max_lines).min(self.visible_books.len());
 
-        shelf.update(&self.visible_books[index_lower..index_upper], hub);
+        let pages = if self.visible_pages.is_empty() {
+            &self.visible_pages[..]
+        } else {
+            &self.visible_pages[index_lower..index_upper]
+        };
+
+        shelf.update(&self.visible_books[index_lower..index_upper], pages, hub);
     }
 
     fn update_top_bar(&mut self, search_visible: bool, hub: &Hub) {
@@ -372,7 +431,7 @@ impl Home {
 
     fn toggle_keyboard(&mut self, enable: bool, update: bool, hub: &Hub, fonts: &mut Fonts) {
         let dpi = CURRENT_DEVICE.dpi;
-        let (_, height) = CURRENT_DEVICE.dims;
+        let (_, height) = CURRENT_DEVICE.screen_dims();
         let &(small_height, big_height) = BAR_SIZES.get(&(height, dpi)).unwrap();
         let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
         let (small_thickness, big_thickness) = halves(thickness);
@@ -475,7 +534,7 @@ impl Home {
 
     fn toggle_search_bar(&mut self, enable: Option<bool>, update: bool, hub: &Hub, fonts: &mut Fonts) {
         let dpi = CURRENT_DEVICE.dpi;
-        let (_, height) = CURRENT_DEVICE.dims;
+        let (_, height) = CURRENT_DEVICE.screen_dims();
         let &(small_height, big_height) = BAR_SIZES.get(&(height, dpi)).unwrap();
         let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
         let small_thickness = small_half(thickness);
@@ -502,6 +561,7 @@ impl Home {
 
             self.resize_summary(-delta_y, false, hub, fonts);
             self.query.clear();
+            self.stop_content_search();
 
             search_visible = false;
         } else {
@@ -511,9 +571,16 @@ impl Home {
 
             let sp_rect = *self.child(5).rect() - pt!(0, small_height as i32);
 
+            let placeholder = if self.content_search {
+                "Phrase"
+            } else {
+                "Title, author, category"
+            };
+
             let search_bar = SearchBar::new(rect![self.rect.min.x, sp_rect.max.y,
                                                   self.rect.max.x,
-                                                  sp_rect.max.y + small_height as i32 - small_thickness]);
+                                                  sp_rect.max.y + small_height as i32 - small_thickness],
+                                            placeholder);
 
             self.children.insert(5, Box::new(search_bar) as Box<View>);
 
@@ -526,12 +593,18 @@ impl Home {
                 shelf.rect.max.y -= small_height as i32;
             }
 
-            if locate::<Keyboard>(self).is_none() {
+            if locate::<Keyboard>(self).is_none() && self.query.is_empty() {
                 self.toggle_keyboard(true, false, hub, fonts);
             }
 
-            self.focus = Some(ViewId::SearchInput);
-            hub.send(Event::Focus(Some(ViewId::SearchInput))).unwrap();
+            // The query of a collection is shown without the keyboard.
+            if self.query.is_empty() {
+                self.focus = Some(ViewId::SearchInput);
+                hub.send(Event::Focus(Some(ViewId::SearchInput))).unwrap();
+            } else {
+                let index = locate::<SearchBar>(self).unwrap();
+                self.children[index].as_mut().downcast_mut::<SearchBar>().unwrap().set_text(&self.query);
+            }
 
             self.resize_summary(delta_y - big_height as i32, false, hub, fonts);
             search_visible = true;
@@ -618,7 +691,7 @@ impl Home {
         }
     }
 
-    fn toggle_matches_menu(&mut self, rect: Rectangle, enable: Option<bool>, hub: &Hub, fonts: &mut Fonts) {
+    fn toggle_matches_menu(&mut self, rect: Rectangle, enable: Option<bool>, hub: &Hub, context: &mut Context) {
         if let Some(index) = locate_by_id(self, ViewId::MatchesMenu) {
             if let Some(true) = enable {
                 return;
@@ -629,20 +702,424 @@ impl Home {
             if let Some(false) = enable {
                 return;
             }
-            let entries = &[EntryKind::Command("Export".to_string(),
-                                               EntryId::ExportMatches)];
-            let matches_menu = Menu::new(rect, ViewId::MatchesMenu, entries, fonts);
+            let entries = &[EntryKind::Command("Import".to_string(),
+                                               EntryId::Import),
+                            EntryKind::Command("Export".to_string(),
+                                               EntryId::ExportMatches),
+                            EntryKind::Separator,
+                            EntryKind::CheckBox("Search Content".to_string(),
+                                                EntryId::ToggleContentSearch,
+                                                self.content_search),
+                            EntryKind::CheckBox("Show Covers".to_string(),
+                                                EntryId::ToggleCovers,
+                                                context.settings.shelf_layout == ShelfLayout::Covers)];
+            let matches_menu = Menu::new(rect, ViewId::MatchesMenu, entries, &mut context.fonts);
             hub.send(Event::Render(*matches_menu.rect(), UpdateMode::Gui)).unwrap();
             self.children.push(Box::new(matches_menu) as Box<View>);
         }
     }
 
+    fn toggle_collections_menu(&mut self, rect: Rectangle, enable: Option<bool>, hub: &Hub, context: &mut Context) {
+        if let Some(index) = locate_by_id(self, ViewId::CollectionsMenu) {
+            if let Some(true) = enable {
+                return;
+            }
+            hub.send(Event::Expose(*self.child(index).rect())).unwrap();
+            self.children.remove(index);
+        } else {
+            if let Some(false) = enable {
+                return;
+            }
+            let current = self.collection("");
+            let mut entries: Vec<EntryKind> = context.settings.collections.iter().enumerate().map(|(i, c)| {
+                let is_current = *c == Collection { name: c.name.clone(), .. current.clone() };
+                EntryKind::RadioButton(c.name.clone(), EntryId::LoadCollection(i), is_current)
+            }).collect();
+            if !entries.is_empty() {
+                entries.push(EntryKind::Separator);
+            }
+            entries.push(EntryKind::Command("Save Collection".to_string(),
+                                            EntryId::SaveCollection));
+            let collections_menu = Menu::new(rect, ViewId::CollectionsMenu, &entries, &mut context.fonts);
+            hub.send(Event::Render(*collections_menu.rect(), UpdateMode::Gui)).unwrap();
+            self.children.push(Box::new(collections_menu) as Box<View>);
+        }
+    }
+
+    fn toggle_collection_name(&mut self, enable: bool, hub: &Hub, fonts: &mut Fonts) {
+        if let Some(index) = locate_by_id(self, ViewId::CollectionName) {
+            if enable {
+                return;
+            }
+            hub.send(Event::Expose(*self.child(index).rect())).unwrap();
+            self.children.remove(index);
+            if let Some(ViewId::CollectionNameInput) = self.focus {
+                self.focus = None;
+                self.toggle_keyboard(false, true, hub, fonts);
+            }
+        } else {
+            if !enable {
+                return;
+            }
+            let anchor = self.child(4).rect().center();
+            let named_input = NamedInput::new(&anchor, "Name".to_string(),
+                                              ViewId::CollectionName, ViewId::CollectionNameInput,
+                                              24, fonts);
+            hub.send(Event::Render(*named_input.rect(), UpdateMode::Gui)).unwrap();
+            hub.send(Event::Focus(Some(ViewId::CollectionNameInput))).unwrap();
+            self.children.push(Box::new(named_input) as Box<View>);
+        }
+    }
+
+    fn toggle_category_menu(&mut self, rect: Rectangle, enable: Option<bool>, hub: &Hub, fonts: &mut Fonts) {
+        if let Some(index) = locate_by_id(self, ViewId::CategoryMenu) {
+            if let Some(true) = enable {
+                return;
+            }
+            hub.send(Event::Expose(*self.child(index).rect())).unwrap();
+            self.children.remove(index);
+        } else {
+            if let Some(false) = enable {
+                return;
+            }
+            let entries = &[EntryKind::Command("Rename".to_string(),
+                                               EntryId::RenameCategory),
+                            EntryKind::Command("Move".to_string(),
+                                               EntryId::MoveCategory),
+                            EntryKind::Command("Remove".to_string(),
+                                               EntryId::RemoveCategory)];
+            let category_menu = Menu::new(rect, ViewId::CategoryMenu, entries, fonts);
+            hub.send(Event::Render(*category_menu.rect(), UpdateMode::Gui)).unwrap();
+            self.children.push(Box::new(category_menu) as Box<View>);
+        }
+    }
+
+    fn toggle_book_menu(&mut self, rect: Rectangle, enable: Option<bool>, hub: &Hub, context: &mut Context) {
+        if let Some(index) = locate_by_id(self, ViewId::BookMenu) {
+            if let Some(true) = enable {
+                return;
+            }
+            hub.send(Event::Expose(*self.child(index).rect())).unwrap();
+            self.children.remove(index);
+        } else {
+            if let Some(false) = enable {
+                return;
+            }
+            let info = match self.target_book(&context.metadata) {
+                Some(info) => info,
+                None => return,
+            };
+            let mut entries = vec![EntryKind::Command("Details".to_string(),
+                                                      EntryId::ShowDetails),
+                                   EntryKind::Separator,
+                                   EntryKind::Command("Add Category".to_string(),
+                                                      EntryId::AddBookCategory),
+                                   EntryKind::Command("Move to Category".to_string(),
+                                                      EntryId::MoveBook)];
+            if !info.categories.is_empty() {
+                entries.push(EntryKind::Separator);
+            }
+            for (i, categ) in info.categories.iter().enumerate() {
+                entries.push(EntryKind::Command(format!("Remove {}", categ),
+                                                EntryId::RemoveBookCategory(i)));
+            }
+            entries.push(EntryKind::Separator);
+            entries.push(EntryKind::Command("Remove from Library".to_string(),
+                                            EntryId::RemoveBook));
+            entries.push(EntryKind::Command("Delete File".to_string(),
+                                            EntryId::DeleteBook));
+            let book_menu = Menu::new(rect, ViewId::BookMenu, &entries, &mut context.fonts);
+            hub.send(Event::Render(*book_menu.rect(), UpdateMode::Gui)).unwrap();
+            self.children.push(Box::new(book_menu) as Box<View>);
+        }
+    }
+
+    fn target_book<'a>(&self, metadata: &'a Metadata) -> Option<&'a Info> {
+        match self.target {
+            Some(Target::Book(ref path)) => metadata.iter().find(|info| info.file.path == *path),
+            _ => None,
+        }
+    }
+
+    fn target_category(&self) -> Option<String> {
+        match self.target {
+            Some(Target::Category(ref categ)) => Some(categ.clone()),
+            _ => None,
+        }
+    }
+
+    // The same dialog is used to rename or move a category, and to add a category to a book.
+    fn toggle_category_name(&mut self, input_id: Option<ViewId>, hub: &Hub, context: &mut Context) {
+        if let Some(index) = locate_by_id(self, ViewId::CategoryName) {
+            if input_id.is_some() {
+                return;
+            }
+            hub.send(Event::Expose(*self.child(index).rect())).unwrap();
+            self.children.remove(index);
+            match self.focus {
+                Some(ViewId::RenameCategoryInput) |
+                Some(ViewId::MoveCategoryInput) |
+                Some(ViewId::AddCategoryInput) |
+                Some(ViewId::MoveBookInput) => {
+                    self.focus = None;
+                    self.toggle_keyboard(false, true, hub, &mut context.fonts);
+                },
+                _ => (),
+            }
+        } else if let Some(input_id) = input_id {
+            let categ = self.target_category().unwrap_or_default();
+            let (label, text) = match input_id {
+                ViewId::RenameCategoryInput => ("Name", categ.last_component().to_string()),
+                ViewId::MoveCategoryInput => ("Parent", categ.parent().unwrap_or_default().to_string()),
+                ViewId::MoveBookInput => ("Category", self.target_book(&context.metadata)
+                                                          .and_then(|info| info.categories.iter().next().cloned())
+                                                          .unwrap_or_default()),
+                _ => ("Category", String::new()),
+            };
+            let anchor = self.child(4).rect().center();
+            let mut named_input = NamedInput::new(&anchor, label.to_string(),
+                                                  ViewId::CategoryName, input_id,
+                                                  24, &mut context.fonts);
+            named_input.set_text(&text);
+            hub.send(Event::Render(*named_input.rect(), UpdateMode::Gui)).unwrap();
+            hub.send(Event::Focus(Some(input_id))).unwrap();
+            self.children.push(Box::new(named_input) as Box<View>);
+        }
+    }
+
+    // Renames the category in the library and in the filters.
+    fn rename_category(&mut self, from: &str, to: &str, hub: &Hub, context: &mut Context) {
+        if to.is_empty() || to == from || to.is_descendant_of(from) {
+            return;
+        }
+        rename_category(&mut context.metadata, from, to);
+        let rename = |categs: &BTreeSet<String>| -> BTreeSet<String> {
+            categs.iter().map(|c| renamed_category(c, from, to).unwrap_or_else(|| c.clone())).collect()
+        };
+        self.selected_categories = rename(&self.selected_categories);
+        self.negated_categories = rename(&self.negated_categories);
+        self.refresh_visibles(true, false, hub, context);
+    }
+
+    fn remove_category(&mut self, categ: &str, hub: &Hub, context: &mut Context) {
+        remove_category(&mut context.metadata, categ);
+        self.selected_categories.retain(|c| c != categ && !c.is_descendant_of(categ));
+        self.negated_categories.retain(|c| c != categ && !c.is_descendant_of(categ));
+        self.refresh_visibles(true, false, hub, context);
+    }
+
+    fn submit_category_name(&mut self, input_id: ViewId, text: &str, hub: &Hub, context: &mut Context) {
+        let text = text.trim().trim_matches(PATH_SEPARATOR);
+        match (input_id, self.target.clone()) {
+            (ViewId::RenameCategoryInput, Some(Target::Category(categ))) => {
+                let to = match categ.parent() {
+                    Some(parent) if !text.is_empty() => format!("{}{}{}", parent, PATH_SEPARATOR, text),
+                    _ => text.to_string(),
+                };
+                self.rename_category(&categ, &to, hub, context);
+            },
+            (ViewId::MoveCategoryInput, Some(Target::Category(categ))) => {
+                let to = if text.is_empty() {
+                    categ.last_component().to_string()
+                } else {
+                    format!("{}{}{}", text, PATH_SEPARATOR, categ.last_component())
+                };
+                self.rename_category(&categ, &to, hub, context);
+            },
+            (ViewId::MoveBookInput, Some(Target::Book(path))) => {
+                self.move_book(&path, text, hub, context);
+            },
+            (ViewId::AddCategoryInput, Some(Target::Book(path))) if !text.is_empty() => {
+                if let Some(info) = context.metadata.iter_mut().find(|info| info.file.path == path) {
+                    info.categories.insert(text.to_string());
+                }
+                self.refresh_visibles(true, false, hub, context);
+            },
+            _ => (),
+        }
+    }
+
+    fn remove_book_category(&mut self, index: usize, hub: &Hub, context: &mut Context) {
+        if let Some(Target::Book(ref path)) = self.target {
+            if let Some(info) = context.metadata.iter_mut().find(|info| info.file.path == *path) {
+                if let Some(categ) = info.categories.iter().nth(index).cloned() {
+                    info.categories.remove(&categ);
+                }
+            }
+        }
+        self.refresh_visibles(true, false, hub, context);
+    }
+
+    fn toggle_delete_confirmation(&mut self, enable: bool, hub: &Hub, context: &mut Context) {
+        if let Some(index) = locate_by_id(self, ViewId::DeleteConfirmation) {
+            if enable {
+                return;
+            }
+            hub.send(Event::Expose(*self.child(index).rect())).unwrap();
+            self.children.remove(index);
+        } else {
+            if !enable {
+                return;
+            }
+            let info = match self.target_book(&context.metadata) {
+                Some(info) => info.clone(),
+                None => return,
+            };
+            let text = format!("Delete {}?", info.title());
+            let confirmation = Confirmation::new(ViewId::DeleteConfirmation, text,
+                                                 Event::Delete(Box::new(info)),
+                                                 &mut context.fonts);
+            hub.send(Event::Render(*confirmation.rect(), UpdateMode::Gui)).unwrap();
+            self.children.push(Box::new(confirmation) as Box<View>);
+        }
+    }
+
+    // Removes the book from the library, its file is left untouched.
+    fn remove_book(&mut self, path: &Path, hub: &Hub, context: &mut Context) {
+        context.metadata.retain(|info| info.file.path != path);
+        self.content_matches.remove(path);
+        self.update_fulltext(&context.settings.library_path, |index| index.remove_book(path));
+        self.refresh_visibles(true, false, hub, context);
+    }
+
+    // Applies the given change to the full-text index, if the library has one, and saves it.
+    fn update_fulltext<F>(&mut self, library_path: &Path, f: F) where F: FnOnce(&mut FulltextIndex) {
+        let path = library_path.join(FULLTEXT_INDEX_FILENAME);
+        if self.fulltext.is_none() {
+            if !path.exists() {
+                return;
+            }
+            match load_json::<FulltextIndex, _>(&path) {
+                Ok(index) => self.fulltext = Some(index),
+                Err(e) => {
+                    eprintln!("Can't load the full-text index: {}.", e);
+                    return;
+                },
+            }
+        }
+        if let Some(ref mut index) = self.fulltext {
+            f(index);
+            save_json(index, &path).map_err(|e| {
+                eprintln!("Can't save the full-text index: {}.", e);
+            }).ok();
+        }
+    }
+
+    fn delete_book(&mut self, path: &Path, hub: &Hub, context: &mut Context) {
+        let library_path = context.settings.library_path.clone();
+        match fs::remove_file(library_path.join(path)) {
+            Err(ref e) if e.kind() != io::ErrorKind::NotFound => {
+                eprintln!("Can't delete {}: {}.", path.display(), e);
+                self.show_notification("Can't delete the file.", hub, &mut context.fonts);
+            },
+            _ => {
+                fs::remove_dir_all(thumbnails_dir(&library_path, path)).ok();
+                self.remove_book(path, hub, context);
+            },
+        }
+    }
+
+    // The book's categories are replaced by the given one.
+    fn move_book(&mut self, path: &Path, categ: &str, hub: &Hub, context: &mut Context) {
+        let library_path = context.settings.library_path.clone();
+        let relocate_files = context.settings.relocate_files;
+        let mut failed = false;
+        let mut moved = None;
+
+        if let Some(info) = context.metadata.iter_mut().find(|info| info.file.path == path) {
+            info.categories.clear();
+            if !categ.is_empty() {
+                info.categories.insert(categ.to_string());
+            }
+            if relocate_files {
+                match relocate_file(&library_path, path, &category_dir(categ)) {
+                    Ok(new_path) => {
+                        info.file.path = new_path.clone();
+                        moved = Some(new_path);
+                    },
+                    Err(e) => {
+                        eprintln!("Can't move {}: {}.", path.display(), e);
+                        failed = true;
+                    },
+                }
+            }
+        }
+
+        if let Some(new_path) = moved {
+            if let Some(pages) = self.content_matches.remove(path) {
+                self.content_matches.insert(new_path.clone(), pages);
+            }
+            self.update_fulltext(&library_path, |index| index.rename_book(path, &new_path));
+        }
+
+        if failed {
+            self.show_notification("Can't move the file.", hub, &mut context.fonts);
+        }
+
+        self.refresh_visibles(true, false, hub, context);
+    }
+
+    // The current state of the filters.
+    fn collection(&self, name: &str) -> Collection {
+        Collection {
+            name: name.to_string(),
+            query: self.query.clone(),
+            selected_categories: self.selected_categories.clone(),
+            negated_categories: self.negated_categories.clone(),
+            sort_method: self.sort_method,
+            reverse_order: self.reverse_order,
+        }
+    }
+
+    // A collection with the same name is replaced.
+    fn save_collection(&mut self, name: &str, context: &mut Context) {
+        let name = name.trim();
+        if name.is_empty() {
+            return;
+        }
+        let collection = self.collection(name);
+        let collections = &mut context.settings.collections;
+        if let Some(c) = collections.iter_mut().find(|c| c.name == name) {
+            *c = collection;
+            return;
+        }
+        collections.push(collection);
+    }
+
+    fn load_collection(&mut self, index: usize, hub: &Hub, context: &mut Context) {
+        let collection = match context.settings.collections.get(index) {
+            Some(collection) => collection.clone(),
+            None => return,
+        };
+
+        // Collections are searches on the metadata.
+        self.content_search = false;
+        self.stop_content_search();
+
+        if locate::<SearchBar>(self).is_some() {
+            self.toggle_search_bar(Some(false), true, hub, &mut context.fonts);
+        }
+
+        self.query = collection.query;
+        self.selected_categories = collection.selected_categories;
+        self.negated_categories = collection.negated_categories;
+        self.sort_method = collection.sort_method;
+        self.reverse_order = collection.reverse_order;
+
+        if !self.query.is_empty() {
+            self.toggle_search_bar(Some(true), true, hub, &mut context.fonts);
+        }
+
+        self.refresh_visibles(true, true, hub, context);
+        self.sort(true, &mut context.metadata, hub);
+    }
+
     // Relatively moves the bottom edge of the summary
     // And consequently moves the top edge of the shelf
     // and the separator between them.
     fn resize_summary(&mut self, delta_y: i32, update: bool, hub: &Hub, fonts: &mut Fonts) {
         let dpi = CURRENT_DEVICE.dpi;
-        let (_, height) = CURRENT_DEVICE.dims;
+        let (_, height) = CURRENT_DEVICE.screen_dims();
         let &(small_height, big_height) = BAR_SIZES.get(&(height, dpi)).unwrap();
         let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
 
@@ -711,7 +1188,21 @@ impl Home {
             self.current_page = 0;
         }
         sort(metadata, self.sort_method, self.reverse_order);
-        sort(&mut self.visible_books, self.sort_method, self.reverse_order);
+        if self.visible_pages.is_empty() {
+            sort(&mut self.visible_books, self.sort_method, self.reverse_order);
+        } else {
+            // Keep the matches of each book together, in the order of the library.
+            let order: FnvHashMap<&PathBuf, usize> = metadata.iter().enumerate()
+                                                             .map(|(i, info)| (&info.file.path, i))
+                                                             .collect();
+            let mut rows: Vec<(Info, usize)> = self.visible_books.drain(..)
+                                                   .zip(self.visible_pages.drain(..))
+                                                   .collect();
+            rows.sort_by_key(|&(ref info, page)| (order[&info.file.path], page));
+            let (books, pages) = rows.into_iter().unzip();
+            self.visible_books = books;
+            self.visible_pages = pages;
+        }
         self.update_shelf(false, hub);
         let search_visible = locate::<SearchBar>(self).is_some();
         self.update_top_bar(search_visible, hub);
@@ -725,6 +1216,182 @@ impl Home {
         hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
     }
 
+    fn toggle_covers(&mut self, hub: &Hub, context: &mut Context) {
+        let layout = match context.settings.shelf_layout {
+            ShelfLayout::List => ShelfLayout::Covers,
+            ShelfLayout::Covers => ShelfLayout::List,
+        };
+        context.settings.shelf_layout = layout;
+        self.children[4].as_mut().downcast_mut::<Shelf>().unwrap().layout = layout;
+        self.update_shelf(false, hub);
+    }
+
+    fn toggle_content_search(&mut self, hub: &Hub, context: &mut Context) {
+        self.content_search = !self.content_search;
+        if !self.query.is_empty() {
+            self.search_content(hub, context);
+            self.refresh_visibles(true, true, hub, context);
+        }
+    }
+
+    fn stop_content_search(&mut self) {
+        if let Some(running) = self.search_running.take() {
+            running.store(false, Ordering::Relaxed);
+        }
+        self.content_matches.clear();
+    }
+
+    // The index gives the pages that contain all the words of the query,
+    // the phrases are then checked against the text of these pages.
+    fn search_content(&mut self, hub: &Hub, context: &mut Context) {
+        self.stop_content_search();
+
+        if !self.content_search {
+            return;
+        }
+
+        let words = phrase_words(&self.query);
+
+        if words.is_empty() {
+            return;
+        }
+
+        if self.fulltext.is_none() {
+            let path = context.settings.library_path.join(FULLTEXT_INDEX_FILENAME);
+            match load_json::<FulltextIndex, _>(path) {
+                Ok(index) => self.fulltext = Some(index),
+                Err(e) => {
+                    eprintln!("Can't load the full-text index: {}.", e);
+                    self.show_notification("The library hasn't been indexed.", hub, &mut context.fonts);
+                    return;
+                },
+            }
+        }
+
+        let mut candidates: Vec<(IndexedBook, Vec<usize>)> = Vec::new();
+
+        if let Some(ref index) = self.fulltext {
+            for (book, page) in index.lookup(&words) {
+                let book = &index.books[book as usize];
+                if candidates.last().map_or(true, |c| c.0.path != book.path) {
+                    candidates.push((book.clone(), Vec::new()));
+                }
+                candidates.last_mut().unwrap().1.push(page as usize);
+            }
+        }
+
+        // The pages of the books whose layout changed since they were indexed
+        // don't match the reader's: these books are laid out again and searched page by page.
+        let mut stale = Vec::new();
+
+        candidates.retain(|&(ref book, _)| {
+            let font_size = context.metadata.iter()
+                                   .find(|info| info.file.path == book.path)
+                                   .and_then(|info| info.reader.as_ref())
+                                   .and_then(|r| r.font_size);
+            let current = IndexedBook::new(book.path.clone(), book.reflowable,
+                                           &CURRENT_DEVICE, font_size);
+            if book.same_layout(&current) {
+                true
+            } else {
+                stale.push(current);
+                false
+            }
+        });
+
+        if words.len() == 1 {
+            self.content_matches = candidates.drain(..)
+                                             .map(|(book, pages)| (book.path, pages))
+                                             .collect();
+        }
+
+        if candidates.is_empty() && stale.is_empty() {
+            return;
+        }
+
+        let running = Arc::new(AtomicBool::new(true));
+        self.search_running = Some(running.clone());
+        let library_path = context.settings.library_path.clone();
+        let hub2 = hub.clone();
+
+        thread::spawn(move || {
+            let stale = stale.into_iter().map(|book| (book, None));
+            let candidates = candidates.into_iter().map(|(book, pages)| (book, Some(pages)));
+            for (book, pages) in candidates.chain(stale) {
+                if !running.load(Ordering::Relaxed) {
+                    return;
+                }
+                if let Some(doc) = open_indexed(&library_path, &book) {
+                    let pages = pages.unwrap_or_else(|| (0..doc.pages_count()).collect());
+                    let pages: Vec<usize> = pages.into_iter().filter(|&index| {
+                        contains_phrase(&page_words(doc.as_ref(), index), &words)
+                    }).collect();
+                    if !pages.is_empty() && running.load(Ordering::Relaxed) {
+                        hub2.send(Event::ContentMatches(book.path, pages)).unwrap();
+                    }
+                }
+            }
+        });
+    }
+
+    fn show_notification(&mut self, text: &str, hub: &Hub, fonts: &mut Fonts) {
+        self.close_notification(hub);
+        let notification = Notification::new(text.to_string(), fonts);
+        hub.send(Event::Render(*notification.rect(), UpdateMode::Gui)).unwrap();
+        self.children.push(Box::new(notification) as Box<View>);
+    }
+
+    fn close_notification(&mut self, hub: &Hub) {
+        if let Some(index) = locate_by_id(self, ViewId::Notification) {
+            hub.send(Event::Expose(*self.child(index).rect())).unwrap();
+            self.children.remove(index);
+        }
+    }
+
+    fn import(&mut self, hub: &Hub, context: &mut Context) {
+        refresh_in_background(&context.settings.library_path, &context.metadata, hub);
+        self.show_notification("Looking for new books.", hub, &mut context.fonts);
+    }
+
+    // The new entries were already added to the metadata. The missing books are
+    // only removed once the user confirms it.
+    fn imported(&mut self, added: usize, missing: &[PathBuf], hub: &Hub, context: &mut Context) {
+        self.refresh_visibles(true, false, hub, context);
+        self.sort(false, &mut context.metadata, hub);
+        if missing.is_empty() {
+            let text = format!("{} added.", added);
+            self.show_notification(&text, hub, &mut context.fonts);
+        } else {
+            self.close_notification(hub);
+            self.close_missing_confirmation(hub);
+            let text = format!("{} added. Remove the {} missing books?", added, missing.len());
+            let confirmation = Confirmation::new(ViewId::MissingConfirmation, text,
+                                                 Event::RemoveMissing(missing.to_vec()),
+                                                 &mut context.fonts);
+            hub.send(Event::Render(*confirmation.rect(), UpdateMode::Gui)).unwrap();
+            self.children.push(Box::new(confirmation) as Box<View>);
+        }
+    }
+
+    fn close_missing_confirmation(&mut self, hub: &Hub) {
+        if let Some(index) = locate_by_id(self, ViewId::MissingConfirmation) {
+            hub.send(Event::Expose(*self.child(index).rect())).unwrap();
+            self.children.remove(index);
+        }
+    }
+
+    fn remove_missing(&mut self, paths: &[PathBuf], hub: &Hub, context: &mut Context) {
+        context.metadata.retain(|info| !paths.contains(&info.file.path));
+        for path in paths {
+            self.content_matches.remove(path);
+        }
+        self.update_fulltext(&context.settings.library_path, |index| {
+            for path in paths {
+                index.remove_book(path);
+            }
+        });
+        self.refresh_visibles(true, false, hub, context);
+    }
     fn export_matches(&self, context: &mut Context) {
         let path = context.settings
                           .library_path
@@ -740,6 +1407,32 @@ impl Home {
 // generic method for updating everything based on the bit field to avoid needlessly updating
 // things multiple times
 
+// The directory that the importer maps to the given category.
+fn category_dir(categ: &str) -> PathBuf {
+    categ.split(PATH_SEPARATOR).filter(|c| !c.is_empty()).collect()
+}
+
+// Moves a file of the library into the given directory, and returns its new path.
+fn relocate_file(library_path: &Path, path: &Path, dir: &Path) -> Result<PathBuf> {
+    let file_name = path.file_name().ok_or("The path has no file name.")?;
+    let new_path = dir.join(file_name);
+    if new_path == path {
+        return Ok(new_path);
+    }
+    let full_path = library_path.join(&new_path);
+    if full_path.exists() {
+        bail!("{} already exists", new_path.display());
+    }
+    fs::create_dir_all(library_path.join(dir)).chain_err(|| "Can't create the directory.")?;
+    fs::rename(library_path.join(path), &full_path).chain_err(|| "Can't rename the file.")?;
+    // The thumbnails are stored under a hash of the path.
+    let thumbnails = thumbnails_dir(library_path, path);
+    if thumbnails.exists() {
+        fs::rename(&thumbnails, thumbnails_dir(library_path, &new_path)).ok();
+    }
+    Ok(new_path)
+}
+
 impl View for Home {
     fn handle_event(&mut self, evt: &Event, hub: &Hub, _bus: &mut Bus, context: &mut Context) -> bool {
         match *evt {
@@ -770,7 +1463,7 @@ impl View for Home {
                 true
             },
             Event::ToggleNear(ViewId::MatchesMenu, rect) => {
-                self.toggle_matches_menu(rect, None, hub, &mut context.fonts);
+                self.toggle_matches_menu(rect, None, hub, context);
                 true
             },
             Event::Close(ViewId::SearchBar) => {
@@ -782,8 +1475,110 @@ impl View for Home {
                 self.toggle_sort_menu(Rectangle::default(), Some(false), hub, &mut context.fonts);
                 true
             },
+            Event::ToggleNear(ViewId::CollectionsMenu, rect) => {
+                self.toggle_collections_menu(rect, None, hub, context);
+                true
+            },
+            Event::Close(ViewId::CollectionsMenu) => {
+                self.toggle_collections_menu(Rectangle::default(), Some(false), hub, context);
+                true
+            },
+            Event::ToggleCategoryMenu(rect, ref categ) => {
+                self.target = Some(Target::Category(categ.clone()));
+                self.toggle_category_menu(rect, None, hub, &mut context.fonts);
+                true
+            },
+            Event::ToggleBookMenu(rect, ref info) => {
+                self.target = Some(Target::Book(info.file.path.clone()));
+                self.toggle_book_menu(rect, None, hub, context);
+                true
+            },
+            Event::Close(ViewId::CategoryMenu) => {
+                self.toggle_category_menu(Rectangle::default(), Some(false), hub, &mut context.fonts);
+                true
+            },
+            Event::Close(ViewId::BookMenu) => {
+                self.toggle_book_menu(Rectangle::default(), Some(false), hub, context);
+                true
+            },
+            Event::Select(EntryId::RenameCategory) => {
+                self.toggle_category_name(Some(ViewId::RenameCategoryInput), hub, context);
+                true
+            },
+            Event::Select(EntryId::MoveCategory) => {
+                self.toggle_category_name(Some(ViewId::MoveCategoryInput), hub, context);
+                true
+            },
+            Event::Select(EntryId::ShowDetails) => {
+                self.toggle_book_menu(Rectangle::default(), Some(false), hub, context);
+                if let Some(Target::Book(ref path)) = self.target {
+                    hub.send(Event::OpenDetails(path.clone())).unwrap();
+                }
+                true
+            },
+            Event::Select(EntryId::AddBookCategory) => {
+                self.toggle_category_name(Some(ViewId::AddCategoryInput), hub, context);
+                true
+            },
+            Event::Select(EntryId::RemoveCategory) => {
+                if let Some(categ) = self.target_category() {
+                    self.remove_category(&categ, hub, context);
+                }
+                true
+            },
+            Event::Select(EntryId::MoveBook) => {
+                self.toggle_category_name(Some(ViewId::MoveBookInput), hub, context);
+                true
+            },
+            Event::Select(EntryId::RemoveBook) => {
+                if let Some(Target::Book(path)) = self.target.clone() {
+                    self.remove_book(&path, hub, context);
+                }
+                true
+            },
+            Event::Select(EntryId::DeleteBook) => {
+                self.toggle_delete_confirmation(true, hub, context);
+                true
+            },
+            Event::Close(ViewId::DeleteConfirmation) => {
+                self.toggle_delete_confirmation(false, hub, context);
+                true
+            },
+            Event::Remove(ref info) => {
+                self.remove_book(&info.file.path, hub, context);
+                true
+            },
+            Event::Delete(ref info) => {
+                self.delete_book(&info.file.path, hub, context);
+                true
+            },
+            Event::Select(EntryId::RemoveBookCategory(index)) => {
+                self.remove_book_category(index, hub, context);
+                true
+            },
+            Event::Submit(id @ ViewId::RenameCategoryInput, ref text) |
+            Event::Submit(id @ ViewId::MoveCategoryInput, ref text) |
+            Event::Submit(id @ ViewId::AddCategoryInput, ref text) |
+            Event::Submit(id @ ViewId::MoveBookInput, ref text) => {
+                self.toggle_category_name(None, hub, context);
+                self.submit_category_name(id, text, hub, context);
+                true
+            },
+            Event::Select(EntryId::LoadCollection(index)) => {
+                self.load_collection(index, hub, context);
+                true
+            },
+            Event::Select(EntryId::SaveCollection) => {
+                self.toggle_collection_name(true, hub, &mut context.fonts);
+                true
+            },
+            Event::Submit(ViewId::CollectionNameInput, ref name) => {
+                self.save_collection(name, context);
+                self.toggle_collection_name(false, hub, &mut context.fonts);
+                true
+            },
             Event::Close(ViewId::MatchesMenu) => {
-                self.toggle_matches_menu(Rectangle::default(), Some(false), hub, &mut context.fonts);
+                self.toggle_matches_menu(Rectangle::default(), Some(false), hub, context);
                 true
             },
             Event::Close(ViewId::MainMenu) => {
@@ -803,17 +1598,60 @@ impl View for Home {
                 self.set_reverse_order(next_value, hub, context);
                 true
             },
+            Event::Select(EntryId::ToggleCovers) => {
+                self.toggle_covers(hub, context);
+                true
+            },
+            Event::Select(EntryId::ToggleContentSearch) => {
+                self.toggle_content_search(hub, context);
+                true
+            },
+            Event::Select(EntryId::Import) => {
+                self.import(hub, context);
+                true
+            },
+            Event::Imported(ref imported, ref missing) => {
+                self.imported(imported.len(), missing, hub, context);
+                true
+            },
+            Event::Close(ViewId::MissingConfirmation) => {
+                self.close_missing_confirmation(hub);
+                true
+            },
+            Event::RemoveMissing(ref paths) => {
+                self.remove_missing(paths, hub, context);
+                true
+            },
             Event::Select(EntryId::ExportMatches) => {
                 self.export_matches(context);
                 true
             },
             Event::Submit(ViewId::SearchInput, ref query) => {
                 self.query = query.clone();
+                if !self.content_search && BookQuery::new(query).is_none() {
+                    self.show_notification("Invalid query.", hub, &mut context.fonts);
+                }
+                self.search_content(hub, context);
                 // TODO: avoid updating things twice
                 self.toggle_keyboard(false, true, hub, &mut context.fonts);
                 self.refresh_visibles(true, true, hub, context);
                 true
             },
+            Event::ContentMatches(ref path, ref pages) => {
+                if self.search_running.is_some() {
+                    self.content_matches.insert(path.clone(), pages.clone());
+                    self.refresh_visibles(true, false, hub, context);
+                }
+                true
+            },
+            Event::Notify(ref text) => {
+                self.show_notification(text, hub, &mut context.fonts);
+                true
+            },
+            Event::Close(ViewId::Notification) => {
+                self.close_notification(hub);
+                true
+            },
             Event::ResizeSummary(delta_y) => {
                 self.resize_summary(delta_y, true, hub, &mut context.fonts);
                 true
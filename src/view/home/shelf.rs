@@ -1,45 +1,110 @@
+use std::thread;
+use std::path::PathBuf;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Sender};
+use fnv::FnvHashMap;
 use device::{CURRENT_DEVICE, BAR_SIZES};
 use view::{View, Event, Hub, Bus, THICKNESS_MEDIUM};
 use framebuffer::{Framebuffer, UpdateMode};
 use font::Fonts;
 use metadata::Info;
+use settings::ShelfLayout;
+use document::open;
+use document::thumbnail::{thumbnails_dir, thumbnail_path, cached_thumbnail, load_pixmap};
 use geom::{Rectangle, Dir, CycleDir};
 use color::{WHITE, SEPARATOR_NORMAL};
 use gesture::GestureEvent;
 use unit::scale_by_dpi;
 use app::Context;
-use view::home::book::Book;
+use view::home::book::{Book, Covers, cover_dims};
 use view::filler::Filler;
 
 pub struct Shelf {
     pub rect: Rectangle,
     children: Vec<Box<View>>,
     pub max_lines: usize,
+    pub layout: ShelfLayout,
+    covers: Covers,
+    sender: Sender<Vec<(PathBuf, Rectangle)>>,
 }
 
 impl Shelf {
-    pub fn new(rect: Rectangle) -> Shelf {
+    pub fn new(rect: Rectangle, library_path: PathBuf, layout: ShelfLayout, hub: &Hub) -> Shelf {
+        let covers: Covers = Arc::new(Mutex::new(FnvHashMap::default()));
+        let (sender, receiver) = mpsc::channel::<Vec<(PathBuf, Rectangle)>>();
+        let covers2 = covers.clone();
+        let hub2 = hub.clone();
+
+        // The covers are loaded from the disk, or rendered from the first page of each book.
+        thread::spawn(move || {
+            let mut pending: VecDeque<(PathBuf, Rectangle)> = VecDeque::new();
+            loop {
+                if pending.is_empty() {
+                    match receiver.recv() {
+                        Ok(books) => pending = books.into_iter().collect(),
+                        Err(_) => break,
+                    }
+                }
+                // Only the books currently shown matter.
+                while let Ok(books) = receiver.try_recv() {
+                    pending = books.into_iter().collect();
+                }
+                if let Some((path, rect)) = pending.pop_front() {
+                    if covers2.lock().unwrap().contains_key(&path) {
+                        continue;
+                    }
+                    let (width, height) = cover_dims(&rect);
+                    let dir = thumbnails_dir(&library_path, &path);
+                    let pixmap = load_pixmap(&thumbnail_path(&dir, 0, width, height)).ok().or_else(|| {
+                        open(library_path.join(&path)).and_then(|doc| {
+                            cached_thumbnail(doc.as_ref(), &dir, 0, width, height)
+                        })
+                    });
+                    if let Some(pixmap) = pixmap {
+                        covers2.lock().unwrap().insert(path, pixmap);
+                        hub2.send(Event::Render(rect, UpdateMode::Gui)).unwrap();
+                    }
+                }
+            }
+        });
+
         Shelf {
             rect,
             max_lines: 0,
             children: vec![],
+            layout,
+            covers,
+            sender,
         }
     }
 
-    pub fn update(&mut self, metadata: &[Info], hub: &Hub) {
+    // When given, `pages` holds the page of each search match.
+    pub fn update(&mut self, metadata: &[Info], pages: &[usize], hub: &Hub) {
         self.children.clear();
         let dpi = CURRENT_DEVICE.dpi;
-        let (_, height) = CURRENT_DEVICE.dims;
+        let (_, height) = CURRENT_DEVICE.screen_dims();
         let &(_, big_height) = BAR_SIZES.get(&(height, dpi)).unwrap();
         let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
         let max_lines = ((self.rect.height() + thickness as u32) / big_height) as usize;
+        let covers = if self.layout == ShelfLayout::Covers {
+            // Only keep the covers of the books currently shown.
+            self.covers.lock().unwrap().retain(|path, _| metadata.iter().any(|info| info.file.path == *path));
+            Some(self.covers.clone())
+        } else {
+            None
+        };
+        let mut requests = Vec::new();
 
         for (index, info) in metadata.iter().enumerate() {
             let y_min = self.rect.min.y + index as i32 * big_height as i32;
             let y_max = y_min + big_height as i32 - thickness;
-            let book = Book::new(rect![self.rect.min.x, y_min,
-                                       self.rect.max.x, y_max],
-                                 info.clone());
+            let book_rect = rect![self.rect.min.x, y_min,
+                                  self.rect.max.x, y_max];
+            if covers.is_some() {
+                requests.push((info.file.path.clone(), book_rect));
+            }
+            let book = Book::new(book_rect, info.clone(), pages.get(index).cloned(), covers.clone());
             self.children.push(Box::new(book) as Box<View>);
             if index < max_lines - 1 {
                 let separator = Filler::new(rect![self.rect.min.x, y_max,
@@ -58,6 +123,7 @@ impl Shelf {
         }
 
         self.max_lines = max_lines;
+        self.sender.send(requests).ok();
         hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
     }
 }
@@ -28,12 +28,20 @@ impl TopBar {
         let mut clock_rect = rect![rect.max - pt!(3*side, side),
                                    rect.max - pt!(2*side, 0)];
         let clock_label = Clock::new(&mut clock_rect, fonts);
+        let collections_rect = rect![pt!(clock_rect.min.x - side, rect.min.y),
+                                     pt!(clock_rect.min.x, rect.max.y)];
         let sort_label = SortLabel::new(rect![pt!(rect.min.x + side,
                                                   rect.min.y),
-                                              pt!(clock_rect.min.x,
+                                              pt!(collections_rect.min.x,
                                                   rect.max.y)],
                                         sort_method.label());
         children.push(Box::new(sort_label) as Box<View>);
+        let collections_icon = Icon::new("star",
+                                         collections_rect,
+                                         WHITE,
+                                         Align::Center,
+                                         Event::ToggleNear(ViewId::CollectionsMenu, collections_rect));
+        children.push(Box::new(collections_icon) as Box<View>);
         children.push(Box::new(clock_label) as Box<View>);
         let frontlight_icon = Icon::new("frontlight",
                                         rect![rect.max - pt!(2*side, side),
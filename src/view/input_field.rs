@@ -90,6 +90,15 @@ impl InputField {
         }
     }
 
+    pub fn set_text(&mut self, text: &str) {
+        self.text = text.to_string();
+        self.cursor = self.text.len();
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
     fn char_move(&mut self, dir: LinearDir) {
         if let Some(index) = closest_char_boundary(&self.text, self.cursor, dir) {
             self.cursor = index;
@@ -268,6 +277,10 @@ impl View for InputField {
         }
     }
 
+    fn id(&self) -> Option<ViewId> {
+        Some(self.id)
+    }
+
     fn rect(&self) -> &Rectangle {
         &self.rect
     }
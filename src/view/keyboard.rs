@@ -48,7 +48,7 @@ impl Keyboard {
         let dpi = CURRENT_DEVICE.dpi;
         let (side, padding) = optimal_key_setup(rect.width(), rect.height(), dpi);
 
-        let (_, height) = CURRENT_DEVICE.dims;
+        let (_, height) = CURRENT_DEVICE.screen_dims();
         let &(_, big_height) = BAR_SIZES.get(&(height, dpi)).unwrap();
         let height_gap = (rect.height() - (4 * side + 5 * padding)) / big_height;
         rect.min.y += (height_gap * big_height) as i32;
@@ -31,7 +31,7 @@ impl Menu {
     pub fn new(target: Rectangle, id: ViewId, entries: &[EntryKind], fonts: &mut Fonts) -> Menu {
         let mut children = Vec::new();
         let dpi = CURRENT_DEVICE.dpi;
-        let (width, height) = CURRENT_DEVICE.dims;
+        let (width, height) = CURRENT_DEVICE.screen_dims();
         let &(small_height, _) = BAR_SIZES.get(&(height, dpi)).unwrap();
 
         let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
@@ -17,6 +17,10 @@ pub mod input_field;
 pub mod rounded_button;
 pub mod page_label;
 pub mod go_to_page;
+pub mod named_input;
+pub mod notification;
+pub mod confirmation;
+pub mod search_bar;
 pub mod menu;
 pub mod menu_entry;
 pub mod clock;
@@ -24,20 +28,23 @@ pub mod keyboard;
 pub mod key;
 pub mod home;
 pub mod reader;
+pub mod details;
 
 use std::sync::mpsc::Sender;
 use std::collections::VecDeque;
+use std::path::PathBuf;
 use std::fmt::{self, Debug};
 use fnv::FnvHashMap;
 use downcast_rs::Downcast;
 use font::Fonts;
-use metadata::{Info, SortMethod, Margin};
+use metadata::{Info, Metadata, SortMethod, Margin};
+use document::TextMatch;
 use framebuffer::{Framebuffer, UpdateMode};
 use input::FingerStatus;
 use gesture::GestureEvent;
 use view::key::KeyKind;
 use app::Context;
-use geom::{LinearDir, CycleDir, Rectangle};
+use geom::{LinearDir, CycleDir, Point, Rectangle};
 
 pub const THICKNESS_SMALL: f32 = 1.0;
 pub const THICKNESS_MEDIUM: f32 = 2.0;
@@ -181,18 +188,34 @@ pub enum Event {
     Keyboard(KeyboardEvent),
     Key(KeyKind),
     Open(Box<Info>),
+    OpenAt(Box<Info>, usize),
+    OpenDetails(PathBuf),
     Invalid(Box<Info>),
     Remove(Box<Info>),
+    Delete(Box<Info>),
+    Imported(Box<Metadata>, Vec<PathBuf>),
+    RemoveMissing(Vec<PathBuf>),
     Page(CycleDir),
+    Scroll(Point),
     GoTo(usize),
+    History(CycleDir),
     CropMargins(Box<Margin>),
     Chapter(CycleDir),
+    SearchResult(usize, Vec<TextMatch>),
+    ContentMatches(PathBuf, Vec<usize>),
+    EndOfSearch(usize),
+    SearchHit(CycleDir),
+    ToggleBookmark,
+    Bookmark(CycleDir),
     Sort(SortMethod),
     ToggleSelectCategory(String),
     ToggleNegateCategory(String),
     ToggleNegateCategoryChildren(String),
+    ToggleCategoryMenu(Rectangle, String),
+    ToggleBookMenu(Rectangle, Box<Info>),
     ResizeSummary(i32),
     Focus(Option<ViewId>),
+    Notify(String),
     Select(EntryId),
     Submit(ViewId, String),
     Slider(SliderId, f32, FingerStatus),
@@ -212,19 +235,44 @@ pub enum Event {
 pub enum ViewId {
     Home,
     Reader,
+    Details,
+    DetailsInput(usize),
     SortMenu,
     MainMenu,
     FrontlightMenu,
     FontSizeMenu,
     MatchesMenu,
+    CollectionsMenu,
+    CollectionName,
+    CollectionNameInput,
+    CategoryMenu,
+    BookMenu,
+    CategoryName,
+    RenameCategoryInput,
+    MoveCategoryInput,
+    AddCategoryInput,
+    MoveBookInput,
+    DeleteConfirmation,
+    MissingConfirmation,
     GoToPage,
     GoToPageInput,
     SearchInput,
     SearchBar,
+    SearchResults,
     Keyboard,
     MarginCropper,
     TopBottomBars,
     TableOfContents,
+    Bookmarks,
+    Annotations,
+    AnnotationMenu,
+    AnnotationNote,
+    AnnotationInput,
+    ZoomMenu,
+    AutoCropMenu,
+    PageOverview,
+    Notification,
+    Definition,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -282,11 +330,34 @@ pub enum EntryKind {
 pub enum EntryId {
     Sort(SortMethod),
     ExportMatches,
+    Import,
+    ToggleCovers,
+    ToggleContentSearch,
+    LoadCollection(usize),
+    RenameCategory,
+    MoveCategory,
+    RemoveCategory,
+    ShowDetails,
+    AddBookCategory,
+    RemoveBookCategory(usize),
+    MoveBook,
+    RemoveBook,
+    DeleteBook,
+    SaveCollection,
     ReverseOrder,
     SubSort,
     ToggleInverted,
     ToggleMonochrome,
     TakeScreenshot,
+    SetRotation(i8),
+    Highlight,
+    Annotate,
+    Define,
+    FitToPage,
+    FitToWidth,
+    ToggleRepage,
+    AutoCropPage,
+    AutoCropAll,
     Quit,
 }
 
@@ -0,0 +1,114 @@
+use framebuffer::Framebuffer;
+use font::{Fonts, font_from_style, NORMAL_STYLE};
+use geom::{Rectangle, Point, CornerSpec, BorderSpec, halves, big_half};
+use view::{View, Event, Hub, Bus, ViewId, Align};
+use view::{THICKNESS_LARGE, BORDER_RADIUS_SMALL};
+use view::label::Label;
+use view::input_field::InputField;
+use unit::scale_by_dpi;
+use color::{BLACK, WHITE};
+use device::CURRENT_DEVICE;
+use app::Context;
+
+// A labeled input field. The parent handles the `Submit` event sent by the field.
+pub struct NamedInput {
+    rect: Rectangle,
+    children: Vec<Box<View>>,
+    id: ViewId,
+}
+
+impl NamedInput {
+    pub fn new(anchor: &Point, text: String, id: ViewId, input_id: ViewId, input_size: usize, fonts: &mut Fonts) -> NamedInput {
+        let mut children = Vec::new();
+        let dpi = CURRENT_DEVICE.dpi;
+        let (width, _) = CURRENT_DEVICE.screen_dims();
+        let font = font_from_style(fonts, &NORMAL_STYLE, dpi);
+        let x_height = font.x_heights.0 as i32;
+        let padding = font.em() as i32;
+
+        let label_width = font.plan(&text, None, None).width as i32;
+        let max_input_field_width = width as i32 - 6 * padding - label_width;
+        let input_field_width = (input_size as i32 * font.plan("0", None, None).width as i32)
+                                    .min(max_input_field_width);
+        let width = 5 * padding + label_width + input_field_width;
+        let (small_half_width, big_half_width) = halves(width);
+        let big_half_padding = big_half(padding);
+
+        let x_min = anchor.x - small_half_width;
+        let x_max = anchor.x + big_half_width;
+        let y_min = anchor.y - 4 * x_height;
+        let y_max = anchor.y + 4 * x_height;
+
+        let label = Label::new(rect![x_min + big_half_padding,
+                                     y_min + x_height,
+                                     x_min + big_half_padding + padding + label_width,
+                                     y_max - x_height],
+                               text,
+                               Align::Center);
+        children.push(Box::new(label) as Box<View>);
+
+        let input_field = InputField::new(rect![x_max - 3 * padding - input_field_width,
+                                                y_min + 2 * x_height,
+                                                x_max - padding,
+                                                y_max - 2 * x_height],
+                                          input_id,
+                                          true,
+                                          None);
+        children.push(Box::new(input_field) as Box<View>);
+
+        let rect = rect![x_min, y_min,
+                         x_max, y_max];
+
+        NamedInput {
+            rect,
+            children,
+            id,
+        }
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        let input_field = self.children[1].as_mut().downcast_mut::<InputField>().unwrap();
+        input_field.set_text(text);
+    }
+}
+
+impl View for NamedInput {
+    fn handle_event(&mut self, _evt: &Event, _hub: &Hub, _bus: &mut Bus, _context: &mut Context) -> bool {
+        false
+    }
+
+    fn render(&self, fb: &mut Framebuffer, _fonts: &mut Fonts) {
+        let dpi = CURRENT_DEVICE.dpi;
+        let border_radius = scale_by_dpi(BORDER_RADIUS_SMALL, dpi) as i32;
+        let border_thickness = scale_by_dpi(THICKNESS_LARGE, dpi) as u16;
+        fb.draw_rounded_rectangle_with_border(&self.rect,
+                                              &CornerSpec::Uniform(border_radius),
+                                              &BorderSpec { thickness: border_thickness,
+                                                            color: BLACK },
+                                              &WHITE);
+    }
+
+    fn is_background(&self) -> bool {
+        true
+    }
+
+    fn id(&self) -> Option<ViewId> {
+        Some(self.id)
+    }
+
+    fn rect(&self) -> &Rectangle {
+        &self.rect
+    }
+
+    fn rect_mut(&mut self) -> &mut Rectangle {
+        &mut self.rect
+    }
+
+    fn children(&self) -> &Vec<Box<View>> {
+        &self.children
+    }
+
+    fn children_mut(&mut self) -> &mut Vec<Box<View>> {
+        &mut self.children
+    }
+}
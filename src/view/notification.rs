@@ -0,0 +1,97 @@
+use framebuffer::Framebuffer;
+use font::{Fonts, font_from_style, NORMAL_STYLE};
+use geom::{Rectangle, CornerSpec, BorderSpec, halves};
+use view::{View, Event, Hub, Bus, ViewId};
+use view::{THICKNESS_LARGE, BORDER_RADIUS_SMALL};
+use gesture::GestureEvent;
+use unit::scale_by_dpi;
+use color::{BLACK, WHITE, TEXT_NORMAL};
+use device::CURRENT_DEVICE;
+use app::Context;
+
+// A single line message, centered on the screen, dismissed by tapping anywhere.
+pub struct Notification {
+    rect: Rectangle,
+    children: Vec<Box<View>>,
+    text: String,
+}
+
+impl Notification {
+    pub fn new(text: String, fonts: &mut Fonts) -> Notification {
+        let dpi = CURRENT_DEVICE.dpi;
+        let (width, height) = CURRENT_DEVICE.screen_dims();
+        let font = font_from_style(fonts, &NORMAL_STYLE, dpi);
+        let x_height = font.x_heights.0 as i32;
+        let padding = font.em() as i32;
+
+        let max_text_width = width as i32 - 4 * padding;
+        let text_width = (font.plan(&text, None, None).width as i32).min(max_text_width);
+        let (small_half_width, big_half_width) = halves(text_width + 2 * padding);
+        let (small_half_height, big_half_height) = halves(5 * x_height);
+        let (x, y) = (width as i32 / 2, height as i32 / 2);
+
+        let rect = rect![x - small_half_width, y - small_half_height,
+                         x + big_half_width, y + big_half_height];
+
+        Notification {
+            rect,
+            children: vec![],
+            text,
+        }
+    }
+}
+
+impl View for Notification {
+    fn handle_event(&mut self, evt: &Event, hub: &Hub, _bus: &mut Bus, _context: &mut Context) -> bool {
+        match *evt {
+            Event::Gesture(GestureEvent::Tap { .. }) => {
+                hub.send(Event::Close(ViewId::Notification)).unwrap();
+                true
+            },
+            Event::Gesture(..) => true,
+            _ => false,
+        }
+    }
+
+    fn render(&self, fb: &mut Framebuffer, fonts: &mut Fonts) {
+        let dpi = CURRENT_DEVICE.dpi;
+        let border_radius = scale_by_dpi(BORDER_RADIUS_SMALL, dpi) as i32;
+        let border_thickness = scale_by_dpi(THICKNESS_LARGE, dpi) as u16;
+        fb.draw_rounded_rectangle_with_border(&self.rect,
+                                              &CornerSpec::Uniform(border_radius),
+                                              &BorderSpec { thickness: border_thickness,
+                                                            color: BLACK },
+                                              &WHITE);
+
+        let font = font_from_style(fonts, &NORMAL_STYLE, dpi);
+        let x_height = font.x_heights.0 as i32;
+        let padding = font.em() as i32;
+        let max_width = self.rect.width() as i32 - 2 * padding;
+        let plan = font.plan(&self.text, Some(max_width as u32), None);
+        let dx = (self.rect.width() as i32 - plan.width as i32) / 2;
+        let dy = (self.rect.height() as i32 - x_height) / 2;
+        let pt = pt!(self.rect.min.x + dx, self.rect.max.y - dy);
+
+        font.render(fb, TEXT_NORMAL[1], &plan, &pt);
+    }
+
+    fn id(&self) -> Option<ViewId> {
+        Some(ViewId::Notification)
+    }
+
+    fn rect(&self) -> &Rectangle {
+        &self.rect
+    }
+
+    fn rect_mut(&mut self) -> &mut Rectangle {
+        &mut self.rect
+    }
+
+    fn children(&self) -> &Vec<Box<View>> {
+        &self.children
+    }
+
+    fn children_mut(&mut self) -> &mut Vec<Box<View>> {
+        &mut self.children
+    }
+}
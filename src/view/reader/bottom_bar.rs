@@ -17,10 +17,12 @@ pub struct BottomBar {
     children: Vec<Box<View>>,
     is_prev_disabled: bool,
     is_next_disabled: bool,
+    is_back_disabled: bool,
+    is_forward_disabled: bool,
 }
 
 impl BottomBar {
-    pub fn new(rect: Rectangle, doc: &Document, current_page: usize, pages_count: usize) -> BottomBar {
+    pub fn new(rect: Rectangle, doc: &Document, current_page: usize, pages_count: usize, can_go_back: bool, can_go_forward: bool) -> BottomBar {
         let mut children = Vec::new();
         let side = rect.height() as i32;
         let is_prev_disabled = pages_count < 2 || current_page == 0;
@@ -40,11 +42,14 @@ impl BottomBar {
             children.push(Box::new(prev_icon) as Box<View>);
         }
 
-        let (small_half_width, big_half_width) = halves(rect.width() as i32 - 2 * side);
+        let back_rect = rect![pt!(rect.min.x + side, rect.min.y),
+                              pt!(rect.min.x + 2 * side, rect.max.y)];
+        children.push(history_icon(back_rect, CycleDir::Previous, !can_go_back));
 
+        let (small_half_width, big_half_width) = halves(rect.width() as i32 - 4 * side);
 
-        let chapter_rect = rect![pt!(rect.min.x + side, rect.min.y),
-                                 pt!(rect.min.x + side + small_half_width, rect.max.y)];
+        let chapter_rect = rect![pt!(rect.min.x + 2 * side, rect.min.y),
+                                 pt!(rect.min.x + 2 * side + small_half_width, rect.max.y)];
 
         let chapter = doc.toc().and_then(|t| chapter_at(&t, current_page))
                                .map(|c| c.title)
@@ -54,12 +59,16 @@ impl BottomBar {
                                        Align::Center);
         children.push(Box::new(chapter_label) as Box<View>);
 
-        let page_label = PageLabel::new(rect![pt!(rect.max.x - side - big_half_width, rect.min.y),
-                                              pt!(rect.max.x - side, rect.max.y)],
+        let page_label = PageLabel::new(rect![pt!(rect.max.x - 2 * side - big_half_width, rect.min.y),
+                                              pt!(rect.max.x - 2 * side, rect.max.y)],
                                         current_page,
                                         pages_count);
         children.push(Box::new(page_label) as Box<View>);
 
+        let forward_rect = rect![pt!(rect.max.x - 2 * side, rect.min.y),
+                                 pt!(rect.max.x - side, rect.max.y)];
+        children.push(history_icon(forward_rect, CycleDir::Next, !can_go_forward));
+
         let next_rect = rect![rect.max - side, rect.max];
 
         if is_next_disabled {
@@ -79,16 +88,18 @@ impl BottomBar {
             children,
             is_prev_disabled,
             is_next_disabled,
+            is_back_disabled: !can_go_back,
+            is_forward_disabled: !can_go_forward,
         }
     }
 
     pub fn update_page_label(&mut self, current_page: usize, pages_count: usize, hub: &Hub) {
-        let page_label = self.child_mut(2).downcast_mut::<PageLabel>().unwrap();
+        let page_label = self.child_mut(3).downcast_mut::<PageLabel>().unwrap();
         page_label.update(current_page, pages_count, hub);
     }
 
     pub fn update_chapter(&mut self, text: String, hub: &Hub) {
-        let chapter_label = self.child_mut(1).downcast_mut::<Label>().unwrap();
+        let chapter_label = self.child_mut(2).downcast_mut::<Label>().unwrap();
         chapter_label.update(text, hub);
     }
 
@@ -133,6 +144,33 @@ impl BottomBar {
             hub.send(Event::Render(next_rect, UpdateMode::Gui)).unwrap();
         }
     }
+
+    pub fn update_history_icons(&mut self, can_go_back: bool, can_go_forward: bool, hub: &Hub) {
+        if self.is_back_disabled == can_go_back {
+            let index = 1;
+            let back_rect = *self.child(index).rect();
+            self.children[index] = history_icon(back_rect, CycleDir::Previous, !can_go_back);
+            self.is_back_disabled = !can_go_back;
+            hub.send(Event::Render(back_rect, UpdateMode::Gui)).unwrap();
+        }
+
+        if self.is_forward_disabled == can_go_forward {
+            let index = self.len() - 2;
+            let forward_rect = *self.child(index).rect();
+            self.children[index] = history_icon(forward_rect, CycleDir::Next, !can_go_forward);
+            self.is_forward_disabled = !can_go_forward;
+            hub.send(Event::Render(forward_rect, UpdateMode::Gui)).unwrap();
+        }
+    }
+}
+
+fn history_icon(rect: Rectangle, dir: CycleDir, disabled: bool) -> Box<View> {
+    if disabled {
+        Box::new(Filler::new(rect, WHITE)) as Box<View>
+    } else {
+        let name = if dir == CycleDir::Previous { "undo" } else { "redo" };
+        Box::new(Icon::new(name, rect, WHITE, Align::Center, Event::History(dir))) as Box<View>
+    }
 }
 
 impl View for BottomBar {
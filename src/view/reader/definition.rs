@@ -0,0 +1,185 @@
+use device::CURRENT_DEVICE;
+use framebuffer::{Framebuffer, UpdateMode};
+use view::{View, Event, Hub, Bus, ViewId, THICKNESS_LARGE, BORDER_RADIUS_SMALL};
+use gesture::GestureEvent;
+use font::{Fonts, Font, RenderPlan, font_from_style, NORMAL_STYLE, MD_KIND, MD_SIZE};
+use geom::{Rectangle, CornerSpec, BorderSpec, Dir, halves};
+use color::{BLACK, WHITE, TEXT_NORMAL};
+use unit::scale_by_dpi;
+use app::Context;
+
+struct Line {
+    plan: RenderPlan,
+    // The name of a dictionary.
+    is_heading: bool,
+}
+
+// A popup showing the definitions of a word, one screenful at a time.
+pub struct Definition {
+    rect: Rectangle,
+    children: Vec<Box<View>>,
+    lines: Vec<Line>,
+    line_height: i32,
+    lines_per_page: usize,
+    first_line: usize,
+}
+
+impl Definition {
+    pub fn new(definitions: &[(String, String)], fonts: &mut Fonts) -> Definition {
+        let dpi = CURRENT_DEVICE.dpi;
+        let (width, height) = CURRENT_DEVICE.screen_dims();
+        let (width, height) = (width as i32, height as i32);
+        let (small_half_width, big_half_width) = halves(4 * width / 5);
+        let (small_half_height, big_half_height) = halves(height / 2);
+        let rect = rect![width / 2 - small_half_width, height / 2 - small_half_height,
+                         width / 2 + big_half_width, height / 2 + big_half_height];
+
+        let (line_height, padding) = {
+            let font = font_from_style(fonts, &NORMAL_STYLE, dpi);
+            (3 * font.x_heights.0 as i32, font.em() as i32)
+        };
+        let max_width = (rect.width() as i32 - 2 * padding) as u32;
+        let mut lines = Vec::new();
+
+        for (name, text) in definitions {
+            let font = font_from_style(fonts, &MD_KIND, dpi);
+            let plan = font.plan(&name.to_uppercase(), Some(max_width), None);
+            lines.push(Line { plan, is_heading: true });
+            let font = font_from_style(fonts, &NORMAL_STYLE, dpi);
+            for paragraph in text.lines().map(|l| l.trim()).filter(|l| !l.is_empty()) {
+                for plan in wrap(font, paragraph, max_width) {
+                    lines.push(Line { plan, is_heading: false });
+                }
+            }
+        }
+
+        let mut lines_per_page = ((rect.height() as i32 - 2 * padding) / line_height).max(2) as usize;
+        // Keep the last line for the page counter.
+        if lines.len() > lines_per_page {
+            lines_per_page -= 1;
+        }
+
+        Definition {
+            rect,
+            children: vec![],
+            lines,
+            line_height,
+            lines_per_page,
+            first_line: 0,
+        }
+    }
+
+    fn pages_count(&self) -> usize {
+        (self.lines.len() + self.lines_per_page - 1) / self.lines_per_page
+    }
+
+    fn turn_page(&mut self, next: bool, hub: &Hub) {
+        let first_line = if next {
+            self.first_line + self.lines_per_page
+        } else {
+            self.first_line.saturating_sub(self.lines_per_page)
+        };
+        if first_line < self.lines.len() && first_line != self.first_line {
+            self.first_line = first_line;
+            hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+        }
+    }
+}
+
+// Breaks the text into lines that fit in the given width.
+fn wrap(font: &mut Font, text: &str, max_width: u32) -> Vec<RenderPlan> {
+    let mut lines = Vec::new();
+    let mut plan = font.plan(text, None, None);
+    while plan.width > max_width {
+        let (index, usable_width) = font.cut_point(&plan, max_width);
+        if index == 0 {
+            font.crop_right(&mut plan, max_width);
+            break;
+        }
+        let next_plan = plan.split_off(index, usable_width);
+        lines.push(plan);
+        plan = next_plan;
+    }
+    lines.push(plan);
+    lines
+}
+
+impl View for Definition {
+    fn handle_event(&mut self, evt: &Event, hub: &Hub, _bus: &mut Bus, _context: &mut Context) -> bool {
+        match *evt {
+            Event::Gesture(GestureEvent::Tap { ref center, .. }) if !self.rect.includes(center) => {
+                hub.send(Event::Close(ViewId::Definition)).unwrap();
+                true
+            },
+            Event::Gesture(GestureEvent::Tap { ref center, .. }) => {
+                let next = center.x >= self.rect.min.x + self.rect.width() as i32 / 2;
+                self.turn_page(next, hub);
+                true
+            },
+            Event::Gesture(GestureEvent::Swipe { dir, ref start, .. }) if self.rect.includes(start) => {
+                match dir {
+                    Dir::West | Dir::North => self.turn_page(true, hub),
+                    Dir::East | Dir::South => self.turn_page(false, hub),
+                }
+                true
+            },
+            Event::Gesture(..) => true,
+            _ => false,
+        }
+    }
+
+    fn render(&self, fb: &mut Framebuffer, fonts: &mut Fonts) {
+        let dpi = CURRENT_DEVICE.dpi;
+        let border_radius = scale_by_dpi(BORDER_RADIUS_SMALL, dpi) as i32;
+        let border_thickness = scale_by_dpi(THICKNESS_LARGE, dpi) as u16;
+        fb.draw_rounded_rectangle_with_border(&self.rect,
+                                              &CornerSpec::Uniform(border_radius),
+                                              &BorderSpec { thickness: border_thickness,
+                                                            color: BLACK },
+                                              &WHITE);
+
+        let (x_height, padding) = {
+            let font = font_from_style(fonts, &NORMAL_STYLE, dpi);
+            (font.x_heights.0 as i32, font.em() as i32)
+        };
+        let dy = (self.line_height + x_height) / 2;
+
+        for (i, line) in self.lines.iter().skip(self.first_line).take(self.lines_per_page).enumerate() {
+            let style = if line.is_heading { &MD_KIND } else { &NORMAL_STYLE };
+            let font = font_from_style(fonts, style, dpi);
+            let pt = pt!(self.rect.min.x + padding,
+                         self.rect.min.y + padding + i as i32 * self.line_height + dy);
+            font.render(fb, TEXT_NORMAL[1], &line.plan, &pt);
+        }
+
+        let pages_count = self.pages_count();
+        if pages_count > 1 {
+            let font = font_from_style(fonts, &MD_SIZE, dpi);
+            let text = format!("{}/{}", self.first_line / self.lines_per_page + 1, pages_count);
+            let plan = font.plan(&text, None, None);
+            let pt = pt!(self.rect.max.x - padding - plan.width as i32,
+                         self.rect.max.y - padding - self.line_height + dy);
+            font.render(fb, TEXT_NORMAL[1], &plan, &pt);
+        }
+    }
+
+    fn id(&self) -> Option<ViewId> {
+        Some(ViewId::Definition)
+    }
+
+    fn rect(&self) -> &Rectangle {
+        &self.rect
+    }
+
+    fn rect_mut(&mut self) -> &mut Rectangle {
+        &mut self.rect
+    }
+
+    fn children(&self) -> &Vec<Box<View>> {
+        &self.children
+    }
+
+    fn children_mut(&mut self) -> &mut Vec<Box<View>> {
+        &mut self.children
+    }
+}
@@ -35,7 +35,7 @@ impl MarginCropper {
         let frame = rect![x_min, y_min, x_max, y_max];
 
         let dpi = CURRENT_DEVICE.dpi;
-        let (_, height) = CURRENT_DEVICE.dims;
+        let (_, height) = CURRENT_DEVICE.screen_dims();
         let &(small_height, _) = BAR_SIZES.get(&(height, dpi)).unwrap();
         let big_button_diameter = small_height as i32;
         let padding = big_button_diameter / 2;
@@ -48,6 +48,15 @@ impl MarginCropper {
                                                 Event::Cancel);
         children.push(Box::new(cancel_button) as Box<View>);
 
+        let auto_crop_rect = rect![rect.center().x - big_button_diameter / 2,
+                                   rect.max.y - padding - big_button_diameter,
+                                   rect.center().x + big_button_diameter - big_button_diameter / 2,
+                                   rect.max.y - padding];
+        let auto_crop_button = RoundedButton::new("crop",
+                                                  auto_crop_rect,
+                                                  Event::ToggleNear(ViewId::AutoCropMenu, auto_crop_rect));
+        children.push(Box::new(auto_crop_button) as Box<View>);
+
         let validate_button = RoundedButton::new("check_mark-large",
                                                  rect![rect.max.x - padding - big_button_diameter,
                                                        rect.max.y - padding - big_button_diameter,
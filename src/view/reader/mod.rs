@@ -2,44 +2,161 @@ mod top_bar;
 mod tool_bar;
 mod bottom_bar;
 mod margin_cropper;
+mod results_bar;
+mod page_list;
+mod table_of_contents;
 mod viewer;
+mod pixmap_cache;
+mod page_overview;
+mod definition;
 
-use std::rc::Rc;
+use std::thread;
+use std::mem;
+use std::collections::{BTreeSet, BTreeMap};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use fnv::FnvHashMap;
 use chrono::Local;
 use input::FingerStatus;
+use gesture::GestureEvent;
 use framebuffer::{Framebuffer, UpdateMode, Pixmap};
-use view::{View, Event, Hub, ViewId, EntryId, SliderId, Bus, THICKNESS_MEDIUM};
-use unit::{scale_by_dpi, pt_to_px};
+use view::{View, Event, Hub, ViewId, EntryId, EntryKind, SliderId, Bus, THICKNESS_MEDIUM};
+use unit::scale_by_dpi;
 use device::{CURRENT_DEVICE, BAR_SIZES};
 use font::{Fonts, DEFAULT_FONT_SIZE};
 use self::margin_cropper::{MarginCropper, BUTTON_DIAMETER};
 use self::top_bar::TopBar;
 use self::viewer::Viewer;
+use self::pixmap_cache::{PixmapCache, PixmapKey};
 use self::tool_bar::ToolBar;
 use self::bottom_bar::BottomBar;
+use self::results_bar::ResultsBar;
+use self::page_list::{PageList, PageListEntry};
+use self::table_of_contents::TableOfContents;
+use self::page_overview::PageOverview;
+use self::definition::Definition;
 use view::common::{locate, locate_by_id, toggle_main_menu};
 use view::filler::Filler;
 use view::go_to_page::GoToPage;
+use view::named_input::NamedInput;
+use view::notification::Notification;
+use view::menu::Menu;
 use view::keyboard::{Keyboard, DEFAULT_LAYOUT};
-use document::{Document, open, chapter_at, chapter_relative};
-use metadata::{Info, ReaderInfo, Margin};
-use geom::{Rectangle, CycleDir, halves};
+use view::search_bar::SearchBar;
+use document::{Document, TextMatch, BoundedText, Anchor, open, chapter_at, chapter_relative};
+use document::{CharsCounts, anchor_at, page_at_anchor, words_at_anchor, reader_layout};
+use document::analysis::{reading_regions, content_bounds};
+use document::thumbnail::thumbnails_dir;
+use dictionary::{Dictionary, DICTIONARIES_DIRNAME, load_dictionaries, define};
+use metadata::{Info, ReaderInfo, Annotation, Margin, ZoomMode, make_literal_query};
+use geom::{Point, Rectangle, CycleDir, halves};
 use color::BLACK;
 use app::Context;
 
+const MAX_ZOOM_FACTOR: f32 = 4.0;
+// Width of the pixmaps used to find the regions of a page.
+const ANALYSIS_WIDTH: f32 = 600.0;
+// Padding added around each region, relative to the page's width.
+const REGION_PADDING: f32 = 0.01;
+// Number of pages of each parity examined when cropping the whole document.
+const AUTO_CROP_SAMPLES: usize = 9;
+
 pub struct Reader {
     rect: Rectangle,
     children: Vec<Box<View>>,
     info: Info,
     doc: Box<Document>,
-    pixmap: Rc<Pixmap>,
+    pixmap: Arc<Pixmap>,
+    cache: PixmapCache,
+    scale: f32,
+    zoom_mode: ZoomMode,
+    scroll: Point,
+    repage: bool,
+    regions: FnvHashMap<usize, Vec<Margin>>,
+    current_region: usize,
     current_page: usize,
     pages_count: usize,
     page_turns: usize,
+    direction: CycleDir,
+    history: History,
     finished: bool,
     refresh_every: Option<u8>,
     focus: Option<ViewId>,
+    anchor: Option<Anchor>,
+    chars_counts: CharsCounts,
+    // The page whose links are given to the viewer.
+    links_page: Option<usize>,
+    search: Option<Search>,
+    searches_count: usize,
+    selection: Option<Selection>,
+    // Loaded on the first lookup.
+    dictionaries: Option<Vec<Dictionary>>,
+}
+
+struct Search {
+    // Identifies the results of this search among the events.
+    id: usize,
+    query: String,
+    running: Arc<AtomicBool>,
+    results: Vec<TextMatch>,
+    current: Option<usize>,
+    completed: bool,
+}
+
+// A run of consecutive words, from `anchor` to `cursor`, on a given page.
+struct Selection {
+    page: usize,
+    words: Vec<BoundedText>,
+    anchor: usize,
+    cursor: usize,
+    dragging: bool,
+}
+
+impl Selection {
+    fn range(&self) -> (usize, usize) {
+        (self.anchor.min(self.cursor), self.anchor.max(self.cursor))
+    }
+
+    fn text(&self) -> String {
+        let (start, end) = self.range();
+        self.words[start..=end].iter().map(|w| w.text.as_str())
+                               .collect::<Vec<&str>>().join(" ")
+    }
+
+    fn rects(&self) -> Vec<Rectangle> {
+        let (start, end) = self.range();
+        self.words[start..=end].iter().map(|w| w.rect).collect()
+    }
+}
+
+// The pages left by discontinuous jumps during the current session.
+#[derive(Default)]
+struct History {
+    back: Vec<usize>,
+    forward: Vec<usize>,
+}
+
+impl History {
+    fn record(&mut self, index: usize) {
+        self.back.push(index);
+        self.forward.clear();
+    }
+
+    fn go(&mut self, dir: CycleDir, current: usize) -> Option<usize> {
+        let (from, to) = match dir {
+            CycleDir::Previous => (&mut self.back, &mut self.forward),
+            CycleDir::Next => (&mut self.forward, &mut self.back),
+        };
+        from.pop().map(|index| {
+            to.push(current);
+            index
+        })
+    }
+
+    fn clear(&mut self) {
+        self.back.clear();
+        self.forward.clear();
+    }
 }
 
 impl Reader {
@@ -50,14 +167,14 @@ impl Reader {
         open(&path).map(|mut doc| {
             let mut children = Vec::new();
 
-            let (width, height) = CURRENT_DEVICE.dims;
             let font_size = info.reader.as_ref().and_then(|r| r.font_size);
-            doc.layout(width as f32, height as f32,
-                       pt_to_px(font_size.unwrap_or(DEFAULT_FONT_SIZE),
-                                CURRENT_DEVICE.dpi));
+            let layout = reader_layout(&CURRENT_DEVICE, font_size);
+            let (width, height, font_size) = layout;
+            doc.layout(width, height, font_size);
 
             let pages_count;
             let current_page;
+            let mut chars_counts = CharsCounts::default();
 
             // TODO: use get_or_insert_with?
             if let Some(ref mut r) = info.reader {
@@ -65,9 +182,24 @@ impl Reader {
                 if r.finished {
                     r.finished = false;
                     r.current_page = 0;
+                    r.anchor = None;
+                }
+                // The layout of reflowable documents might have changed since the last time.
+                match r.anchor {
+                    Some(ref anchor) if doc.is_reflowable() => {
+                        current_page = page_at_anchor(doc.as_ref(), anchor, &mut chars_counts);
+                        pages_count = doc.pages_count();
+                    },
+                    _ => {
+                        current_page = r.current_page;
+                        pages_count = r.pages_count;
+                    },
+                }
+                if doc.is_reflowable() && r.layout != Some(layout) {
+                    relocate_annotations(doc.as_ref(), &mut r.annotations, &mut chars_counts);
+                    relocate_bookmarks(doc.as_ref(), r, &mut chars_counts);
+                    r.layout = Some(layout);
                 }
-                current_page = r.current_page;
-                pages_count = r.pages_count;
             } else {
                 current_page = 0;
                 pages_count = doc.pages_count();
@@ -77,22 +209,55 @@ impl Reader {
                     pages_count,
                     cropping_margins: FnvHashMap::default(),
                     font_size: None,
+                    anchor: None,
+                    layout: if doc.is_reflowable() { Some(layout) } else { None },
+                    zoom_mode: None,
+                    repage: false,
+                    bookmarks: BTreeSet::new(),
+                    bookmark_anchors: BTreeMap::new(),
+                    annotations: Vec::new(),
                     finished: false,
                 });
             }
 
             println!("{}", info.file.path.display());
 
-            let margin = info.reader.as_ref()
-                             .and_then(|r| r.margin_at(current_page))
-                             .cloned().unwrap_or_default();
-            let pixmap = Rc::new(build_pixmap(&rect, doc.as_ref(), current_page, &margin));
-            let frame = rect![(margin.left * pixmap.width as f32).ceil() as i32,
-                              (margin.top * pixmap.height as f32).ceil() as i32,
-                              ((1.0 - margin.right) * pixmap.width as f32).floor() as i32,
-                              ((1.0 - margin.bottom) * pixmap.height as f32).floor() as i32];
+            let anchor = info.reader.as_ref().and_then(|r| r.anchor.clone());
+            let zoom_mode = if doc.is_reflowable() {
+                ZoomMode::FitToPage
+            } else {
+                info.reader.as_ref().and_then(|r| r.zoom_mode)
+                    .unwrap_or(ZoomMode::FitToPage)
+            };
+            let repage = !doc.is_reflowable() &&
+                         info.reader.as_ref().map_or(false, |r| r.repage);
+            let mut regions = FnvHashMap::default();
+
+            let margin = if repage {
+                let page_regions = region_margins(doc.as_ref(), current_page);
+                let margin = page_regions.first().cloned();
+                regions.insert(current_page, page_regions);
+                margin
+            } else {
+                None
+            };
+            let margin = margin.or_else(|| {
+                info.reader.as_ref()
+                    .and_then(|r| r.margin_at(current_page))
+                    .cloned()
+            }).unwrap_or_default();
+            let (pixmap, scale) = build_pixmap(&rect, doc.as_ref(), current_page, &margin, zoom_mode);
+            let pixmap = Arc::new(pixmap);
+            let cache = PixmapCache::new(path.clone(), width, height, font_size);
+            cache.insert(PixmapKey::new(current_page, scale, &margin), pixmap.clone());
+            let frame = visible_frame(&rect, &crop_frame(&pixmap, &margin), &Point::default());
 
-            let viewer = Viewer::new(rect, pixmap.clone(), frame, UpdateMode::Partial);
+            let mut viewer = Viewer::new(rect, pixmap.clone(), frame, scale, UpdateMode::Partial);
+            viewer.set_links(doc.links(current_page).unwrap_or_default());
+            if let Some(ref r) = info.reader {
+                viewer.set_annotation_rects(annotation_rects(&r.annotations, current_page));
+                viewer.set_bookmarked(r.bookmarks.contains(&current_page));
+            }
             children.push(Box::new(viewer) as Box<View>);
 
             hub.send(Event::Render(rect, UpdateMode::Partial)).unwrap();
@@ -103,12 +268,28 @@ impl Reader {
                 info,
                 doc,
                 pixmap,
+                cache,
+                scale,
+                zoom_mode,
+                scroll: Point::default(),
+                repage,
+                regions,
+                current_region: 0,
                 current_page,
                 pages_count,
                 page_turns: 0,
+                direction: CycleDir::Next,
+                history: History::default(),
                 finished: false,
                 refresh_every: settings.refresh_every,
                 focus: None,
+                anchor,
+                chars_counts,
+                links_page: Some(current_page),
+                search: None,
+                searches_count: 0,
+                selection: None,
+                dictionaries: None,
             }
         })
     }
@@ -119,26 +300,60 @@ impl Reader {
             return;
         }
         self.current_page = index;
+        self.anchor = None;
+        self.scroll = Point::default();
+        self.current_region = 0;
         self.update_viewer(hub);
         self.update_bottom_bar(hub);
     }
 
+    // Goes to the given page and remembers where we came from.
+    fn jump_to_page(&mut self, index: usize, hub: &Hub) {
+        if index != self.current_page && index < self.pages_count {
+            self.history.record(self.current_page);
+            self.update_history_icons(hub);
+        }
+        self.go_to_page(index, hub);
+    }
+
+    fn go_through_history(&mut self, dir: CycleDir, hub: &Hub) {
+        if let Some(index) = self.history.go(dir, self.current_page) {
+            self.go_to_page(index, hub);
+            self.update_history_icons(hub);
+        }
+    }
+
     fn go_to_chapter(&mut self, dir: CycleDir, hub: &Hub) {
         let current_page = self.current_page;
         if let Some(index) = self.doc.toc().and_then(|t| chapter_relative(&t, current_page, dir)) {
-            self.go_to_page(index, hub);
+            self.jump_to_page(index, hub);
         }
     }
 
     fn set_current_page(&mut self, dir: CycleDir, hub: &Hub) {
+        self.direction = dir;
+        if self.scroll_step(dir, hub) || self.region_step(dir, hub) {
+            return;
+        }
         match dir {
             CycleDir::Next if self.current_page < self.pages_count - 1 => {
                 self.current_page += 1;
+                self.anchor = None;
+                self.scroll = Point::default();
+                self.current_region = 0;
                 self.update_viewer(hub);
                 self.update_bottom_bar(hub);
             },
             CycleDir::Previous if self.current_page > 0 => {
                 self.current_page -= 1;
+                self.anchor = None;
+                // Start at the end of the previous page.
+                self.scroll = pt!(i32::max_value());
+                self.current_region = if self.repage {
+                    self.regions_count(self.current_page).saturating_sub(1)
+                } else {
+                    0
+                };
                 self.update_viewer(hub);
                 self.update_bottom_bar(hub);
             },
@@ -163,6 +378,15 @@ impl Reader {
         }
     }
 
+    fn update_history_icons(&mut self, hub: &Hub) {
+        if let Some(index) = locate::<BottomBar>(self) {
+            let can_go_back = !self.history.back.is_empty();
+            let can_go_forward = !self.history.forward.is_empty();
+            let bottom_bar = self.children[index].as_mut().downcast_mut::<BottomBar>().unwrap();
+            bottom_bar.update_history_icons(can_go_back, can_go_forward, hub);
+        }
+    }
+
     fn update_viewer(&mut self, hub: &Hub) {
         self.page_turns += 1;
         let update_mode = if let Some(n) = self.refresh_every {
@@ -174,20 +398,255 @@ impl Reader {
         } else {
             UpdateMode::Partial
         };
-        let margin = self.info.reader.as_ref()
-                         .and_then(|r| r.margin_at(self.current_page))
-                         .cloned().unwrap_or_default();
-        self.pixmap = Rc::new(build_pixmap(&self.rect, self.doc.as_ref(), self.current_page, &margin));
-        let frame = rect![(margin.left * self.pixmap.width as f32).ceil() as i32,
-                          (margin.top * self.pixmap.height as f32).ceil() as i32,
-                          ((1.0 - margin.right) * self.pixmap.width as f32).floor() as i32,
-                          ((1.0 - margin.bottom) * self.pixmap.height as f32).floor() as i32];
+        self.update_pixmap();
+        self.render_page(update_mode, hub);
+    }
+
+    fn current_margin(&self) -> Margin {
+        if self.repage {
+            let margin = self.regions.get(&self.current_page)
+                             .and_then(|r| r.get(self.current_region));
+            if let Some(margin) = margin {
+                return margin.clone();
+            }
+        }
+        self.info.reader.as_ref()
+            .and_then(|r| r.margin_at(self.current_page))
+            .cloned().unwrap_or_default()
+    }
+
+    fn regions_count(&mut self, index: usize) -> usize {
+        if !self.regions.contains_key(&index) {
+            let page_regions = region_margins(self.doc.as_ref(), index);
+            self.regions.insert(index, page_regions);
+        }
+        self.regions[&index].len()
+    }
+
+    // Moves to the neighboring region of the current page, when repaging.
+    fn region_step(&mut self, dir: CycleDir, hub: &Hub) -> bool {
+        if !self.repage {
+            return false;
+        }
+        let count = self.regions_count(self.current_page);
+        match dir {
+            CycleDir::Next if self.current_region + 1 < count => {
+                self.current_region += 1;
+                self.scroll = Point::default();
+            },
+            CycleDir::Previous if self.current_region > 0 => {
+                self.current_region -= 1;
+                self.scroll = pt!(i32::max_value());
+            },
+            _ => return false,
+        }
+        self.update_viewer(hub);
+        true
+    }
+
+    fn toggle_repage(&mut self, hub: &Hub) {
+        if self.doc.is_reflowable() {
+            return;
+        }
+        self.repage = !self.repage;
+        if let Some(ref mut r) = self.info.reader {
+            r.repage = self.repage;
+        }
+        self.current_region = 0;
+        self.scroll = Point::default();
+        self.update_viewer(hub);
+    }
+
+    fn update_pixmap(&mut self) {
+        if self.repage {
+            let current_page = self.current_page;
+            self.regions_count(current_page);
+        }
+        let margin = self.current_margin();
+        let scale = zoom_scale(&self.rect, self.doc.as_ref(), self.current_page, &margin, self.zoom_mode);
+        let key = PixmapKey::new(self.current_page, scale, &margin);
+        self.pixmap = self.cache.get(&key).unwrap_or_else(|| {
+            let pixmap = Arc::new(self.doc.pixmap(self.current_page, scale).unwrap());
+            self.cache.insert(key, pixmap.clone());
+            pixmap
+        });
+        self.scale = scale;
+        self.prefetch();
+    }
+
+    // Renders the neighbors of the current page in the background, starting in the reading direction.
+    // When repaging, only the regions of the current page are known in advance.
+    fn prefetch(&self) {
+        let step: isize = match self.direction {
+            CycleDir::Next => 1,
+            CycleDir::Previous => -1,
+        };
+        let mut targets = Vec::new();
+
+        if self.repage {
+            if let Some(regions) = self.regions.get(&self.current_page) {
+                for &delta in &[step, -step] {
+                    let region = self.current_region as isize + delta;
+                    if region >= 0 && (region as usize) < regions.len() {
+                        targets.push((self.current_page, regions[region as usize].clone()));
+                    }
+                }
+            }
+        } else {
+            for &delta in &[step, -step, 2 * step] {
+                let index = self.current_page as isize + delta;
+                if index >= 0 && (index as usize) < self.pages_count {
+                    let index = index as usize;
+                    let margin = self.info.reader.as_ref()
+                                     .and_then(|r| r.margin_at(index))
+                                     .cloned().unwrap_or_default();
+                    targets.push((index, margin));
+                }
+            }
+        }
+
+        let keys = targets.into_iter().map(|(index, margin)| {
+            let scale = zoom_scale(&self.rect, self.doc.as_ref(), index, &margin, self.zoom_mode);
+            PixmapKey::new(index, scale, &margin)
+        }).collect();
+
+        self.cache.prefetch(keys);
+    }
+
+    // The amount by which the cropped page exceeds the screen in each direction.
+    fn max_scroll(&self) -> Point {
+        let crop = crop_frame(&self.pixmap, &self.current_margin());
+        pt!((crop.width() as i32 - self.rect.width() as i32).max(0),
+            (crop.height() as i32 - self.rect.height() as i32).max(0))
+    }
+
+    fn render_page(&mut self, update_mode: UpdateMode, hub: &Hub) {
+        let max_scroll = self.max_scroll();
+        self.scroll = pt!(self.scroll.x.max(0).min(max_scroll.x),
+                          self.scroll.y.max(0).min(max_scroll.y));
+        let crop = crop_frame(&self.pixmap, &self.current_margin());
+        let frame = visible_frame(&self.rect, &crop, &self.scroll);
+        let scale = self.scale;
+        let search_rects = self.search_highlights();
+        let annotation_rects = self.annotation_rects();
+        let selection_rects = self.selection_rects();
+        let bookmarked = self.is_bookmarked();
+        // The links only change with the page.
+        let links = if self.links_page != Some(self.current_page) {
+            self.links_page = Some(self.current_page);
+            Some(self.doc.links(self.current_page).unwrap_or_default())
+        } else {
+            None
+        };
         if let Some(index) = locate::<Viewer>(self) {
             let viewer = self.children[index].as_mut().downcast_mut::<Viewer>().unwrap();
-            viewer.update(self.pixmap.clone(), frame, update_mode, hub);
+            viewer.set_search_rects(search_rects);
+            viewer.set_annotation_rects(annotation_rects);
+            viewer.set_selection_rects(selection_rects);
+            viewer.set_bookmarked(bookmarked);
+            if let Some(links) = links {
+                viewer.set_links(links);
+            }
+            viewer.update(self.pixmap.clone(), frame, scale, update_mode, hub);
+        }
+    }
+
+    // Moves through the page, column by column, when it doesn't fit on the screen.
+    fn scroll_step(&mut self, dir: CycleDir, hub: &Hub) -> bool {
+        let max_scroll = self.max_scroll();
+        let (width, height) = (self.rect.width() as i32, self.rect.height() as i32);
+        let scroll = self.scroll;
+        match dir {
+            CycleDir::Next if scroll.y < max_scroll.y => {
+                self.scroll.y = (scroll.y + height).min(max_scroll.y);
+            },
+            CycleDir::Next if scroll.x < max_scroll.x => {
+                self.scroll = pt!((scroll.x + width).min(max_scroll.x), 0);
+            },
+            CycleDir::Previous if scroll.y > 0 => {
+                self.scroll.y = (scroll.y - height).max(0);
+            },
+            CycleDir::Previous if scroll.x > 0 => {
+                self.scroll = pt!((scroll.x - width).max(0), max_scroll.y);
+            },
+            _ => return false,
+        }
+        self.render_page(UpdateMode::Partial, hub);
+        true
+    }
+
+    fn scroll(&mut self, delta: Point, hub: &Hub) {
+        let scroll = self.scroll;
+        let max_scroll = self.max_scroll();
+        self.scroll = pt!((scroll.x + delta.x).max(0).min(max_scroll.x),
+                          (scroll.y + delta.y).max(0).min(max_scroll.y));
+        if self.scroll != scroll {
+            self.render_page(UpdateMode::Partial, hub);
+        }
+    }
+
+    // The target is the point of the screen that shouldn't move.
+    fn set_zoom_mode(&mut self, zoom_mode: ZoomMode, target: Option<Point>, hub: &Hub) {
+        if self.doc.is_reflowable() || zoom_mode == self.zoom_mode {
+            return;
+        }
+        let page_pt = target.and_then(|pt| self.to_page(&pt).map(|page_pt| (pt, page_pt)));
+        self.zoom_mode = zoom_mode;
+        if let Some(ref mut r) = self.info.reader {
+            r.zoom_mode = Some(zoom_mode);
+        }
+        self.update_pixmap();
+        self.scroll = if let Some((pt, page_pt)) = page_pt {
+            let crop = crop_frame(&self.pixmap, &self.current_margin());
+            pt!((page_pt.x as f32 * self.scale) as i32,
+                (page_pt.y as f32 * self.scale) as i32) - crop.min - (pt - self.rect.min)
+        } else {
+            Point::default()
+        };
+        self.render_page(UpdateMode::Partial, hub);
+    }
+
+    fn zoom(&mut self, factor: f32, target: Point, hub: &Hub) {
+        let margin = self.current_margin();
+        let page_scale = fit_scales(&self.rect, self.doc.as_ref(), self.current_page, &margin).0;
+        let zoom_factor = (factor * self.scale / page_scale).max(1.0).min(MAX_ZOOM_FACTOR);
+        self.set_zoom_mode(ZoomMode::Custom(zoom_factor), Some(target), hub);
+    }
+
+    fn toggle_zoom_menu(&mut self, rect: Rectangle, enable: Option<bool>, hub: &Hub, fonts: &mut Fonts) {
+        if let Some(index) = locate_by_id(self, ViewId::ZoomMenu) {
+            if let Some(true) = enable {
+                return;
+            }
+            hub.send(Event::Expose(*self.child(index).rect())).unwrap();
+            self.children.remove(index);
+        } else {
+            if let Some(false) = enable {
+                return;
+            }
+            let entries = &[EntryKind::RadioButton("Fit to Page".to_string(),
+                                                   EntryId::FitToPage,
+                                                   self.zoom_mode == ZoomMode::FitToPage),
+                            EntryKind::RadioButton("Fit to Width".to_string(),
+                                                   EntryId::FitToWidth,
+                                                   self.zoom_mode == ZoomMode::FitToWidth),
+                            EntryKind::Separator,
+                            EntryKind::CheckBox("Repage".to_string(),
+                                                EntryId::ToggleRepage,
+                                                self.repage)];
+            let zoom_menu = Menu::new(rect, ViewId::ZoomMenu, entries, fonts);
+            hub.send(Event::Render(*zoom_menu.rect(), UpdateMode::Gui)).unwrap();
+            self.children.push(Box::new(zoom_menu) as Box<View>);
         }
     }
 
+    fn search_highlights(&self) -> Vec<Rectangle> {
+        self.search.as_ref().map(|s| {
+            s.results.iter().filter(|m| m.page == self.current_page)
+                     .flat_map(|m| m.rects.clone()).collect()
+        }).unwrap_or_default()
+    }
+
     fn toggle_keyboard(&mut self, enable: bool, hub: &Hub) {
         if let Some(index) = locate::<Keyboard>(self) {
             if enable {
@@ -203,18 +662,22 @@ impl Reader {
                 return;
             }
 
-            let index = locate::<BottomBar>(self).unwrap() - 1;
-
             let dpi = CURRENT_DEVICE.dpi;
-            let (_, height) = CURRENT_DEVICE.dims;
+            let (_, height) = CURRENT_DEVICE.screen_dims();
             let &(small_height, big_height) = BAR_SIZES.get(&(height, dpi)).unwrap();
             let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
-            let (small_thickness, big_thickness) = halves(thickness);
+            let (small_thickness, _) = halves(thickness);
+
+            // Without bars, the keyboard sits at the bottom of the screen.
+            let (index, y_max) = match locate::<BottomBar>(self).or_else(|| locate::<SearchBar>(self)) {
+                Some(index) => (index - 1, self.rect.max.y - small_height as i32 - small_thickness),
+                None => (self.children.len(), self.rect.max.y),
+            };
 
             let mut kb_rect = rect![self.rect.min.x,
-                                    self.rect.max.y - (small_height + 3 * big_height) as i32 + big_thickness,
+                                    y_max - 3 * big_height as i32 + thickness,
                                     self.rect.max.x,
-                                    self.rect.max.y - small_height as i32 - small_thickness];
+                                    y_max];
 
             let keyboard = Keyboard::new(&mut kb_rect, DEFAULT_LAYOUT.clone());
             self.children.insert(index, Box::new(keyboard) as Box<View>);
@@ -235,7 +698,7 @@ impl Reader {
             self.children.drain(index..index+6);
         } else {
             let dpi = CURRENT_DEVICE.dpi;
-            let (_, height) = CURRENT_DEVICE.dims;
+            let (_, height) = CURRENT_DEVICE.screen_dims();
             let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
             let (small_thickness, big_thickness) = halves(thickness);
             let &(small_height, big_height) = BAR_SIZES.get(&(height, dpi)).unwrap();
@@ -283,7 +746,9 @@ impl Reader {
                                                   self.rect.max.y],
                                             self.doc.as_ref(),
                                             self.current_page,
-                                            self.pages_count);
+                                            self.pages_count,
+                                            !self.history.back.is_empty(),
+                                            !self.history.forward.is_empty());
             self.children.push(Box::new(bottom_bar) as Box<View>);
         }
     }
@@ -344,10 +809,11 @@ impl Reader {
                              .and_then(|r| r.margin_at(self.current_page))
                              .cloned().unwrap_or_default();
 
-            let pixmap = build_pixmap(&pixmap_rect,
-                                      self.doc.as_ref(),
-                                      self.current_page,
-                                      &Margin::default());
+            let (pixmap, _) = build_pixmap(&pixmap_rect,
+                                           self.doc.as_ref(),
+                                           self.current_page,
+                                           &Margin::default(),
+                                           ZoomMode::FitToPage);
 
             let margin_cropper = MarginCropper::new(self.rect, pixmap, &margin);
             hub.send(Event::Render(*margin_cropper.rect(), UpdateMode::Gui)).unwrap();
@@ -355,122 +821,987 @@ impl Reader {
         }
     }
 
-    fn set_font_size(&mut self, font_size: f32, hub: &Hub) {
-        if let Some(ref mut r) = self.info.reader {
-            r.font_size = Some(font_size);
+    fn show_search_bar(&mut self, hub: &Hub, fonts: &mut Fonts) {
+        if locate::<ResultsBar>(self).is_some() {
+            self.close_search(hub);
         }
-        let (width, height) = CURRENT_DEVICE.dims;
-        self.doc.layout(width as f32, height as f32,
-                        pt_to_px(font_size,
-                                 CURRENT_DEVICE.dpi));
-        let position = self.current_page as f32 / self.pages_count as f32;
-        self.pages_count = self.doc.pages_count();
-        self.current_page = ((position * self.pages_count as f32) as usize).min(self.pages_count - 1);
-        self.update_viewer(hub);
-        self.update_bottom_bar(hub);
+
+        if locate::<TopBar>(self).is_some() {
+            self.toggle_bars(fonts);
+            hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+        }
+
+        let dpi = CURRENT_DEVICE.dpi;
+        let (_, height) = CURRENT_DEVICE.screen_dims();
+        let &(small_height, _) = BAR_SIZES.get(&(height, dpi)).unwrap();
+        let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
+        let (small_thickness, big_thickness) = halves(thickness);
+
+        let separator = Filler::new(rect![self.rect.min.x,
+                                          self.rect.max.y - small_height as i32 - small_thickness,
+                                          self.rect.max.x,
+                                          self.rect.max.y - small_height as i32 + big_thickness],
+                                    BLACK);
+        hub.send(Event::Render(*separator.rect(), UpdateMode::Gui)).unwrap();
+        self.children.push(Box::new(separator) as Box<View>);
+
+        let search_bar = SearchBar::new(rect![self.rect.min.x,
+                                              self.rect.max.y - small_height as i32 + big_thickness,
+                                              self.rect.max.x,
+                                              self.rect.max.y],
+                                        "Text");
+        hub.send(Event::Render(*search_bar.rect(), UpdateMode::Gui)).unwrap();
+        self.children.push(Box::new(search_bar) as Box<View>);
+
+        hub.send(Event::Focus(Some(ViewId::SearchInput))).unwrap();
     }
 
-    fn crop_margins(&mut self, margin: &Margin, hub: &Hub) {
-        if let Some(ref mut r) = self.info.reader {
-            r.cropping_margins.insert(self.current_page, margin.clone());
+    fn search(&mut self, text: &str, hub: &Hub, context: &mut Context) {
+        let query = match make_literal_query(text) {
+            Some(query) => query,
+            None => {
+                self.show_notification("Invalid search query.", hub, &mut context.fonts);
+                return;
+            },
+        };
+
+        if let Some(index) = locate::<SearchBar>(self) {
+            let results_bar = ResultsBar::new(*self.child(index).rect());
+            hub.send(Event::Render(*results_bar.rect(), UpdateMode::Gui)).unwrap();
+            self.children[index] = Box::new(results_bar) as Box<View>;
         }
-        self.update_viewer(hub);
+
+        // The previous search might still be running.
+        if let Some(ref s) = self.search {
+            s.running.store(false, Ordering::Relaxed);
+        }
+
+        let id = self.searches_count;
+        self.searches_count += 1;
+        let running = Arc::new(AtomicBool::new(true));
+        self.search = Some(Search {
+            id,
+            query: text.to_string(),
+            running: running.clone(),
+            results: Vec::new(),
+            current: None,
+            completed: false,
+        });
+
+        // The document isn't shared with the search thread, which opens its own copy.
+        let path = context.settings.library_path.join(&self.info.file.path);
+        let font_size = self.info.reader.as_ref().and_then(|r| r.font_size);
+        let (width, height, font_size) = reader_layout(&CURRENT_DEVICE, font_size);
+        let hub2 = hub.clone();
+
+        thread::spawn(move || {
+            if let Some(mut doc) = open(&path) {
+                doc.layout(width, height, font_size);
+                for index in 0..doc.pages_count() {
+                    if !running.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    if let Some(text) = doc.text(index) {
+                        let matches = text.search(index, &query);
+                        if !matches.is_empty() {
+                            hub2.send(Event::SearchResult(id, matches)).unwrap();
+                        }
+                    }
+                }
+            }
+            if running.load(Ordering::Relaxed) {
+                hub2.send(Event::EndOfSearch(id)).unwrap();
+            }
+        });
     }
 
-    fn quit(&mut self, context: &mut Context) {
-        if let Some(ref mut r) = self.info.reader {
-            r.current_page = self.current_page;
-            r.pages_count = self.pages_count;
-            r.finished = self.finished;
+    fn add_search_results(&mut self, matches: &[TextMatch], hub: &Hub) {
+        let current_page = self.current_page;
+        let mut target = None;
+
+        if let Some(ref mut s) = self.search {
+            let start = s.results.len();
+            s.results.extend_from_slice(matches);
+            // Jump to the first match following the current page.
+            if s.current.is_none() {
+                if let Some(i) = matches.iter().position(|m| m.page >= current_page) {
+                    s.current = Some(start + i);
+                    target = Some(matches[i].page);
+                }
+            }
+        } else {
+            return;
         }
-        for i in &mut context.metadata {
-            if i.file.path == self.info.file.path {
-                *i = self.info.clone();
-                break;
+
+        if let Some(index) = target {
+            if index == self.current_page {
+                self.update_viewer(hub);
+            } else {
+                self.jump_to_page(index, hub);
             }
         }
+
+        self.update_results_bar(hub);
+        self.update_search_results(hub);
     }
-}
 
-impl View for Reader {
-    fn handle_event(&mut self, evt: &Event, hub: &Hub, _bus: &mut Bus, context: &mut Context) -> bool {
-        match *evt {
-            Event::Page(dir) => {
-                self.set_current_page(dir, hub);
-                true
-            },
-            Event::GoTo(index) => {
-                self.go_to_page(index, hub);
-                true
-            },
-            Event::Chapter(dir) => {
-                self.go_to_chapter(dir, hub);
-                true
-            },
-            Event::CropMargins(ref margin) => {
-                self.crop_margins(margin.as_ref(), hub);
-                true
-            },
-            Event::Toggle(ViewId::TopBottomBars) => {
-                self.toggle_bars(&mut context.fonts);
-                let update_mode = if locate::<TopBar>(self).is_some() {
-                    UpdateMode::Gui
-                } else {
-                    UpdateMode::Full
+    fn go_to_search_hit(&mut self, dir: CycleDir, hub: &Hub) {
+        let current_page = self.current_page;
+        let index = match self.search {
+            Some(ref mut s) if !s.results.is_empty() => {
+                let index = match (s.current, dir) {
+                    (Some(i), CycleDir::Next) if i < s.results.len() - 1 => i + 1,
+                    (Some(i), CycleDir::Previous) if i > 0 => i - 1,
+                    (Some(_), _) => return,
+                    (None, CycleDir::Next) => s.results.iter()
+                                               .position(|m| m.page >= current_page)
+                                               .unwrap_or(0),
+                    (None, CycleDir::Previous) => s.results.iter()
+                                                   .rposition(|m| m.page <= current_page)
+                                                   .unwrap_or(s.results.len() - 1),
                 };
-                hub.send(Event::Render(self.rect, update_mode)).unwrap();
-                true
-            },
-            Event::Toggle(ViewId::GoToPage) => {
-                self.toggle_go_to_page(None, hub, &mut context.fonts);
-                true
-            },
-            Event::Slider(SliderId::FontSize, font_size, FingerStatus::Up) => {
-                self.set_font_size(font_size, hub);
-                true
-            },
-            Event::ToggleNear(ViewId::MainMenu, rect) => {
-                toggle_main_menu(self, rect, None, hub, context);
-                true
-            },
-            Event::Close(ViewId::MainMenu) => {
-                toggle_main_menu(self, Rectangle::default(), Some(false), hub, context);
-                true
-            },
-            Event::Close(ViewId::GoToPage) => {
-                self.toggle_go_to_page(Some(false), hub, &mut context.fonts);
-                true
-            },
-            Event::Show(ViewId::MarginCropper) => {
-                self.toggle_margin_cropper(true, hub, &mut context.fonts);
-                true
-            },
-            Event::Close(ViewId::MarginCropper) => {
-                self.toggle_margin_cropper(false, hub, &mut context.fonts);
-                true
-            },
-            Event::Select(EntryId::Quit) | Event::Back => {
-                self.quit(context);
-                false
-            },
-            Event::Focus(v) => {
-                self.focus = v;
-                self.toggle_keyboard(true, hub);
-                false
+                s.current = Some(index);
+                s.results[index].page
             },
-            _ => false,
+            _ => return,
+        };
+
+        if index == self.current_page {
+            self.update_viewer(hub);
+        } else {
+            self.jump_to_page(index, hub);
         }
-    }
 
-    fn render(&self, _fb: &mut Framebuffer, _fonts: &mut Fonts) {
+        self.update_results_bar(hub);
     }
 
-    fn rect(&self) -> &Rectangle {
-        &self.rect
+    fn update_results_bar(&mut self, hub: &Hub) {
+        if let Some(index) = locate::<ResultsBar>(self) {
+            if let Some(ref s) = self.search {
+                let results_bar = self.children[index].as_mut().downcast_mut::<ResultsBar>().unwrap();
+                results_bar.update(s.current, s.results.len(), s.completed, hub);
+            }
+        }
     }
 
-    fn rect_mut(&mut self) -> &mut Rectangle {
-        &mut self.rect
+    fn search_entries(&self) -> Vec<PageListEntry> {
+        self.search.as_ref().map(|s| {
+            s.results.iter().map(|m| PageListEntry { page: m.page, text: m.context.clone() })
+                     .collect()
+        }).unwrap_or_default()
+    }
+
+    fn update_search_results(&mut self, hub: &Hub) {
+        if let Some(index) = locate_by_id(self, ViewId::SearchResults) {
+            let entries = self.search_entries();
+            let page_list = self.children[index].as_mut().downcast_mut::<PageList>().unwrap();
+            page_list.update(entries, hub);
+        }
+    }
+
+    fn toggle_page_list(&mut self, id: ViewId, enable: bool, hub: &Hub) {
+        if let Some(index) = locate_by_id(self, id) {
+            if enable {
+                return;
+            }
+
+            self.children.remove(index);
+            hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+        } else {
+            if !enable {
+                return;
+            }
+
+            let (title, entries) = match id {
+                ViewId::SearchResults => {
+                    if let Some(ref s) = self.search {
+                        (format!("Results for “{}”", s.query), self.search_entries())
+                    } else {
+                        return;
+                    }
+                },
+                ViewId::Bookmarks => ("Bookmarks".to_string(), self.bookmark_entries()),
+                ViewId::Annotations => ("Annotations".to_string(), self.annotation_entries()),
+                _ => return,
+            };
+
+            let page_list = PageList::new(self.rect, id, title, entries);
+            hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+            self.children.push(Box::new(page_list) as Box<View>);
+        }
+    }
+
+    fn is_bookmarked(&self) -> bool {
+        self.info.reader.as_ref()
+            .map_or(false, |r| r.bookmarks.contains(&self.current_page))
+    }
+
+    fn toggle_bookmark(&mut self, hub: &Hub) {
+        let current_page = self.current_page;
+        if let Some(ref mut r) = self.info.reader {
+            if r.bookmarks.remove(&current_page) {
+                r.bookmark_anchors.remove(&current_page);
+            } else {
+                r.bookmarks.insert(current_page);
+                if self.doc.is_reflowable() {
+                    let anchor = match self.anchor {
+                        Some(ref anchor) => anchor.clone(),
+                        None => anchor_at(self.doc.as_ref(), current_page, &mut self.chars_counts),
+                    };
+                    r.bookmark_anchors.insert(current_page, anchor);
+                }
+            }
+        }
+        let bookmarked = self.is_bookmarked();
+        if let Some(index) = locate::<Viewer>(self) {
+            let viewer = self.children[index].as_mut().downcast_mut::<Viewer>().unwrap();
+            viewer.set_bookmarked(bookmarked);
+            hub.send(Event::Render(*viewer.rect(), UpdateMode::Gui)).unwrap();
+        }
+    }
+
+    fn go_to_bookmark(&mut self, dir: CycleDir, hub: &Hub) {
+        let current_page = self.current_page;
+        let index = self.info.reader.as_ref().and_then(|r| {
+            match dir {
+                CycleDir::Next => r.bookmarks.range(current_page+1..).next().cloned(),
+                CycleDir::Previous => r.bookmarks.range(..current_page).next_back().cloned(),
+            }
+        });
+        if let Some(index) = index {
+            self.jump_to_page(index, hub);
+        }
+    }
+
+    fn bookmark_entries(&self) -> Vec<PageListEntry> {
+        let toc = self.doc.toc();
+        self.info.reader.as_ref().map(|r| {
+            r.bookmarks.iter().map(|&page| {
+                let text = toc.as_ref().and_then(|t| chapter_at(t, page))
+                              .map(|c| c.title)
+                              .unwrap_or_default();
+                PageListEntry { page, text }
+            }).collect()
+        }).unwrap_or_default()
+    }
+
+    fn annotation_rects(&self) -> Vec<Rectangle> {
+        self.info.reader.as_ref().map(|r| {
+            annotation_rects(&r.annotations, self.current_page)
+        }).unwrap_or_default()
+    }
+
+    fn selection_rects(&self) -> Vec<Rectangle> {
+        self.selection.as_ref().filter(|s| s.page == self.current_page)
+                      .map(|s| s.rects()).unwrap_or_default()
+    }
+
+    fn annotation_entries(&self) -> Vec<PageListEntry> {
+        self.info.reader.as_ref().map(|r| {
+            let mut entries: Vec<PageListEntry> = r.annotations.iter().map(|a| {
+                let text = if a.note.is_empty() {
+                    format!("“{}”", a.text)
+                } else {
+                    format!("“{}” — {}", a.text, a.note)
+                };
+                PageListEntry { page: a.page, text }
+            }).collect();
+            entries.sort_by_key(|e| e.page);
+            entries
+        }).unwrap_or_default()
+    }
+
+    fn render_annotations(&mut self, update_mode: UpdateMode, hub: &Hub) {
+        let annotation_rects = self.annotation_rects();
+        let selection_rects = self.selection_rects();
+        if let Some(index) = locate::<Viewer>(self) {
+            let viewer = self.children[index].as_mut().downcast_mut::<Viewer>().unwrap();
+            viewer.set_annotation_rects(annotation_rects);
+            viewer.set_selection_rects(selection_rects);
+            hub.send(Event::Render(*viewer.rect(), update_mode)).unwrap();
+        }
+    }
+
+    fn to_page(&self, pt: &Point) -> Option<Point> {
+        locate::<Viewer>(self).map(|index| {
+            self.child(index).downcast_ref::<Viewer>().unwrap().to_page(pt)
+        })
+    }
+
+    // Returns false if there's no word under the given point.
+    fn start_selection(&mut self, pt: &Point, hub: &Hub) -> bool {
+        let page_pt = match self.to_page(pt) {
+            Some(page_pt) => page_pt,
+            None => return false,
+        };
+        let words = self.doc.text(self.current_page)
+                        .map(|t| t.word_boxes())
+                        .unwrap_or_default();
+        if let Some(index) = words.iter().position(|w| w.rect.includes(&page_pt)) {
+            self.selection = Some(Selection {
+                page: self.current_page,
+                words,
+                anchor: index,
+                cursor: index,
+                dragging: true,
+            });
+            self.render_annotations(UpdateMode::Fast, hub);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn extend_selection(&mut self, pt: &Point, hub: &Hub) {
+        let page_pt = match self.to_page(pt) {
+            Some(page_pt) => page_pt,
+            None => return,
+        };
+        let mut changed = false;
+        if let Some(ref mut s) = self.selection {
+            let nearest = s.words.iter().enumerate()
+                           .min_by_key(|&(_, w)| w.rect.center().dist2(&page_pt))
+                           .map(|(i, _)| i);
+            if let Some(index) = nearest {
+                changed = index != s.cursor;
+                s.cursor = index;
+            }
+        }
+        if changed {
+            self.render_annotations(UpdateMode::Fast, hub);
+        }
+    }
+
+    fn end_selection(&mut self, hub: &Hub, context: &mut Context) {
+        let rects = match self.selection {
+            Some(ref mut s) => {
+                s.dragging = false;
+                s.rects()
+            },
+            None => return,
+        };
+
+        let target = match locate::<Viewer>(self) {
+            Some(index) => {
+                let viewer = self.child(index).downcast_ref::<Viewer>().unwrap();
+                rects.iter().fold(viewer.to_screen(&rects[0]), |mut target, rect| {
+                    target.absorb(&viewer.to_screen(rect));
+                    target
+                })
+            },
+            None => return,
+        };
+
+        // A single word is defined right away, the menu is shown above the definition.
+        let single_word = self.selection.as_ref().map_or(false, |s| s.anchor == s.cursor);
+        let has_dictionaries = self.has_dictionaries(context);
+        let mut entries = vec![EntryKind::Command("Highlight".to_string(), EntryId::Highlight),
+                               EntryKind::Command("Add Note".to_string(), EntryId::Annotate)];
+        if has_dictionaries {
+            if single_word {
+                self.define_selection(hub, context);
+            } else {
+                entries.insert(0, EntryKind::Command("Define".to_string(), EntryId::Define));
+            }
+        }
+        let menu = Menu::new(target, ViewId::AnnotationMenu, &entries, &mut context.fonts);
+        hub.send(Event::Render(*menu.rect(), UpdateMode::Gui)).unwrap();
+        self.children.push(Box::new(menu) as Box<View>);
+        self.render_annotations(UpdateMode::Gui, hub);
+    }
+
+    fn define_selection(&mut self, hub: &Hub, context: &mut Context) {
+        let word = match self.selection {
+            Some(ref s) => s.text(),
+            None => return,
+        };
+        if !self.has_dictionaries(context) {
+            return;
+        }
+        let definitions = define(self.dictionaries.as_mut().unwrap(), &word);
+        if definitions.is_empty() {
+            let text = format!("No definitions found for {}.", word);
+            self.show_notification(&text, hub, &mut context.fonts);
+        } else {
+            self.close_definition(hub);
+            let definition = Definition::new(&definitions, &mut context.fonts);
+            hub.send(Event::Render(*definition.rect(), UpdateMode::Gui)).unwrap();
+            self.children.push(Box::new(definition) as Box<View>);
+        }
+    }
+
+    // The dictionaries are loaded on the first use.
+    fn has_dictionaries(&mut self, context: &Context) -> bool {
+        if self.dictionaries.is_none() {
+            let dir = context.settings.library_path.join(DICTIONARIES_DIRNAME);
+            self.dictionaries = Some(load_dictionaries(&dir));
+        }
+        self.dictionaries.as_ref().map_or(false, |d| !d.is_empty())
+    }
+
+    fn close_definition(&mut self, hub: &Hub) {
+        if let Some(index) = locate_by_id(self, ViewId::Definition) {
+            hub.send(Event::Expose(*self.child(index).rect())).unwrap();
+            self.children.remove(index);
+        }
+    }
+
+    fn clear_selection(&mut self, hub: &Hub) {
+        if self.selection.take().is_some() {
+            self.render_annotations(UpdateMode::Gui, hub);
+        }
+    }
+
+    fn close_annotation_menu(&mut self, hub: &Hub) {
+        if let Some(index) = locate_by_id(self, ViewId::AnnotationMenu) {
+            hub.send(Event::Expose(*self.child(index).rect())).unwrap();
+            self.children.remove(index);
+        }
+        if locate_by_id(self, ViewId::AnnotationNote).is_none() {
+            self.clear_selection(hub);
+        }
+    }
+
+    fn toggle_annotation_note(&mut self, enable: bool, hub: &Hub, fonts: &mut Fonts) {
+        if let Some(index) = locate_by_id(self, ViewId::AnnotationNote) {
+            if enable {
+                return;
+            }
+
+            hub.send(Event::Expose(*self.child(index).rect())).unwrap();
+            self.children.remove(index);
+
+            if let Some(ViewId::AnnotationInput) = self.focus {
+                self.focus = None;
+                self.toggle_keyboard(false, hub);
+            }
+        } else {
+            if !enable {
+                return;
+            }
+
+            let viewer_rect = *self.child(0).rect();
+            let anchor = pt!(viewer_rect.center().x,
+                             viewer_rect.min.y + viewer_rect.height() as i32 / 3);
+            let named_input = NamedInput::new(&anchor, "Note".to_string(),
+                                              ViewId::AnnotationNote, ViewId::AnnotationInput,
+                                              32, fonts);
+
+            hub.send(Event::Render(*named_input.rect(), UpdateMode::Gui)).unwrap();
+            hub.send(Event::Focus(Some(ViewId::AnnotationInput))).unwrap();
+
+            self.children.push(Box::new(named_input) as Box<View>);
+        }
+    }
+
+    fn add_annotation(&mut self, note: &str, hub: &Hub) {
+        if let Some(s) = self.selection.take() {
+            let anchor = if self.doc.is_reflowable() {
+                let (start, _) = s.range();
+                let mut anchor = anchor_at(self.doc.as_ref(), s.page, &mut self.chars_counts);
+                anchor.offset += s.words[..start].iter().map(|w| w.text.chars().count()).sum::<usize>();
+                Some(anchor)
+            } else {
+                None
+            };
+            if let Some(ref mut r) = self.info.reader {
+                r.annotations.push(Annotation {
+                    page: s.page,
+                    anchor,
+                    text: s.text(),
+                    note: note.to_string(),
+                    rects: s.rects(),
+                });
+            }
+            self.render_annotations(UpdateMode::Gui, hub);
+        }
+    }
+
+    fn toggle_table_of_contents(&mut self, enable: bool, hub: &Hub) {
+        if let Some(index) = locate_by_id(self, ViewId::TableOfContents) {
+            if enable {
+                return;
+            }
+
+            self.children.remove(index);
+            hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+        } else {
+            if !enable {
+                return;
+            }
+
+            if let Some(toc) = self.doc.toc().filter(|t| !t.is_empty()) {
+                let toc_view = TableOfContents::new(self.rect, toc, self.current_page);
+                hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+                self.children.push(Box::new(toc_view) as Box<View>);
+            }
+        }
+    }
+
+    fn toggle_page_overview(&mut self, enable: bool, hub: &Hub, context: &Context) {
+        if let Some(index) = locate_by_id(self, ViewId::PageOverview) {
+            if enable {
+                return;
+            }
+
+            self.children.remove(index);
+            hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+        } else {
+            if !enable {
+                return;
+            }
+
+            let library_path = &context.settings.library_path;
+            let title = if self.info.title.is_empty() {
+                "Pages".to_string()
+            } else {
+                self.info.title.clone()
+            };
+            let layout = if self.doc.is_reflowable() {
+                let font_size = self.info.reader.as_ref().and_then(|r| r.font_size);
+                Some(reader_layout(&CURRENT_DEVICE, font_size))
+            } else {
+                None
+            };
+            let page_overview = PageOverview::new(self.rect, title,
+                                                  library_path.join(&self.info.file.path),
+                                                  thumbnails_dir(library_path, &self.info.file.path),
+                                                  layout, self.pages_count, self.current_page, hub);
+            hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+            self.children.push(Box::new(page_overview) as Box<View>);
+        }
+    }
+
+    fn close_search(&mut self, hub: &Hub) {
+        if let Some(ViewId::SearchInput) = self.focus {
+            self.focus = None;
+            self.toggle_keyboard(false, hub);
+        }
+
+        self.toggle_page_list(ViewId::SearchResults, false, hub);
+
+        if let Some(index) = locate::<SearchBar>(self).or_else(|| locate::<ResultsBar>(self)) {
+            let mut rect = *self.child(index).rect();
+            rect.absorb(self.child(index-1).rect());
+            hub.send(Event::Expose(rect)).unwrap();
+            self.children.drain(index - 1 .. index + 1);
+        }
+
+        if let Some(s) = self.search.take() {
+            s.running.store(false, Ordering::Relaxed);
+            if s.results.iter().any(|m| m.page == self.current_page) {
+                self.update_viewer(hub);
+            }
+        }
+    }
+
+    fn set_font_size(&mut self, font_size: f32, hub: &Hub) {
+        if let Some(ref mut r) = self.info.reader {
+            r.font_size = Some(font_size);
+            if self.doc.is_reflowable() {
+                anchor_bookmarks(self.doc.as_ref(), r, &mut self.chars_counts);
+            }
+        }
+        // Reuse the anchor as long as the page doesn't change, to avoid drifting.
+        let anchor = self.anchor.take().or_else(|| {
+            if self.doc.is_reflowable() {
+                Some(anchor_at(self.doc.as_ref(), self.current_page, &mut self.chars_counts))
+            } else {
+                None
+            }
+        });
+        let layout = reader_layout(&CURRENT_DEVICE, Some(font_size));
+        let (width, height, font_size) = layout;
+        self.doc.layout(width, height, font_size);
+        self.cache.relayout(width, height, font_size);
+        self.chars_counts.clear();
+        if let Some(ref mut r) = self.info.reader {
+            relocate_annotations(self.doc.as_ref(), &mut r.annotations, &mut self.chars_counts);
+            relocate_bookmarks(self.doc.as_ref(), r, &mut self.chars_counts);
+            r.layout = Some(layout);
+        }
+        self.links_page = None;
+        let position = self.current_page as f32 / self.pages_count as f32;
+        self.pages_count = self.doc.pages_count();
+        self.current_page = if let Some(ref anchor) = anchor {
+            page_at_anchor(self.doc.as_ref(), anchor, &mut self.chars_counts)
+        } else {
+            ((position * self.pages_count as f32) as usize).min(self.pages_count - 1)
+        };
+        self.anchor = anchor;
+        // The page numbers are meaningless in the new layout.
+        self.history.clear();
+        self.update_viewer(hub);
+        self.update_bottom_bar(hub);
+        self.update_history_icons(hub);
+    }
+
+    fn show_notification(&mut self, text: &str, hub: &Hub, fonts: &mut Fonts) {
+        self.close_notification(hub);
+        let notification = Notification::new(text.to_string(), fonts);
+        hub.send(Event::Render(*notification.rect(), UpdateMode::Gui)).unwrap();
+        self.children.push(Box::new(notification) as Box<View>);
+    }
+
+    fn close_notification(&mut self, hub: &Hub) {
+        if let Some(index) = locate_by_id(self, ViewId::Notification) {
+            hub.send(Event::Expose(*self.child(index).rect())).unwrap();
+            self.children.remove(index);
+        }
+    }
+
+    fn toggle_auto_crop_menu(&mut self, rect: Rectangle, enable: Option<bool>, hub: &Hub, fonts: &mut Fonts) {
+        if let Some(index) = locate_by_id(self, ViewId::AutoCropMenu) {
+            if let Some(true) = enable {
+                return;
+            }
+            hub.send(Event::Expose(*self.child(index).rect())).unwrap();
+            self.children.remove(index);
+        } else {
+            if let Some(false) = enable {
+                return;
+            }
+            let entries = &[EntryKind::Command("Crop This Page".to_string(),
+                                               EntryId::AutoCropPage),
+                            EntryKind::Command("Crop Odd and Even Pages".to_string(),
+                                               EntryId::AutoCropAll)];
+            let auto_crop_menu = Menu::new(rect, ViewId::AutoCropMenu, entries, fonts);
+            hub.send(Event::Render(*auto_crop_menu.rect(), UpdateMode::Gui)).unwrap();
+            self.children.push(Box::new(auto_crop_menu) as Box<View>);
+        }
+    }
+
+    fn auto_crop_page(&mut self, padding: f32, hub: &Hub) {
+        if let Some(margin) = auto_margin(self.doc.as_ref(), self.current_page, padding) {
+            self.crop_margins(&margin, hub);
+        }
+    }
+
+    // The margins of each parity are the medians of the margins of a few pages
+    // spread over the document. They replace the existing margins.
+    fn auto_crop_all(&mut self, padding: f32, hub: &Hub) {
+        let mut cropping_margins = FnvHashMap::default();
+
+        for parity in 0..2 {
+            let count = (self.pages_count + 1 - parity) / 2;
+            let samples = count.min(AUTO_CROP_SAMPLES);
+            let margins: Vec<Margin> = (0..samples).filter_map(|i| {
+                let index = parity + 2 * ((i + 1) * count / (samples + 1));
+                auto_margin(self.doc.as_ref(), index, padding)
+            }).collect();
+            if let Some(margin) = median_margin(&margins) {
+                cropping_margins.insert(parity, margin);
+            }
+        }
+
+        if let Some(ref mut r) = self.info.reader {
+            r.cropping_margins = cropping_margins;
+        }
+
+        self.cache.clear();
+
+        self.update_viewer(hub);
+    }
+
+    fn crop_margins(&mut self, margin: &Margin, hub: &Hub) {
+        if let Some(ref mut r) = self.info.reader {
+            r.cropping_margins.insert(self.current_page, margin.clone());
+        }
+        self.cache.clear();
+        self.update_viewer(hub);
+    }
+
+    pub fn info(&self) -> &Info {
+        &self.info
+    }
+
+    pub fn quit(&mut self, context: &mut Context) {
+        if let Some(ref s) = self.search {
+            s.running.store(false, Ordering::Relaxed);
+        }
+        if self.anchor.is_none() && self.doc.is_reflowable() {
+            self.anchor = Some(anchor_at(self.doc.as_ref(), self.current_page, &mut self.chars_counts));
+        }
+        if let Some(ref mut r) = self.info.reader {
+            r.current_page = self.current_page;
+            r.pages_count = self.pages_count;
+            r.anchor = self.anchor.clone();
+            r.finished = self.finished;
+        }
+        for i in &mut context.metadata {
+            if i.file.path == self.info.file.path {
+                *i = self.info.clone();
+                break;
+            }
+        }
+    }
+}
+
+impl View for Reader {
+    fn handle_event(&mut self, evt: &Event, hub: &Hub, _bus: &mut Bus, context: &mut Context) -> bool {
+        match *evt {
+            Event::Page(dir) => {
+                self.set_current_page(dir, hub);
+                true
+            },
+            Event::GoTo(index) => {
+                self.jump_to_page(index, hub);
+                if let Some(ref mut s) = self.search {
+                    if let Some(i) = s.results.iter().position(|m| m.page == index) {
+                        s.current = Some(i);
+                    }
+                }
+                self.update_results_bar(hub);
+                true
+            },
+            Event::History(dir) => {
+                self.go_through_history(dir, hub);
+                true
+            },
+            Event::Chapter(dir) => {
+                self.go_to_chapter(dir, hub);
+                true
+            },
+            Event::Scroll(delta) => {
+                self.scroll(delta, hub);
+                true
+            },
+            Event::Gesture(GestureEvent::Spread { ref target, strength, .. }) if self.rect.includes(target) => {
+                let factor = 1.0 + strength as f32 / self.rect.width() as f32;
+                self.zoom(factor, *target, hub);
+                true
+            },
+            Event::Gesture(GestureEvent::Pinch { ref target, strength, .. }) if self.rect.includes(target) => {
+                let factor = 1.0 + strength as f32 / self.rect.width() as f32;
+                self.zoom(1.0 / factor, *target, hub);
+                true
+            },
+            Event::ToggleNear(ViewId::ZoomMenu, rect) => {
+                self.toggle_zoom_menu(rect, None, hub, &mut context.fonts);
+                true
+            },
+            Event::Close(ViewId::ZoomMenu) => {
+                self.toggle_zoom_menu(Rectangle::default(), Some(false), hub, &mut context.fonts);
+                true
+            },
+            Event::Select(EntryId::FitToPage) => {
+                self.set_zoom_mode(ZoomMode::FitToPage, None, hub);
+                true
+            },
+            Event::Select(EntryId::FitToWidth) => {
+                self.set_zoom_mode(ZoomMode::FitToWidth, None, hub);
+                true
+            },
+            Event::Select(EntryId::ToggleRepage) => {
+                self.toggle_repage(hub);
+                true
+            },
+            Event::CropMargins(ref margin) => {
+                self.crop_margins(margin.as_ref(), hub);
+                true
+            },
+            Event::Toggle(ViewId::TopBottomBars) => {
+                self.toggle_bars(&mut context.fonts);
+                let update_mode = if locate::<TopBar>(self).is_some() {
+                    UpdateMode::Gui
+                } else {
+                    UpdateMode::Full
+                };
+                hub.send(Event::Render(self.rect, update_mode)).unwrap();
+                true
+            },
+            Event::Toggle(ViewId::GoToPage) => {
+                self.toggle_go_to_page(None, hub, &mut context.fonts);
+                true
+            },
+            Event::Toggle(ViewId::SearchBar) => {
+                if locate::<SearchBar>(self).is_some() {
+                    self.close_search(hub);
+                } else {
+                    self.show_search_bar(hub, &mut context.fonts);
+                }
+                true
+            },
+            Event::Close(ViewId::SearchBar) => {
+                self.close_search(hub);
+                true
+            },
+            Event::Submit(ViewId::SearchInput, ref text) => {
+                self.focus = None;
+                self.toggle_keyboard(false, hub);
+                self.search(text, hub, context);
+                true
+            },
+            Event::SearchResult(id, ref matches) => {
+                if self.search.as_ref().map_or(false, |s| s.id == id) {
+                    self.add_search_results(matches, hub);
+                }
+                true
+            },
+            Event::EndOfSearch(id) => {
+                if let Some(ref mut s) = self.search {
+                    if s.id == id {
+                        s.completed = true;
+                    }
+                }
+                self.update_results_bar(hub);
+                true
+            },
+            Event::SearchHit(dir) => {
+                self.go_to_search_hit(dir, hub);
+                true
+            },
+            Event::Show(ViewId::TableOfContents) => {
+                self.toggle_table_of_contents(true, hub);
+                true
+            },
+            Event::Close(ViewId::TableOfContents) => {
+                self.toggle_table_of_contents(false, hub);
+                true
+            },
+            Event::Show(ViewId::PageOverview) => {
+                self.toggle_page_overview(true, hub, context);
+                true
+            },
+            Event::Close(ViewId::PageOverview) => {
+                self.toggle_page_overview(false, hub, context);
+                true
+            },
+            Event::Show(id @ ViewId::SearchResults) |
+            Event::Show(id @ ViewId::Bookmarks) |
+            Event::Show(id @ ViewId::Annotations) => {
+                self.toggle_page_list(id, true, hub);
+                true
+            },
+            Event::Close(id @ ViewId::SearchResults) |
+            Event::Close(id @ ViewId::Bookmarks) |
+            Event::Close(id @ ViewId::Annotations) => {
+                self.toggle_page_list(id, false, hub);
+                true
+            },
+            // The viewer handles the holds in the left and right thirds.
+            // Holding a word in the middle third starts a text selection.
+            Event::Gesture(GestureEvent::HoldFinger(ref center)) if self.rect.includes(center) => {
+                if !self.start_selection(center, hub) {
+                    hub.send(Event::Select(EntryId::TakeScreenshot)).unwrap();
+                }
+                true
+            },
+            Event::Gesture(GestureEvent::Finger { status: FingerStatus::Motion, ref position, .. })
+                if self.selection.as_ref().map_or(false, |s| s.dragging) => {
+                self.extend_selection(position, hub);
+                true
+            },
+            Event::Gesture(GestureEvent::Finger { status: FingerStatus::Up, .. })
+                if self.selection.as_ref().map_or(false, |s| s.dragging) => {
+                self.end_selection(hub, context);
+                true
+            },
+            Event::Select(EntryId::Define) => {
+                self.define_selection(hub, context);
+                true
+            },
+            Event::Close(ViewId::Definition) => {
+                self.close_definition(hub);
+                true
+            },
+            Event::Select(EntryId::Highlight) => {
+                self.add_annotation("", hub);
+                true
+            },
+            Event::Select(EntryId::Annotate) => {
+                self.toggle_annotation_note(true, hub, &mut context.fonts);
+                true
+            },
+            Event::Close(ViewId::AnnotationMenu) => {
+                self.close_annotation_menu(hub);
+                true
+            },
+            Event::Submit(ViewId::AnnotationInput, ref text) => {
+                self.add_annotation(text, hub);
+                self.toggle_annotation_note(false, hub, &mut context.fonts);
+                true
+            },
+            Event::ToggleBookmark => {
+                self.toggle_bookmark(hub);
+                true
+            },
+            Event::Bookmark(dir) => {
+                self.go_to_bookmark(dir, hub);
+                true
+            },
+            Event::Slider(SliderId::FontSize, font_size, FingerStatus::Up) => {
+                self.set_font_size(font_size, hub);
+                true
+            },
+            Event::ToggleNear(ViewId::MainMenu, rect) => {
+                toggle_main_menu(self, rect, None, hub, context);
+                true
+            },
+            Event::Close(ViewId::MainMenu) => {
+                toggle_main_menu(self, Rectangle::default(), Some(false), hub, context);
+                true
+            },
+            Event::Close(ViewId::GoToPage) => {
+                self.toggle_go_to_page(Some(false), hub, &mut context.fonts);
+                true
+            },
+            Event::Show(ViewId::MarginCropper) => {
+                self.toggle_margin_cropper(true, hub, &mut context.fonts);
+                true
+            },
+            Event::ToggleNear(ViewId::AutoCropMenu, rect) => {
+                self.toggle_auto_crop_menu(rect, None, hub, &mut context.fonts);
+                true
+            },
+            Event::Close(ViewId::AutoCropMenu) => {
+                self.toggle_auto_crop_menu(Rectangle::default(), Some(false), hub, &mut context.fonts);
+                true
+            },
+            Event::Select(EntryId::AutoCropPage) => {
+                self.toggle_margin_cropper(false, hub, &mut context.fonts);
+                self.auto_crop_page(context.settings.auto_crop_padding, hub);
+                true
+            },
+            Event::Select(EntryId::AutoCropAll) => {
+                self.toggle_margin_cropper(false, hub, &mut context.fonts);
+                self.auto_crop_all(context.settings.auto_crop_padding, hub);
+                true
+            },
+            Event::Close(ViewId::MarginCropper) => {
+                self.toggle_margin_cropper(false, hub, &mut context.fonts);
+                true
+            },
+            Event::Notify(ref text) => {
+                self.show_notification(text, hub, &mut context.fonts);
+                true
+            },
+            Event::Close(ViewId::Notification) => {
+                self.close_notification(hub);
+                true
+            },
+            Event::Select(EntryId::Quit) | Event::Back => {
+                self.quit(context);
+                false
+            },
+            Event::Focus(v) => {
+                self.focus = v;
+                self.toggle_keyboard(true, hub);
+                false
+            },
+            _ => false,
+        }
+    }
+
+    fn render(&self, _fb: &mut Framebuffer, _fonts: &mut Fonts) {
+    }
+
+    fn rect(&self) -> &Rectangle {
+        &self.rect
+    }
+
+    fn rect_mut(&mut self) -> &mut Rectangle {
+        &mut self.rect
     }
 
     fn children(&self) -> &Vec<Box<View>> {
@@ -482,12 +1813,125 @@ impl View for Reader {
     }
 }
 
-fn build_pixmap(rect: &Rectangle, doc: &Document, index: usize, margin: &Margin) -> Pixmap {
+// Returns the fit to page and fit to width scales.
+fn annotation_rects(annotations: &[Annotation], index: usize) -> Vec<Rectangle> {
+    annotations.iter().filter(|a| a.page == index)
+               .flat_map(|a| a.rects.clone()).collect()
+}
+
+// Places the annotations of reflowable documents in the current layout.
+// The words that don't fit on the page of the first word aren't highlighted.
+fn relocate_annotations(doc: &Document, annotations: &mut [Annotation], counts: &mut CharsCounts) {
+    for a in annotations {
+        if let Some(ref anchor) = a.anchor {
+            let (index, words) = words_at_anchor(doc, anchor, counts);
+            a.page = index;
+            a.rects = words.into_iter().take(a.text.split_whitespace().count())
+                           .map(|w| w.rect).collect();
+        }
+    }
+}
+
+// Gives an anchor to the bookmarks that don't have one, in the current layout.
+fn anchor_bookmarks(doc: &Document, r: &mut ReaderInfo, counts: &mut CharsCounts) {
+    for &page in &r.bookmarks {
+        r.bookmark_anchors.entry(page).or_insert_with(|| anchor_at(doc, page, counts));
+    }
+}
+
+// Moves the bookmarks of reflowable documents to the pages of their anchors in
+// the current layout. The bookmarks without anchors are left as they are.
+fn relocate_bookmarks(doc: &Document, r: &mut ReaderInfo, counts: &mut CharsCounts) {
+    let anchors = mem::replace(&mut r.bookmark_anchors, BTreeMap::new());
+    for page in anchors.keys() {
+        r.bookmarks.remove(page);
+    }
+    for (_, anchor) in anchors {
+        let page = page_at_anchor(doc, &anchor, counts);
+        r.bookmarks.insert(page);
+        r.bookmark_anchors.entry(page).or_insert(anchor);
+    }
+}
+
+fn fit_scales(rect: &Rectangle, doc: &Document, index: usize, margin: &Margin) -> (f32, f32) {
     let (width, height) = doc.dims(index).unwrap();
     let p_width = (1.0 - (margin.left + margin.right)) * width;
     let p_height = (1.0 - (margin.top + margin.bottom)) * height;
     let w_ratio = rect.width() as f32 / p_width;
     let h_ratio = rect.height() as f32 / p_height;
-    let scale = w_ratio.min(h_ratio);
-    doc.pixmap(index, scale).unwrap()
+    (w_ratio.min(h_ratio), w_ratio)
+}
+
+fn zoom_scale(rect: &Rectangle, doc: &Document, index: usize, margin: &Margin, zoom_mode: ZoomMode) -> f32 {
+    let (page_scale, width_scale) = fit_scales(rect, doc, index, margin);
+    match zoom_mode {
+        ZoomMode::FitToPage => page_scale,
+        ZoomMode::FitToWidth => width_scale,
+        ZoomMode::Custom(factor) => factor * page_scale,
+    }
+}
+
+fn build_pixmap(rect: &Rectangle, doc: &Document, index: usize, margin: &Margin, zoom_mode: ZoomMode) -> (Pixmap, f32) {
+    let scale = zoom_scale(rect, doc, index, margin, zoom_mode);
+    (doc.pixmap(index, scale).unwrap(), scale)
+}
+
+// Computes the margins around the content of a page.
+fn auto_margin(doc: &Document, index: usize, padding: f32) -> Option<Margin> {
+    let (width, _) = doc.dims(index)?;
+    let pixmap = doc.pixmap(index, ANALYSIS_WIDTH / width)?;
+    let bounds = content_bounds(&pixmap)?;
+    let (w, h) = (pixmap.width as f32, pixmap.height as f32);
+    Some(Margin::new((bounds.min.y as f32 / h - padding).max(0.0),
+                     (1.0 - bounds.max.x as f32 / w - padding).max(0.0),
+                     (1.0 - bounds.max.y as f32 / h - padding).max(0.0),
+                     (bounds.min.x as f32 / w - padding).max(0.0)))
+}
+
+fn median_margin(margins: &[Margin]) -> Option<Margin> {
+    if margins.is_empty() {
+        return None;
+    }
+    let median = |f: &Fn(&Margin) -> f32| {
+        let mut values: Vec<f32> = margins.iter().map(|m| f(m)).collect();
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        values[values.len() / 2]
+    };
+    Some(Margin::new(median(&|m| m.top), median(&|m| m.right),
+                     median(&|m| m.bottom), median(&|m| m.left)))
+}
+
+// Converts the reading regions of a page into margins.
+fn region_margins(doc: &Document, index: usize) -> Vec<Margin> {
+    let (width, _) = match doc.dims(index) {
+        Some(dims) => dims,
+        None => return Vec::new(),
+    };
+    let pixmap = match doc.pixmap(index, ANALYSIS_WIDTH / width) {
+        Some(pixmap) => pixmap,
+        None => return Vec::new(),
+    };
+    let (w, h) = (pixmap.width as f32, pixmap.height as f32);
+    let padding = REGION_PADDING * w;
+    reading_regions(&pixmap).into_iter().map(|r| {
+        Margin::new(((r.min.y as f32 - padding) / h).max(0.0),
+                    (1.0 - (r.max.x as f32 + padding) / w).max(0.0),
+                    (1.0 - (r.max.y as f32 + padding) / h).max(0.0),
+                    ((r.min.x as f32 - padding) / w).max(0.0))
+    }).collect()
+}
+
+// The region of the pixmap that remains once the margins are cropped.
+fn crop_frame(pixmap: &Pixmap, margin: &Margin) -> Rectangle {
+    rect![(margin.left * pixmap.width as f32).ceil() as i32,
+          (margin.top * pixmap.height as f32).ceil() as i32,
+          ((1.0 - margin.right) * pixmap.width as f32).floor() as i32,
+          ((1.0 - margin.bottom) * pixmap.height as f32).floor() as i32]
+}
+
+fn visible_frame(rect: &Rectangle, crop: &Rectangle, scroll: &Point) -> Rectangle {
+    let width = (rect.width() as i32).min(crop.width() as i32);
+    let height = (rect.height() as i32).min(crop.height() as i32);
+    let min = crop.min + *scroll;
+    rect![min, min + pt!(width, height)]
 }
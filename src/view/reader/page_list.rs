@@ -0,0 +1,246 @@
+use device::{CURRENT_DEVICE, BAR_SIZES};
+use framebuffer::{Framebuffer, UpdateMode};
+use view::{View, Event, Hub, Bus, ViewId, Align, THICKNESS_MEDIUM};
+use view::icon::Icon;
+use view::label::Label;
+use view::filler::Filler;
+use gesture::GestureEvent;
+use font::{Fonts, font_from_style, NORMAL_STYLE};
+use geom::{Rectangle, Dir, CycleDir, halves};
+use color::{BLACK, WHITE, TEXT_NORMAL, SEPARATOR_NORMAL};
+use unit::scale_by_dpi;
+use app::Context;
+
+#[derive(Debug, Clone)]
+pub struct PageListEntry {
+    pub page: usize,
+    pub text: String,
+}
+
+// A full screen list of entries pointing to pages of the current document.
+// Tapping an entry sends the corresponding `GoTo` event.
+pub struct PageList {
+    rect: Rectangle,
+    children: Vec<Box<View>>,
+    id: ViewId,
+    entries: Vec<PageListEntry>,
+    entries_rect: Rectangle,
+    line_height: i32,
+    current_page: usize,
+}
+
+impl PageList {
+    pub fn new(rect: Rectangle, id: ViewId, title: String, entries: Vec<PageListEntry>) -> PageList {
+        let mut children = Vec::new();
+        let dpi = CURRENT_DEVICE.dpi;
+        let (_, height) = CURRENT_DEVICE.screen_dims();
+        let &(small_height, _) = BAR_SIZES.get(&(height, dpi)).unwrap();
+        let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
+        let (small_thickness, big_thickness) = halves(thickness);
+        let side = small_height as i32 - small_thickness;
+
+        let title_label = Label::new(rect![rect.min.x, rect.min.y,
+                                           rect.max.x - side, rect.min.y + side],
+                                     title,
+                                     Align::Center);
+        children.push(Box::new(title_label) as Box<View>);
+
+        let close_icon = Icon::new("close",
+                                   rect![rect.max.x - side, rect.min.y,
+                                         rect.max.x, rect.min.y + side],
+                                   WHITE,
+                                   Align::Center,
+                                   Event::Close(id));
+        children.push(Box::new(close_icon) as Box<View>);
+
+        let separator = Filler::new(rect![rect.min.x, rect.min.y + side,
+                                          rect.max.x, rect.min.y + side + thickness],
+                                    BLACK);
+        children.push(Box::new(separator) as Box<View>);
+
+        let separator = Filler::new(rect![rect.min.x, rect.max.y - small_height as i32 - small_thickness,
+                                          rect.max.x, rect.max.y - small_height as i32 + big_thickness],
+                                    BLACK);
+        children.push(Box::new(separator) as Box<View>);
+
+        let side = small_height as i32 - big_thickness;
+        let bottom_rect = rect![rect.min.x, rect.max.y - side,
+                                rect.max.x, rect.max.y];
+
+        let prev_icon = Icon::new("arrow-left",
+                                  rect![bottom_rect.min, bottom_rect.min + side],
+                                  WHITE,
+                                  Align::Center,
+                                  Event::Page(CycleDir::Previous));
+        children.push(Box::new(prev_icon) as Box<View>);
+
+        let entries_rect = rect![rect.min.x, rect.min.y + small_height as i32 + big_thickness,
+                                 rect.max.x, rect.max.y - small_height as i32 - small_thickness];
+        let line_height = small_height as i32;
+        let max_lines = ((entries_rect.height() as i32 / line_height) as usize).max(1);
+
+        let page_label = Label::new(rect![bottom_rect.min.x + side, bottom_rect.min.y,
+                                          bottom_rect.max.x - side, bottom_rect.max.y],
+                                    page_label_text(0, pages_count(entries.len(), max_lines)),
+                                    Align::Center);
+        children.push(Box::new(page_label) as Box<View>);
+
+        let next_icon = Icon::new("arrow-right",
+                                  rect![bottom_rect.max - side, bottom_rect.max],
+                                  WHITE,
+                                  Align::Center,
+                                  Event::Page(CycleDir::Next));
+        children.push(Box::new(next_icon) as Box<View>);
+
+        PageList {
+            rect,
+            children,
+            id,
+            entries,
+            entries_rect,
+            line_height,
+            current_page: 0,
+        }
+    }
+
+    pub fn update(&mut self, entries: Vec<PageListEntry>, hub: &Hub) {
+        self.entries = entries;
+        self.current_page = self.current_page.min(self.pages_count() - 1);
+        self.update_page_label(hub);
+        hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+    }
+
+    fn max_lines(&self) -> usize {
+        ((self.entries_rect.height() as i32 / self.line_height) as usize).max(1)
+    }
+
+    fn pages_count(&self) -> usize {
+        pages_count(self.entries.len(), self.max_lines())
+    }
+
+    fn update_page_label(&mut self, hub: &Hub) {
+        let text = page_label_text(self.current_page, self.pages_count());
+        self.child_mut(5).downcast_mut::<Label>().unwrap().update(text, hub);
+    }
+
+    fn go_to_neighbor(&mut self, dir: CycleDir, hub: &Hub) {
+        let index = match dir {
+            CycleDir::Next => self.current_page + 1,
+            CycleDir::Previous => self.current_page.wrapping_sub(1),
+        };
+        self.set_current_page(index, hub);
+    }
+
+    fn set_current_page(&mut self, index: usize, hub: &Hub) {
+        if index == self.current_page || index >= self.pages_count() {
+            return;
+        }
+        self.current_page = index;
+        self.update_page_label(hub);
+        hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+    }
+}
+
+impl View for PageList {
+    fn handle_event(&mut self, evt: &Event, hub: &Hub, bus: &mut Bus, _context: &mut Context) -> bool {
+        match *evt {
+            Event::Page(dir) => {
+                self.go_to_neighbor(dir, hub);
+                true
+            },
+            Event::Chapter(dir) => {
+                let index = match dir {
+                    CycleDir::Next => self.pages_count() - 1,
+                    CycleDir::Previous => 0,
+                };
+                self.set_current_page(index, hub);
+                true
+            },
+            Event::Gesture(GestureEvent::Swipe { dir, ref start, .. }) if self.entries_rect.includes(start) => {
+                match dir {
+                    Dir::West => self.go_to_neighbor(CycleDir::Next, hub),
+                    Dir::East => self.go_to_neighbor(CycleDir::Previous, hub),
+                    _ => (),
+                }
+                true
+            },
+            Event::Gesture(GestureEvent::Tap { ref center, .. }) if self.entries_rect.includes(center) => {
+                let line = ((center.y - self.entries_rect.min.y) / self.line_height) as usize;
+                let index = self.current_page * self.max_lines() + line;
+                if line < self.max_lines() && index < self.entries.len() {
+                    bus.push_back(Event::GoTo(self.entries[index].page));
+                    bus.push_back(Event::Close(self.id));
+                }
+                true
+            },
+            Event::Gesture(GestureEvent::Tap { ref center, .. }) |
+            Event::Gesture(GestureEvent::HoldFinger(ref center)) if self.rect.includes(center) => true,
+            Event::Gesture(GestureEvent::Swipe { ref start, .. }) if self.rect.includes(start) => true,
+            _ => false,
+        }
+    }
+
+    fn render(&self, fb: &mut Framebuffer, fonts: &mut Fonts) {
+        let dpi = CURRENT_DEVICE.dpi;
+        let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
+        let font = font_from_style(fonts, &NORMAL_STYLE, dpi);
+        let x_height = font.x_heights.0 as i32;
+        let padding = font.em() as i32;
+
+        fb.draw_rectangle(&self.entries_rect, TEXT_NORMAL[0]);
+
+        let max_lines = self.max_lines();
+        let start = self.current_page * max_lines;
+        let end = (start + max_lines).min(self.entries.len());
+
+        for (i, entry) in self.entries[start..end].iter().enumerate() {
+            let y_min = self.entries_rect.min.y + i as i32 * self.line_height;
+            let dy = (self.line_height - x_height) / 2;
+            let baseline = y_min + self.line_height - dy;
+
+            let page_plan = font.plan(&format!("{}", entry.page + 1), None, None);
+            let pt = pt!(self.entries_rect.max.x - padding - page_plan.width as i32, baseline);
+            font.render(fb, TEXT_NORMAL[1], &page_plan, &pt);
+
+            let max_width = self.entries_rect.width() as i32 - 4 * padding - page_plan.width as i32;
+            let plan = font.plan(&entry.text, Some(max_width.max(0) as u32), None);
+            let pt = pt!(self.entries_rect.min.x + padding, baseline);
+            font.render(fb, TEXT_NORMAL[1], &plan, &pt);
+
+            if i < max_lines - 1 {
+                let y_max = y_min + self.line_height;
+                fb.draw_rectangle(&rect![self.entries_rect.min.x + padding, y_max - thickness / 2,
+                                         self.entries_rect.max.x - padding, y_max - thickness / 2 + 1],
+                                  SEPARATOR_NORMAL);
+            }
+        }
+    }
+
+    fn id(&self) -> Option<ViewId> {
+        Some(self.id)
+    }
+
+    fn rect(&self) -> &Rectangle {
+        &self.rect
+    }
+
+    fn rect_mut(&mut self) -> &mut Rectangle {
+        &mut self.rect
+    }
+
+    fn children(&self) -> &Vec<Box<View>> {
+        &self.children
+    }
+
+    fn children_mut(&mut self) -> &mut Vec<Box<View>> {
+        &mut self.children
+    }
+}
+
+fn pages_count(entries_count: usize, max_lines: usize) -> usize {
+    ((entries_count + max_lines - 1) / max_lines).max(1)
+}
+
+fn page_label_text(current_page: usize, pages_count: usize) -> String {
+    format!("Page {} of {}", current_page + 1, pages_count)
+}
@@ -0,0 +1,340 @@
+use std::thread;
+use std::path::PathBuf;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Sender};
+use fnv::FnvHashMap;
+use device::{CURRENT_DEVICE, BAR_SIZES};
+use framebuffer::{Framebuffer, UpdateMode, Pixmap};
+use view::{View, Event, Hub, Bus, ViewId, Align, THICKNESS_MEDIUM};
+use view::icon::Icon;
+use view::label::Label;
+use view::filler::Filler;
+use gesture::GestureEvent;
+use document::open;
+use document::thumbnail::{cached_thumbnail, render_thumbnail};
+use font::{Fonts, font_from_style, NORMAL_STYLE};
+use geom::{Rectangle, BorderSpec, Dir, CycleDir, halves};
+use color::{BLACK, WHITE, TEXT_NORMAL, SEPARATOR_NORMAL};
+use unit::scale_by_dpi;
+use app::Context;
+
+// Approximate width of a cell of the grid, in pixels at 300 DPI.
+const CELL_WIDTH: f32 = 330.0;
+const THUMBNAIL_PADDING: f32 = 24.0;
+const LABEL_HEIGHT: f32 = 60.0;
+
+// A full screen grid of page thumbnails.
+// Tapping a thumbnail sends the corresponding `GoTo` event.
+// The thumbnails are rendered, or loaded from the disk, by a worker thread.
+// Reflowable documents are laid out as they are in the reader, and the thumbnails
+// of their pages, which depend on the layout, aren't saved.
+pub struct PageOverview {
+    rect: Rectangle,
+    children: Vec<Box<View>>,
+    grid_rect: Rectangle,
+    columns: usize,
+    rows: usize,
+    pages_count: usize,
+    current_page: usize,
+    reader_page: usize,
+    thumbnails: Arc<Mutex<FnvHashMap<usize, Pixmap>>>,
+    sender: Sender<Vec<(usize, Rectangle)>>,
+}
+
+impl PageOverview {
+    pub fn new(rect: Rectangle, title: String, path: PathBuf, thumbnails_dir: PathBuf,
+               layout: Option<(f32, f32, f32)>, pages_count: usize, reader_page: usize,
+               hub: &Hub) -> PageOverview {
+        let mut children = Vec::new();
+        let dpi = CURRENT_DEVICE.dpi;
+        let (_, height) = CURRENT_DEVICE.screen_dims();
+        let &(small_height, _) = BAR_SIZES.get(&(height, dpi)).unwrap();
+        let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
+        let (small_thickness, big_thickness) = halves(thickness);
+        let side = small_height as i32 - small_thickness;
+
+        let title_label = Label::new(rect![rect.min.x, rect.min.y,
+                                           rect.max.x - side, rect.min.y + side],
+                                     title,
+                                     Align::Center);
+        children.push(Box::new(title_label) as Box<View>);
+
+        let close_icon = Icon::new("close",
+                                   rect![rect.max.x - side, rect.min.y,
+                                         rect.max.x, rect.min.y + side],
+                                   WHITE,
+                                   Align::Center,
+                                   Event::Close(ViewId::PageOverview));
+        children.push(Box::new(close_icon) as Box<View>);
+
+        let separator = Filler::new(rect![rect.min.x, rect.min.y + side,
+                                          rect.max.x, rect.min.y + side + thickness],
+                                    BLACK);
+        children.push(Box::new(separator) as Box<View>);
+
+        let separator = Filler::new(rect![rect.min.x, rect.max.y - small_height as i32 - small_thickness,
+                                          rect.max.x, rect.max.y - small_height as i32 + big_thickness],
+                                    BLACK);
+        children.push(Box::new(separator) as Box<View>);
+
+        let side = small_height as i32 - big_thickness;
+        let bottom_rect = rect![rect.min.x, rect.max.y - side,
+                                rect.max.x, rect.max.y];
+
+        let prev_icon = Icon::new("arrow-left",
+                                  rect![bottom_rect.min, bottom_rect.min + side],
+                                  WHITE,
+                                  Align::Center,
+                                  Event::Page(CycleDir::Previous));
+        children.push(Box::new(prev_icon) as Box<View>);
+
+        let grid_rect = rect![rect.min.x, rect.min.y + small_height as i32 + big_thickness,
+                              rect.max.x, rect.max.y - small_height as i32 - small_thickness];
+        let cell_width = scale_by_dpi(CELL_WIDTH, dpi) as i32;
+        let columns = ((grid_rect.width() as i32 / cell_width) as usize).max(1);
+        // The cells can hold a portrait page and its number.
+        let cell_height = 4 * (grid_rect.width() as i32 / columns as i32) / 3 +
+                          scale_by_dpi(LABEL_HEIGHT, dpi) as i32;
+        let rows = ((grid_rect.height() as i32 / cell_height) as usize).max(1);
+        let current_page = reader_page / (columns * rows);
+
+        let page_label = Label::new(rect![bottom_rect.min.x + side, bottom_rect.min.y,
+                                          bottom_rect.max.x - side, bottom_rect.max.y],
+                                    page_label_text(current_page, grid_pages_count(pages_count, columns * rows)),
+                                    Align::Center);
+        children.push(Box::new(page_label) as Box<View>);
+
+        let next_icon = Icon::new("arrow-right",
+                                  rect![bottom_rect.max - side, bottom_rect.max],
+                                  WHITE,
+                                  Align::Center,
+                                  Event::Page(CycleDir::Next));
+        children.push(Box::new(next_icon) as Box<View>);
+
+        let thumbnails = Arc::new(Mutex::new(FnvHashMap::default()));
+        let (sender, receiver) = mpsc::channel::<Vec<(usize, Rectangle)>>();
+        let thumbnails2 = thumbnails.clone();
+        let hub2 = hub.clone();
+
+        thread::spawn(move || {
+            let mut doc = match open(&path) {
+                Some(doc) => doc,
+                None => return,
+            };
+            if let Some((width, height, font_size)) = layout {
+                doc.layout(width, height, font_size);
+            }
+            let mut pending: VecDeque<(usize, Rectangle)> = VecDeque::new();
+            loop {
+                if pending.is_empty() {
+                    match receiver.recv() {
+                        Ok(cells) => pending = cells.into_iter().collect(),
+                        Err(_) => break,
+                    }
+                }
+                // Only the cells of the last grid page shown matter.
+                while let Ok(cells) = receiver.try_recv() {
+                    pending = cells.into_iter().collect();
+                }
+                if let Some((index, rect)) = pending.pop_front() {
+                    if thumbnails2.lock().unwrap().contains_key(&index) {
+                        continue;
+                    }
+                    let (width, height) = thumbnail_dims(&rect);
+                    let pixmap = if layout.is_some() {
+                        render_thumbnail(doc.as_ref(), index, width, height)
+                    } else {
+                        cached_thumbnail(doc.as_ref(), &thumbnails_dir, index, width, height)
+                    };
+                    if let Some(pixmap) = pixmap {
+                        thumbnails2.lock().unwrap().insert(index, pixmap);
+                        hub2.send(Event::Render(rect, UpdateMode::Gui)).unwrap();
+                    }
+                }
+            }
+        });
+
+        let page_overview = PageOverview {
+            rect,
+            children,
+            grid_rect,
+            columns,
+            rows,
+            pages_count,
+            current_page,
+            reader_page,
+            thumbnails,
+            sender,
+        };
+
+        page_overview.request_thumbnails();
+        page_overview
+    }
+
+    fn cells_per_page(&self) -> usize {
+        self.columns * self.rows
+    }
+
+    fn pages_count(&self) -> usize {
+        grid_pages_count(self.pages_count, self.cells_per_page())
+    }
+
+    fn cell_rect(&self, position: usize) -> Rectangle {
+        let width = self.grid_rect.width() as i32 / self.columns as i32;
+        let height = self.grid_rect.height() as i32 / self.rows as i32;
+        let x = self.grid_rect.min.x + (position % self.columns) as i32 * width;
+        let y = self.grid_rect.min.y + (position / self.columns) as i32 * height;
+        rect![x, y, x + width, y + height]
+    }
+
+    fn visible_pages(&self) -> (usize, usize) {
+        let start = self.current_page * self.cells_per_page();
+        let end = (start + self.cells_per_page()).min(self.pages_count);
+        (start, end)
+    }
+
+    fn request_thumbnails(&self) {
+        let (start, end) = self.visible_pages();
+        let cells = (start..end).map(|index| (index, self.cell_rect(index - start))).collect();
+        self.sender.send(cells).ok();
+    }
+
+    fn update_page_label(&mut self, hub: &Hub) {
+        let text = page_label_text(self.current_page, self.pages_count());
+        self.child_mut(5).downcast_mut::<Label>().unwrap().update(text, hub);
+    }
+
+    fn go_to_neighbor(&mut self, dir: CycleDir, hub: &Hub) {
+        let index = match dir {
+            CycleDir::Next => self.current_page + 1,
+            CycleDir::Previous => self.current_page.wrapping_sub(1),
+        };
+        self.set_current_page(index, hub);
+    }
+
+    fn set_current_page(&mut self, index: usize, hub: &Hub) {
+        if index == self.current_page || index >= self.pages_count() {
+            return;
+        }
+        self.current_page = index;
+        // Keep the memory usage bounded.
+        self.thumbnails.lock().unwrap().clear();
+        self.request_thumbnails();
+        self.update_page_label(hub);
+        hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+    }
+}
+
+impl View for PageOverview {
+    fn handle_event(&mut self, evt: &Event, hub: &Hub, bus: &mut Bus, _context: &mut Context) -> bool {
+        match *evt {
+            Event::Page(dir) => {
+                self.go_to_neighbor(dir, hub);
+                true
+            },
+            Event::Chapter(dir) => {
+                let index = match dir {
+                    CycleDir::Next => self.pages_count() - 1,
+                    CycleDir::Previous => 0,
+                };
+                self.set_current_page(index, hub);
+                true
+            },
+            Event::Gesture(GestureEvent::Swipe { dir, ref start, .. }) if self.grid_rect.includes(start) => {
+                match dir {
+                    Dir::West => self.go_to_neighbor(CycleDir::Next, hub),
+                    Dir::East => self.go_to_neighbor(CycleDir::Previous, hub),
+                    _ => (),
+                }
+                true
+            },
+            Event::Gesture(GestureEvent::Tap { ref center, .. }) if self.grid_rect.includes(center) => {
+                let (start, end) = self.visible_pages();
+                if let Some(index) = (start..end).find(|&i| self.cell_rect(i - start).includes(center)) {
+                    bus.push_back(Event::GoTo(index));
+                    bus.push_back(Event::Close(ViewId::PageOverview));
+                }
+                true
+            },
+            Event::Gesture(GestureEvent::Tap { ref center, .. }) |
+            Event::Gesture(GestureEvent::HoldFinger(ref center)) if self.rect.includes(center) => true,
+            Event::Gesture(GestureEvent::Swipe { ref start, .. }) if self.rect.includes(start) => true,
+            _ => false,
+        }
+    }
+
+    fn render(&self, fb: &mut Framebuffer, fonts: &mut Fonts) {
+        let dpi = CURRENT_DEVICE.dpi;
+        let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as u16;
+        let font = font_from_style(fonts, &NORMAL_STYLE, dpi);
+        let padding = scale_by_dpi(THUMBNAIL_PADDING, dpi) as i32;
+
+        fb.draw_rectangle(&self.grid_rect, TEXT_NORMAL[0]);
+
+        let thumbnails = self.thumbnails.lock().unwrap();
+        let (start, end) = self.visible_pages();
+
+        for index in start..end {
+            let cell = self.cell_rect(index - start);
+            let (max_width, max_height) = thumbnail_dims(&cell);
+            // Outline the whole box until the thumbnail is available.
+            let (width, height) = thumbnails.get(&index).map_or((max_width as i32, max_height as i32),
+                                                                 |p| (p.width, p.height));
+            let pt = pt!(cell.min.x + (cell.width() as i32 - width) / 2,
+                         cell.min.y + padding + (max_height as i32 - height) / 2);
+            let frame = rect![pt, pt + pt!(width, height)];
+
+            if let Some(pixmap) = thumbnails.get(&index) {
+                fb.draw_pixmap(pixmap, &pt);
+            }
+
+            let color = if index == self.reader_page { BLACK } else { SEPARATOR_NORMAL };
+            let border = if index == self.reader_page { 2 * thickness } else { thickness / 2 };
+            fb.draw_rectangle_outline(&rect![frame.min - border as i32, frame.max + border as i32],
+                                      &BorderSpec { thickness: border.max(1), color });
+
+            let plan = font.plan(&format!("{}", index + 1), None, None);
+            let pt = pt!(cell.min.x + (cell.width() as i32 - plan.width as i32) / 2,
+                         cell.max.y - padding);
+            font.render(fb, TEXT_NORMAL[1], &plan, &pt);
+        }
+    }
+
+    fn id(&self) -> Option<ViewId> {
+        Some(ViewId::PageOverview)
+    }
+
+    fn rect(&self) -> &Rectangle {
+        &self.rect
+    }
+
+    fn rect_mut(&mut self) -> &mut Rectangle {
+        &mut self.rect
+    }
+
+    fn children(&self) -> &Vec<Box<View>> {
+        &self.children
+    }
+
+    fn children_mut(&mut self) -> &mut Vec<Box<View>> {
+        &mut self.children
+    }
+}
+
+// The box in which the thumbnail of a cell fits: the bottom of the cell holds the page number.
+fn thumbnail_dims(cell: &Rectangle) -> (u32, u32) {
+    let dpi = CURRENT_DEVICE.dpi;
+    let padding = scale_by_dpi(THUMBNAIL_PADDING, dpi) as u32;
+    let label_height = scale_by_dpi(LABEL_HEIGHT, dpi) as u32;
+    (cell.width().saturating_sub(2 * padding),
+     cell.height().saturating_sub(2 * padding + label_height))
+}
+
+fn grid_pages_count(count: usize, cells_per_page: usize) -> usize {
+    ((count + cells_per_page - 1) / cells_per_page).max(1)
+}
+
+fn page_label_text(current_page: usize, pages_count: usize) -> String {
+    format!("Page {} of {}", current_page + 1, pages_count)
+}
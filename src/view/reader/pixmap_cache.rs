@@ -0,0 +1,147 @@
+use std::thread;
+use std::path::PathBuf;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Sender};
+use fnv::FnvHashMap;
+use framebuffer::Pixmap;
+use document::open;
+use metadata::Margin;
+
+const CACHE_CAPACITY: usize = 6;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct PixmapKey {
+    index: usize,
+    scale: u32,
+    margin: [u32; 4],
+}
+
+impl PixmapKey {
+    pub fn new(index: usize, scale: f32, margin: &Margin) -> PixmapKey {
+        PixmapKey {
+            index,
+            scale: scale.to_bits(),
+            margin: [margin.top.to_bits(), margin.right.to_bits(),
+                     margin.bottom.to_bits(), margin.left.to_bits()],
+        }
+    }
+
+    fn scale(&self) -> f32 {
+        f32::from_bits(self.scale)
+    }
+}
+
+#[derive(Default)]
+struct Entries {
+    pixmaps: FnvHashMap<PixmapKey, Arc<Pixmap>>,
+    // From the least to the most recently used.
+    order: VecDeque<PixmapKey>,
+}
+
+impl Entries {
+    fn get(&mut self, key: &PixmapKey) -> Option<Arc<Pixmap>> {
+        let pixmap = self.pixmaps.get(key).cloned();
+        if pixmap.is_some() {
+            self.touch(key);
+        }
+        pixmap
+    }
+
+    fn insert(&mut self, key: PixmapKey, pixmap: Arc<Pixmap>) {
+        self.pixmaps.insert(key, pixmap);
+        self.touch(&key);
+        while self.order.len() > CACHE_CAPACITY {
+            if let Some(k) = self.order.pop_front() {
+                self.pixmaps.remove(&k);
+            }
+        }
+    }
+
+    fn touch(&mut self, key: &PixmapKey) {
+        if let Some(index) = self.order.iter().position(|k| k == key) {
+            self.order.remove(index);
+        }
+        self.order.push_back(*key);
+    }
+
+    fn clear(&mut self) {
+        self.pixmaps.clear();
+        self.order.clear();
+    }
+}
+
+// A least recently used cache of rendered pages.
+// The pages requested through `prefetch` are rendered by a worker thread
+// that owns its own instance of the document.
+pub struct PixmapCache {
+    path: PathBuf,
+    entries: Arc<Mutex<Entries>>,
+    sender: Sender<Vec<PixmapKey>>,
+}
+
+impl PixmapCache {
+    pub fn new(path: PathBuf, width: f32, height: f32, font_size: f32) -> PixmapCache {
+        let entries = Arc::new(Mutex::new(Entries::default()));
+        let (sender, receiver) = mpsc::channel::<Vec<PixmapKey>>();
+        let entries2 = entries.clone();
+        let path2 = path.clone();
+
+        thread::spawn(move || {
+            let mut doc = match open(&path2) {
+                Some(doc) => doc,
+                None => return,
+            };
+            doc.layout(width, height, font_size);
+            let mut pending: VecDeque<PixmapKey> = VecDeque::new();
+            loop {
+                if pending.is_empty() {
+                    match receiver.recv() {
+                        Ok(keys) => pending = keys.into_iter().collect(),
+                        Err(_) => break,
+                    }
+                }
+                // Newer requests supersede the pending ones.
+                while let Ok(keys) = receiver.try_recv() {
+                    pending = keys.into_iter().collect();
+                }
+                if let Some(key) = pending.pop_front() {
+                    if entries2.lock().unwrap().pixmaps.contains_key(&key) {
+                        continue;
+                    }
+                    if let Some(pixmap) = doc.pixmap(key.index, key.scale()) {
+                        entries2.lock().unwrap().insert(key, Arc::new(pixmap));
+                    }
+                }
+            }
+        });
+
+        PixmapCache {
+            path,
+            entries,
+            sender,
+        }
+    }
+
+    pub fn get(&self, key: &PixmapKey) -> Option<Arc<Pixmap>> {
+        self.entries.lock().unwrap().get(key)
+    }
+
+    pub fn insert(&self, key: PixmapKey, pixmap: Arc<Pixmap>) {
+        self.entries.lock().unwrap().insert(key, pixmap);
+    }
+
+    pub fn prefetch(&self, keys: Vec<PixmapKey>) {
+        self.sender.send(keys).ok();
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    // The pages of reflowable documents change with the layout:
+    // start over with a new worker.
+    pub fn relayout(&mut self, width: f32, height: f32, font_size: f32) {
+        *self = PixmapCache::new(self.path.clone(), width, height, font_size);
+    }
+}
@@ -0,0 +1,104 @@
+use framebuffer::Framebuffer;
+use view::{View, Event, Hub, Bus, ViewId, Align};
+use view::icon::Icon;
+use view::label::Label;
+use gesture::GestureEvent;
+use geom::{Rectangle, CycleDir};
+use color::WHITE;
+use font::Fonts;
+use app::Context;
+
+#[derive(Debug)]
+pub struct ResultsBar {
+    rect: Rectangle,
+    children: Vec<Box<View>>,
+}
+
+impl ResultsBar {
+    pub fn new(rect: Rectangle) -> ResultsBar {
+        let mut children = Vec::new();
+        let side = rect.height() as i32;
+
+        let prev_icon = Icon::new("arrow-left",
+                                  rect![rect.min, rect.min + side],
+                                  WHITE,
+                                  Align::Center,
+                                  Event::SearchHit(CycleDir::Previous));
+        children.push(Box::new(prev_icon) as Box<View>);
+
+        let label = Label::new(rect![rect.min.x + side, rect.min.y,
+                                     rect.max.x - 2 * side, rect.max.y],
+                               results_text(None, 0, false),
+                               Align::Center);
+        children.push(Box::new(label) as Box<View>);
+
+        let next_icon = Icon::new("arrow-right",
+                                  rect![rect.max.x - 2 * side, rect.min.y,
+                                        rect.max.x - side, rect.max.y],
+                                  WHITE,
+                                  Align::Center,
+                                  Event::SearchHit(CycleDir::Next));
+        children.push(Box::new(next_icon) as Box<View>);
+
+        let close_icon = Icon::new("close",
+                                   rect![rect.max - side, rect.max],
+                                   WHITE,
+                                   Align::Center,
+                                   Event::Close(ViewId::SearchBar));
+        children.push(Box::new(close_icon) as Box<View>);
+
+        ResultsBar {
+            rect,
+            children,
+        }
+    }
+
+    pub fn update(&mut self, current: Option<usize>, count: usize, completed: bool, hub: &Hub) {
+        let label = self.child_mut(1).downcast_mut::<Label>().unwrap();
+        label.update(results_text(current, count, completed), hub);
+    }
+}
+
+fn results_text(current: Option<usize>, count: usize, completed: bool) -> String {
+    let suffix = if completed { "" } else { "…" };
+    match current {
+        _ if count == 0 && !completed => "Searching…".to_string(),
+        _ if count == 0 => "No results".to_string(),
+        Some(index) => format!("{} of {}{}", index + 1, count, suffix),
+        None if count == 1 => format!("1 result{}", suffix),
+        None => format!("{} results{}", count, suffix),
+    }
+}
+
+impl View for ResultsBar {
+    fn handle_event(&mut self, evt: &Event, _hub: &Hub, bus: &mut Bus, _context: &mut Context) -> bool {
+        match *evt {
+            Event::Gesture(GestureEvent::Tap { ref center, .. }) if self.child(1).rect().includes(center) => {
+                bus.push_back(Event::Show(ViewId::SearchResults));
+                true
+            },
+            Event::Gesture(GestureEvent::Tap { ref center, .. }) if self.rect.includes(center) => true,
+            Event::Gesture(GestureEvent::Swipe { ref start, .. }) if self.rect.includes(start) => true,
+            _ => false,
+        }
+    }
+
+    fn render(&self, _fb: &mut Framebuffer, _fonts: &mut Fonts) {
+    }
+
+    fn rect(&self) -> &Rectangle {
+        &self.rect
+    }
+
+    fn rect_mut(&mut self) -> &mut Rectangle {
+        &mut self.rect
+    }
+
+    fn children(&self) -> &Vec<Box<View>> {
+        &self.children
+    }
+
+    fn children_mut(&mut self) -> &mut Vec<Box<View>> {
+        &mut self.children
+    }
+}
@@ -0,0 +1,326 @@
+use fnv::FnvHashSet;
+use device::{CURRENT_DEVICE, BAR_SIZES};
+use framebuffer::{Framebuffer, UpdateMode};
+use view::{View, Event, Hub, Bus, ViewId, Align, THICKNESS_MEDIUM};
+use view::icon::{Icon, ICONS_PIXMAPS};
+use view::label::Label;
+use view::filler::Filler;
+use gesture::GestureEvent;
+use document::{TocEntry, chapter_at};
+use font::{Fonts, font_from_style, NORMAL_STYLE};
+use geom::{Rectangle, Dir, CycleDir, halves};
+use color::{BLACK, WHITE, TEXT_NORMAL, TEXT_BUMP_SMALL, SEPARATOR_NORMAL};
+use unit::scale_by_dpi;
+use app::Context;
+
+// The path of an entry is the list of the indices leading to it.
+type TocPath = Vec<usize>;
+
+struct TocLine {
+    path: TocPath,
+    title: String,
+    page: usize,
+    has_children: bool,
+}
+
+pub struct TableOfContents {
+    rect: Rectangle,
+    children: Vec<Box<View>>,
+    toc: Vec<TocEntry>,
+    lines: Vec<TocLine>,
+    expanded: FnvHashSet<TocPath>,
+    chapter: Option<TocPath>,
+    entries_rect: Rectangle,
+    line_height: i32,
+    current_page: usize,
+}
+
+impl TableOfContents {
+    pub fn new(rect: Rectangle, toc: Vec<TocEntry>, current_page: usize) -> TableOfContents {
+        let mut children = Vec::new();
+        let dpi = CURRENT_DEVICE.dpi;
+        let (_, height) = CURRENT_DEVICE.screen_dims();
+        let &(small_height, _) = BAR_SIZES.get(&(height, dpi)).unwrap();
+        let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
+        let (small_thickness, big_thickness) = halves(thickness);
+        let side = small_height as i32 - small_thickness;
+
+        let entries_rect = rect![rect.min.x, rect.min.y + small_height as i32 + big_thickness,
+                                 rect.max.x, rect.max.y - small_height as i32 - small_thickness];
+        let line_height = small_height as i32;
+        let max_lines = ((entries_rect.height() as i32 / line_height) as usize).max(1);
+
+        let chapter = chapter_at(&toc, current_page).and_then(|chap| {
+            find_path(&toc, &|e| e.page == chap.page && e.title == chap.title)
+        });
+
+        // Reveal the current chapter.
+        let mut expanded = FnvHashSet::default();
+        if let Some(ref path) = chapter {
+            for i in 1..path.len() {
+                expanded.insert(path[..i].to_vec());
+            }
+        }
+
+        let mut lines = Vec::new();
+        flatten(&toc, &mut Vec::new(), &expanded, &mut lines);
+
+        let current_page = chapter.as_ref()
+                                  .and_then(|c| lines.iter().position(|l| &l.path == c))
+                                  .map_or(0, |index| index / max_lines);
+
+        let title_label = Label::new(rect![rect.min.x, rect.min.y,
+                                           rect.max.x - side, rect.min.y + side],
+                                     "Table of Contents".to_string(),
+                                     Align::Center);
+        children.push(Box::new(title_label) as Box<View>);
+
+        let close_icon = Icon::new("close",
+                                   rect![rect.max.x - side, rect.min.y,
+                                         rect.max.x, rect.min.y + side],
+                                   WHITE,
+                                   Align::Center,
+                                   Event::Close(ViewId::TableOfContents));
+        children.push(Box::new(close_icon) as Box<View>);
+
+        let separator = Filler::new(rect![rect.min.x, rect.min.y + side,
+                                          rect.max.x, rect.min.y + side + thickness],
+                                    BLACK);
+        children.push(Box::new(separator) as Box<View>);
+
+        let separator = Filler::new(rect![rect.min.x, rect.max.y - small_height as i32 - small_thickness,
+                                          rect.max.x, rect.max.y - small_height as i32 + big_thickness],
+                                    BLACK);
+        children.push(Box::new(separator) as Box<View>);
+
+        let side = small_height as i32 - big_thickness;
+        let bottom_rect = rect![rect.min.x, rect.max.y - side,
+                                rect.max.x, rect.max.y];
+
+        let prev_icon = Icon::new("arrow-left",
+                                  rect![bottom_rect.min, bottom_rect.min + side],
+                                  WHITE,
+                                  Align::Center,
+                                  Event::Page(CycleDir::Previous));
+        children.push(Box::new(prev_icon) as Box<View>);
+
+        let page_label = Label::new(rect![bottom_rect.min.x + side, bottom_rect.min.y,
+                                          bottom_rect.max.x - side, bottom_rect.max.y],
+                                    page_label_text(current_page, pages_count(lines.len(), max_lines)),
+                                    Align::Center);
+        children.push(Box::new(page_label) as Box<View>);
+
+        let next_icon = Icon::new("arrow-right",
+                                  rect![bottom_rect.max - side, bottom_rect.max],
+                                  WHITE,
+                                  Align::Center,
+                                  Event::Page(CycleDir::Next));
+        children.push(Box::new(next_icon) as Box<View>);
+
+        TableOfContents {
+            rect,
+            children,
+            toc,
+            lines,
+            expanded,
+            chapter,
+            entries_rect,
+            line_height,
+            current_page,
+        }
+    }
+
+    fn update_lines(&mut self) {
+        let mut lines = Vec::new();
+        flatten(&self.toc, &mut Vec::new(), &self.expanded, &mut lines);
+        self.lines = lines;
+    }
+
+    fn max_lines(&self) -> usize {
+        ((self.entries_rect.height() as i32 / self.line_height) as usize).max(1)
+    }
+
+    fn pages_count(&self) -> usize {
+        pages_count(self.lines.len(), self.max_lines())
+    }
+
+    fn update_page_label(&mut self, hub: &Hub) {
+        let text = page_label_text(self.current_page, self.pages_count());
+        self.child_mut(5).downcast_mut::<Label>().unwrap().update(text, hub);
+    }
+
+    fn go_to_neighbor(&mut self, dir: CycleDir, hub: &Hub) {
+        let index = match dir {
+            CycleDir::Next if self.current_page < self.pages_count() - 1 => self.current_page + 1,
+            CycleDir::Previous if self.current_page > 0 => self.current_page - 1,
+            _ => return,
+        };
+        self.current_page = index;
+        self.update_page_label(hub);
+        hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+    }
+
+    fn toggle_expanded(&mut self, path: TocPath, hub: &Hub) {
+        if !self.expanded.remove(&path) {
+            self.expanded.insert(path);
+        }
+        self.update_lines();
+        self.current_page = self.current_page.min(self.pages_count() - 1);
+        self.update_page_label(hub);
+        hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+    }
+}
+
+fn pages_count(lines_count: usize, max_lines: usize) -> usize {
+    ((lines_count + max_lines - 1) / max_lines).max(1)
+}
+
+fn page_label_text(current_page: usize, pages_count: usize) -> String {
+    format!("Page {} of {}", current_page + 1, pages_count)
+}
+
+fn find_path<F>(toc: &[TocEntry], predicate: &F) -> Option<TocPath> where F: Fn(&TocEntry) -> bool {
+    for (i, entry) in toc.iter().enumerate() {
+        if predicate(entry) {
+            return Some(vec![i]);
+        }
+        if let Some(mut path) = find_path(&entry.children, predicate) {
+            path.insert(0, i);
+            return Some(path);
+        }
+    }
+    None
+}
+
+fn flatten(toc: &[TocEntry], prefix: &mut TocPath, expanded: &FnvHashSet<TocPath>, lines: &mut Vec<TocLine>) {
+    for (i, entry) in toc.iter().enumerate() {
+        prefix.push(i);
+        lines.push(TocLine {
+            path: prefix.clone(),
+            title: entry.title.clone(),
+            page: entry.page,
+            has_children: !entry.children.is_empty(),
+        });
+        if expanded.contains(prefix) {
+            flatten(&entry.children, prefix, expanded, lines);
+        }
+        prefix.pop();
+    }
+}
+
+impl View for TableOfContents {
+    fn handle_event(&mut self, evt: &Event, hub: &Hub, bus: &mut Bus, _context: &mut Context) -> bool {
+        match *evt {
+            Event::Page(dir) => {
+                self.go_to_neighbor(dir, hub);
+                true
+            },
+            Event::Chapter(..) => true,
+            Event::Gesture(GestureEvent::Swipe { dir, ref start, .. }) if self.entries_rect.includes(start) => {
+                match dir {
+                    Dir::West => self.go_to_neighbor(CycleDir::Next, hub),
+                    Dir::East => self.go_to_neighbor(CycleDir::Previous, hub),
+                    _ => (),
+                }
+                true
+            },
+            Event::Gesture(GestureEvent::Tap { ref center, .. }) if self.entries_rect.includes(center) => {
+                let line = ((center.y - self.entries_rect.min.y) / self.line_height) as usize;
+                let index = self.current_page * self.max_lines() + line;
+                if line < self.max_lines() && index < self.lines.len() {
+                    let indent = self.line_height * self.lines[index].path.len() as i32;
+                    if self.lines[index].has_children && center.x < self.entries_rect.min.x + indent {
+                        let path = self.lines[index].path.clone();
+                        self.toggle_expanded(path, hub);
+                    } else {
+                        bus.push_back(Event::GoTo(self.lines[index].page));
+                        bus.push_back(Event::Close(ViewId::TableOfContents));
+                    }
+                }
+                true
+            },
+            Event::Gesture(GestureEvent::Tap { ref center, .. }) |
+            Event::Gesture(GestureEvent::HoldFinger(ref center)) if self.rect.includes(center) => true,
+            Event::Gesture(GestureEvent::Swipe { ref start, .. }) if self.rect.includes(start) => true,
+            _ => false,
+        }
+    }
+
+    fn render(&self, fb: &mut Framebuffer, fonts: &mut Fonts) {
+        let dpi = CURRENT_DEVICE.dpi;
+        let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
+        let font = font_from_style(fonts, &NORMAL_STYLE, dpi);
+        let x_height = font.x_heights.0 as i32;
+        let padding = font.em() as i32;
+
+        fb.draw_rectangle(&self.entries_rect, TEXT_NORMAL[0]);
+
+        let max_lines = self.max_lines();
+        let start = self.current_page * max_lines;
+        let end = (start + max_lines).min(self.lines.len());
+
+        for (i, line) in self.lines[start..end].iter().enumerate() {
+            let y_min = self.entries_rect.min.y + i as i32 * self.line_height;
+            let line_rect = rect![self.entries_rect.min.x, y_min,
+                                  self.entries_rect.max.x, y_min + self.line_height];
+            let scheme = if self.chapter.as_ref() == Some(&line.path) {
+                TEXT_BUMP_SMALL
+            } else {
+                TEXT_NORMAL
+            };
+
+            fb.draw_rectangle(&line_rect, scheme[0]);
+
+            let dy = (self.line_height - x_height) / 2;
+            let baseline = line_rect.max.y - dy;
+            let indent = self.line_height * line.path.len() as i32;
+
+            if line.has_children {
+                let name = if self.expanded.contains(&line.path) {
+                    "angle-down"
+                } else {
+                    "angle-right-small"
+                };
+                let pixmap = ICONS_PIXMAPS.get(name).unwrap();
+                let pt = pt!(line_rect.min.x + indent - (self.line_height + pixmap.width) / 2,
+                             line_rect.min.y + (self.line_height - pixmap.height) / 2);
+                fb.draw_blended_pixmap(pixmap, &pt, scheme[1]);
+            }
+
+            let page_plan = font.plan(&format!("{}", line.page + 1), None, None);
+            let pt = pt!(line_rect.max.x - padding - page_plan.width as i32, baseline);
+            font.render(fb, scheme[1], &page_plan, &pt);
+
+            let max_width = line_rect.width() as i32 - indent - 3 * padding - page_plan.width as i32;
+            let plan = font.plan(&line.title, Some(max_width.max(0) as u32), None);
+            let pt = pt!(line_rect.min.x + indent, baseline);
+            font.render(fb, scheme[1], &plan, &pt);
+
+            if i < max_lines - 1 {
+                fb.draw_rectangle(&rect![line_rect.min.x + padding, line_rect.max.y - thickness / 2,
+                                         line_rect.max.x - padding, line_rect.max.y - thickness / 2 + 1],
+                                  SEPARATOR_NORMAL);
+            }
+        }
+    }
+
+    fn id(&self) -> Option<ViewId> {
+        Some(ViewId::TableOfContents)
+    }
+
+    fn rect(&self) -> &Rectangle {
+        &self.rect
+    }
+
+    fn rect_mut(&mut self) -> &mut Rectangle {
+        &mut self.rect
+    }
+
+    fn children(&self) -> &Vec<Box<View>> {
+        &self.children
+    }
+
+    fn children_mut(&mut self) -> &mut Vec<Box<View>> {
+        &mut self.children
+    }
+}
@@ -29,7 +29,7 @@ impl ToolBar {
             children.push(Box::new(font_size_icon) as Box<View>);
 
             let slider = Slider::new(rect![rect.min.x + side, rect.min.y,
-                                           rect.max.x - side, rect.max.y],
+                                           rect.max.x - 2 * side, rect.max.y],
                                      SliderId::FontSize,
                                      font_size,
                                      DEFAULT_FONT_SIZE / 2.0,
@@ -43,12 +43,37 @@ impl ToolBar {
                                       Event::Show(ViewId::MarginCropper));
             children.push(Box::new(crop_icon) as Box<View>);
 
-            let filler = Filler::new(rect![rect.min.x + side, rect.min.y,
-                                           rect.max.x - side, rect.max.y],
+            let zoom_rect = rect![rect.min.x + side, rect.min.y,
+                                  rect.min.x + 2 * side, rect.max.y];
+            let zoom_icon = Icon::new("zoom",
+                                      zoom_rect,
+                                      WHITE,
+                                      Align::Center,
+                                      Event::ToggleNear(ViewId::ZoomMenu, zoom_rect));
+            children.push(Box::new(zoom_icon) as Box<View>);
+
+            let filler = Filler::new(rect![rect.min.x + 2 * side, rect.min.y,
+                                           rect.max.x - 3 * side, rect.max.y],
                                      WHITE);
             children.push(Box::new(filler) as Box<View>);
+
+            let overview_icon = Icon::new("thumbnails",
+                                          rect![rect.max.x - 3 * side, rect.min.y,
+                                                rect.max.x - 2 * side, rect.max.y],
+                                          WHITE,
+                                          Align::Center,
+                                          Event::Show(ViewId::PageOverview));
+            children.push(Box::new(overview_icon) as Box<View>);
         }
 
+        let search_icon = Icon::new("search",
+                                    rect![rect.max.x - 2 * side, rect.min.y,
+                                          rect.max.x - side, rect.max.y],
+                                    WHITE,
+                                    Align::Center,
+                                    Event::Toggle(ViewId::SearchBar));
+        children.push(Box::new(search_icon) as Box<View>);
+
         let toc_icon = Icon::new("toc",
                                  rect![rect.max - pt!(side), rect.max],
                                  WHITE,
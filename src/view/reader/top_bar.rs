@@ -31,8 +31,24 @@ impl TopBar {
         let clock_label = Clock::new(&mut clock_rect, fonts);
         children.push(Box::new(clock_label) as Box<View>);
 
+        let bookmarks_icon = Icon::new("bookmark",
+                                       rect![clock_rect.min.x - side, rect.min.y,
+                                             clock_rect.min.x, rect.max.y],
+                                       WHITE,
+                                       Align::Center,
+                                       Event::Show(ViewId::Bookmarks));
+        children.push(Box::new(bookmarks_icon) as Box<View>);
+
+        let annotations_icon = Icon::new("annotation",
+                                         rect![clock_rect.min.x - 2 * side, rect.min.y,
+                                               clock_rect.min.x - side, rect.max.y],
+                                         WHITE,
+                                         Align::Center,
+                                         Event::Show(ViewId::Annotations));
+        children.push(Box::new(annotations_icon) as Box<View>);
+
         let title_label = Label::new(rect![rect.min.x + side, rect.min.y,
-                                           clock_rect.min.x, rect.max.y],
+                                           clock_rect.min.x - 2 * side, rect.max.y],
                                      info.title(),
                                      Align::Center);
         children.push(Box::new(title_label) as Box<View>);
@@ -1,43 +1,128 @@
-use std::rc::Rc;
+use std::sync::Arc;
 use framebuffer::{Framebuffer, UpdateMode, Pixmap};
-use view::{View, Event, Hub, Bus, ViewId, EntryId};
+use view::{View, Event, Hub, Bus, ViewId, THICKNESS_MEDIUM};
+use document::{Link, LinkTarget};
 use gesture::GestureEvent;
-use geom::{Rectangle, Dir, CycleDir};
-use color::WHITE;
+use geom::{Point, Rectangle, BorderSpec, Dir, CycleDir};
+use color::{BLACK, WHITE};
 use app::Context;
 use font::Fonts;
+use device::CURRENT_DEVICE;
+use unit::scale_by_dpi;
+
+const HIGHLIGHT_ALPHA: f32 = 0.3;
+const SELECTION_ALPHA: f32 = 0.5;
+const BOOKMARK_SIZE: f32 = 42.0;
 
 pub struct Viewer {
     rect: Rectangle,
     children: Vec<Box<View>>,
     frame: Rectangle,
-    pixmap: Rc<Pixmap>,
+    pixmap: Arc<Pixmap>,
+    scale: f32,
+    search_rects: Vec<Rectangle>,
+    annotation_rects: Vec<Rectangle>,
+    selection_rects: Vec<Rectangle>,
+    links: Vec<Link>,
+    bookmarked: bool,
     update_mode: UpdateMode,
 }
 
 impl Viewer {
-    pub fn new(rect: Rectangle, pixmap: Rc<Pixmap>, frame: Rectangle, update_mode: UpdateMode)-> Viewer {
+    pub fn new(rect: Rectangle, pixmap: Arc<Pixmap>, frame: Rectangle, scale: f32, update_mode: UpdateMode)-> Viewer {
         Viewer {
             rect,
             children: vec![],
             frame,
             pixmap,
+            scale,
+            search_rects: vec![],
+            annotation_rects: vec![],
+            selection_rects: vec![],
+            links: vec![],
+            bookmarked: false,
             update_mode,
         }
     }
 
-    pub fn update(&mut self, pixmap: Rc<Pixmap>, frame: Rectangle, update_mode: UpdateMode, hub: &Hub) {
+    pub fn update(&mut self, pixmap: Arc<Pixmap>, frame: Rectangle, scale: f32, update_mode: UpdateMode, hub: &Hub) {
         self.pixmap = pixmap;
         self.frame = frame;
+        self.scale = scale;
         self.update_mode = update_mode;
         hub.send(Event::Render(self.rect, update_mode)).unwrap();
     }
+
+    // The rectangles of the following setters are given in page coordinates.
+    pub fn set_search_rects(&mut self, rects: Vec<Rectangle>) {
+        self.search_rects = rects;
+    }
+
+    pub fn set_annotation_rects(&mut self, rects: Vec<Rectangle>) {
+        self.annotation_rects = rects;
+    }
+
+    pub fn set_selection_rects(&mut self, rects: Vec<Rectangle>) {
+        self.selection_rects = rects;
+    }
+
+    pub fn set_links(&mut self, links: Vec<Link>) {
+        self.links = links;
+    }
+
+    pub fn set_bookmarked(&mut self, bookmarked: bool) {
+        self.bookmarked = bookmarked;
+    }
+
+    fn bookmark_rect(&self) -> Rectangle {
+        let side = scale_by_dpi(BOOKMARK_SIZE, CURRENT_DEVICE.dpi) as i32;
+        rect![self.rect.max.x - side, self.rect.min.y,
+              self.rect.max.x, self.rect.min.y + side]
+    }
+
+    fn link_at(&self, pt: &Point) -> Option<&Link> {
+        let pt = self.to_page(pt);
+        self.links.iter().find(|link| link.rect.includes(&pt))
+    }
+
+    fn offset(&self) -> Point {
+        let dx = (self.rect.width() - self.frame.width()) as i32 / 2;
+        let dy = (self.rect.height() - self.frame.height()) as i32 / 2;
+        pt!(dx, dy)
+    }
+
+    pub fn to_screen(&self, rect: &Rectangle) -> Rectangle {
+        rect.scale(self.scale) - self.frame.min + self.offset()
+    }
+
+    pub fn to_page(&self, pt: &Point) -> Point {
+        let pt = *pt + self.frame.min - self.offset();
+        pt!((pt.x as f32 / self.scale) as i32,
+            (pt.y as f32 / self.scale) as i32)
+    }
 }
 
 impl View for Viewer {
-    fn handle_event(&mut self, evt: &Event, hub: &Hub, bus: &mut Bus, _context: &mut Context) -> bool {
+    fn handle_event(&mut self, evt: &Event, _hub: &Hub, bus: &mut Bus, _context: &mut Context) -> bool {
         match *evt {
-            Event::Gesture(GestureEvent::Swipe { dir, ref start, .. }) if self.rect.includes(start) => {
+            Event::Gesture(GestureEvent::Swipe { dir, ref start, fingers_count: 2, .. }) if self.rect.includes(start) => {
+                match dir {
+                    Dir::West => {
+                        bus.push_back(Event::Bookmark(CycleDir::Next));
+                    },
+                    Dir::East => {
+                        bus.push_back(Event::Bookmark(CycleDir::Previous));
+                    },
+                    Dir::North => {
+                        bus.push_back(Event::History(CycleDir::Previous));
+                    },
+                    Dir::South => {
+                        bus.push_back(Event::History(CycleDir::Next));
+                    },
+                };
+                true
+            },
+            Event::Gesture(GestureEvent::Swipe { dir, ref start, ref end, .. }) if self.rect.includes(start) => {
                 match dir {
                     Dir::West => {
                         bus.push_back(Event::Page(CycleDir::Next));
@@ -45,10 +130,13 @@ impl View for Viewer {
                     Dir::East => {
                         bus.push_back(Event::Page(CycleDir::Previous));
                     },
-                    _ => (),
+                    _ => {
+                        bus.push_back(Event::Scroll(*start - *end));
+                    },
                 };
                 true
             },
+            // Holds in the middle third are left to the reader: they select text.
             Event::Gesture(GestureEvent::HoldFinger(ref center)) if self.rect.includes(center) => {
                 let w = self.rect.width() as i32;
                 let x1 = self.rect.min.x + w / 3;
@@ -58,7 +146,19 @@ impl View for Viewer {
                 } else if center.x > x2 {
                     bus.push_back(Event::Chapter(CycleDir::Next));
                 } else {
-                    hub.send(Event::Select(EntryId::TakeScreenshot)).unwrap();
+                    return false;
+                }
+                true
+            },
+            Event::Gesture(GestureEvent::Tap { ref center, .. }) if self.bookmark_rect().includes(center) => {
+                bus.push_back(Event::ToggleBookmark);
+                true
+            },
+            Event::Gesture(GestureEvent::Tap { ref center, .. }) if self.rect.includes(center) &&
+                                                                    self.link_at(center).is_some() => {
+                match self.link_at(center).unwrap().target {
+                    LinkTarget::Page(index) => bus.push_back(Event::GoTo(index)),
+                    LinkTarget::Uri(ref uri) => bus.push_back(Event::Notify(uri.clone())),
                 }
                 true
             },
@@ -80,10 +180,35 @@ impl View for Viewer {
     }
 
     fn render(&self, fb: &mut Framebuffer, _fonts: &mut Fonts) {
-        let dx = (self.rect.width() - self.frame.width()) as i32 / 2;
-        let dy = (self.rect.height() - self.frame.height()) as i32 / 2;
         fb.draw_rectangle(&self.rect, WHITE);
-        fb.draw_framed_pixmap(&self.pixmap, &self.frame, &pt!(dx, dy));
+        fb.draw_framed_pixmap(&self.pixmap, &self.frame, &self.offset());
+        for rect in &self.annotation_rects {
+            if let Some(ref rect) = self.to_screen(rect).intersection(&self.rect) {
+                fb.draw_blended_rectangle(rect, BLACK, HIGHLIGHT_ALPHA);
+            }
+        }
+        for rect in &self.selection_rects {
+            if let Some(ref rect) = self.to_screen(rect).intersection(&self.rect) {
+                fb.draw_blended_rectangle(rect, BLACK, SELECTION_ALPHA);
+            }
+        }
+        let border = BorderSpec { thickness: scale_by_dpi(THICKNESS_MEDIUM, CURRENT_DEVICE.dpi) as u16,
+                                  color: BLACK };
+        for rect in &self.search_rects {
+            if let Some(ref rect) = self.to_screen(rect).intersection(&self.rect) {
+                fb.draw_rectangle_outline(rect, &border);
+            }
+        }
+        if self.bookmarked {
+            // The dog-ear covers the upper right half of the corner's touch area.
+            let rect = self.bookmark_rect();
+            let side = rect.height() as i32 / 2;
+            for i in 0..side {
+                fb.draw_rectangle(&rect![rect.max.x - side + i, rect.min.y + i,
+                                         rect.max.x, rect.min.y + i + 1],
+                                  BLACK);
+            }
+        }
     }
 
     fn rect(&self) -> &Rectangle {
@@ -17,7 +17,7 @@ pub struct SearchBar {
 }
 
 impl SearchBar {
-    pub fn new(rect: Rectangle) -> SearchBar {
+    pub fn new(rect: Rectangle, placeholder: &str) -> SearchBar {
         let mut children = Vec::new();
         let dpi = CURRENT_DEVICE.dpi;
         let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
@@ -41,7 +41,7 @@ impl SearchBar {
                                                 pt!(rect.max.x - side - thickness, rect.max.y)],
                                           ViewId::SearchInput,
                                           false,
-                                          Some("Title, author, category"));
+                                          Some(placeholder));
 
         children.push(Box::new(input_field) as Box<View>);
 
@@ -65,6 +65,11 @@ impl SearchBar {
             children,
         }
     }
+
+    pub fn set_text(&mut self, text: &str) {
+        let input_field = self.children[2].as_mut().downcast_mut::<InputField>().unwrap();
+        input_field.set_text(text);
+    }
 }
 
 impl View for SearchBar {
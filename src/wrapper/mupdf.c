@@ -13,3 +13,5 @@ WRAP(load_page, fz_page*, NULL, fz_load_page(ctx, doc, pageno), fz_document *doc
 WRAP(load_outline, fz_outline*, NULL, fz_load_outline(ctx, doc), fz_document *doc)
 WRAP(count_pages, int, -1, fz_count_pages(ctx, doc), fz_document *doc)
 WRAP(new_stext_page_from_page, fz_stext_page*, NULL, fz_new_stext_page_from_page(ctx, page, options), fz_page *page, fz_stext_options *options)
+WRAP(load_links, fz_link*, NULL, fz_load_links(ctx, page), fz_page *page)
+WRAP(resolve_link, int, -1, fz_resolve_link(ctx, doc, uri, xp, yp), fz_document *doc, const char *uri, float *xp, float *yp)